@@ -1,9 +1,12 @@
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, sync::Arc};
 
 use hardware::Hardware;
 
+use crate::config::utils::hardware_id::{rebind_hardware, Binding};
 use crate::config::Config;
-use crate::config::{control::Control, fan::Fan, temp::Temp};
+use crate::config::{
+    control::Control, custom_temp::CustomTemp, fan::Fan, filter::Filter, temp::Temp,
+};
 
 use crate::id::{Id, IdGenerator};
 use crate::node::{self, Node, NodeType, NodeTypeLight, ToNode};
@@ -12,11 +15,35 @@ use crate::utils::RemoveElem;
 pub type Nodes = BTreeMap<Id, Node>;
 pub type RootNodes = Vec<Id>;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct AppGraph {
     pub nodes: Nodes,
     pub id_generator: IdGenerator,
     pub root_nodes: RootNodes,
+    /// Nodes whose hardware was found with a legacy id when loading the config.
+    pub rebound: Vec<Rebound>,
+}
+
+/// A node from the config which was bound to its hardware using a legacy id,
+/// and now use the current id of this hardware.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rebound {
+    pub node_name: String,
+    pub previous_id: String,
+    pub new_id: String,
+}
+
+/// Nodes whose hardware appeared or disappeared after a rescan.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HardwareChanges {
+    pub bound: Vec<String>,
+    pub unbound: Vec<String>,
+}
+
+impl HardwareChanges {
+    pub fn is_empty(&self) -> bool {
+        self.bound.is_empty() && self.unbound.is_empty()
+    }
 }
 
 impl AppGraph {
@@ -25,9 +52,87 @@ impl AppGraph {
             nodes: Nodes::new(),
             id_generator: IdGenerator::new(),
             root_nodes: Vec::new(),
+            rebound: Vec::new(),
         }
     }
 
+    pub fn add_rebound(&mut self, node_name: &str, previous_id: &str, new_id: &str) {
+        info!(
+            "{}: hardware id \"{}\" is now \"{}\".",
+            node_name, previous_id, new_id
+        );
+        self.rebound.push(Rebound {
+            node_name: node_name.to_owned(),
+            previous_id: previous_id.to_owned(),
+            new_id: new_id.to_owned(),
+        });
+    }
+
+    /// Bind the nodes to the hardware after a rescan.
+    pub fn rebind_hardware(&mut self, hardware: &Hardware) -> HardwareChanges {
+        let mut changes = HardwareChanges::default();
+
+        let mut used_controls = self
+            .nodes
+            .values()
+            .filter_map(|node| match &node.node_type {
+                NodeType::Control(control) => control.control_h.clone(),
+                _ => None,
+            })
+            // the stale handles are unbound below
+            .filter(|control_h| hardware.controls.iter().any(|h| Arc::ptr_eq(h, control_h)))
+            .collect::<Vec<_>>();
+
+        for node in self.nodes.values_mut() {
+            let binding = match &mut node.node_type {
+                NodeType::Control(control) => {
+                    let binding = rebind_hardware(
+                        &control.hardware_id,
+                        &mut control.control_h,
+                        &hardware.controls,
+                        |h| {
+                            used_controls
+                                .iter()
+                                .any(|used| used.hardware_id == h.hardware_id)
+                        },
+                    );
+                    if binding.is_some() {
+                        control.mode_set = None;
+                        control.output_set = None;
+                    }
+                    if let (Some(Binding::Bound), Some(control_h)) = (binding, &control.control_h) {
+                        used_controls.push(control_h.clone());
+                    }
+                    binding
+                }
+                NodeType::Fan(fan) => {
+                    rebind_hardware(&fan.hardware_id, &mut fan.fan_h, &hardware.fans, |_| false)
+                }
+                NodeType::Temp(temp) => {
+                    rebind_hardware(&temp.hardware_id, &mut temp.temp_h, &hardware.temps, |_| {
+                        false
+                    })
+                }
+                _ => None,
+            };
+
+            match binding {
+                Some(Binding::Bound) => {
+                    info!("{} is bound to its hardware again.", node.name());
+                    changes.bound.push(node.name().clone());
+                }
+                Some(Binding::Unbound) => {
+                    warn!("The hardware of {} disappeared.", node.name());
+                    node.value = None;
+                    changes.unbound.push(node.name().clone());
+                }
+                None => {}
+            }
+        }
+
+        changes
+    }
+
     pub fn insert_node(&mut self, node: Node) {
         if node.is_root() {
             self.root_nodes.push(node.id);
@@ -67,6 +172,7 @@ impl AppGraph {
                 name: fan_h.name.clone(),
                 hardware_id: Some(fan_h.hardware_id.clone()),
                 fan_h: Some(fan_h.clone()),
+                ..Default::default()
             };
 
             let node = Node::new(NodeType::Fan(fan), &mut app_graph);
@@ -78,6 +184,7 @@ impl AppGraph {
                 name: temp_h.name.clone(),
                 hardware_id: Some(temp_h.hardware_id.clone()),
                 temp_h: Some(temp_h.clone()),
+                ..Default::default()
             };
 
             let node = Node::new(NodeType::Temp(temp), &mut app_graph);
@@ -90,7 +197,7 @@ impl AppGraph {
     pub fn from_config(config: Config, hardware: &Hardware) -> Self {
         let mut app_graph = AppGraph::new();
 
-        // order: fan -> temp -> custom_temp -> behavior -> control
+        // order: fan -> temp -> command_temp -> file_temp -> filter and custom_temp -> behavior -> mix -> control
 
         for fan in config.fans {
             let node = fan.to_node(&mut app_graph, hardware);
@@ -102,8 +209,40 @@ impl AppGraph {
             app_graph.insert_node(node);
         }
 
-        for custom_temp in config.custom_temps {
-            let node = custom_temp.to_node(&mut app_graph, hardware);
+        for command_temp in config.command_temps {
+            let node = command_temp.to_node(&mut app_graph, hardware);
+            app_graph.insert_node(node);
+        }
+
+        for file_temp in config.file_temps {
+            let node = file_temp.to_node(&mut app_graph, hardware);
+            app_graph.insert_node(node);
+        }
+
+        // filters and custom temps can use each other,
+        // so each one is inserted after its inputs
+        let mut pending = config
+            .filters
+            .into_iter()
+            .map(TempNode::Filter)
+            .chain(config.custom_temps.into_iter().map(TempNode::CustomTemp))
+            .collect::<Vec<_>>();
+        while !pending.is_empty() {
+            let index = pending
+                .iter()
+                .position(|temp_node| {
+                    temp_node
+                        .inputs()
+                        .iter()
+                        .all(|input| pending.iter().all(|other| other.name() != input))
+                })
+                // a cycle, its inputs are missing anyway
+                .unwrap_or(0);
+
+            let node = match pending.remove(index) {
+                TempNode::Filter(filter) => filter.to_node(&mut app_graph, hardware),
+                TempNode::CustomTemp(custom_temp) => custom_temp.to_node(&mut app_graph, hardware),
+            };
             app_graph.insert_node(node);
         }
 
@@ -122,11 +261,21 @@ impl AppGraph {
             app_graph.insert_node(node);
         }
 
+        for pid in config.pids {
+            let node = pid.to_node(&mut app_graph, hardware);
+            app_graph.insert_node(node);
+        }
+
         for graph in config.graphs {
             let node = graph.to_node(&mut app_graph, hardware);
             app_graph.insert_node(node);
         }
 
+        for mix in config.mixes {
+            let node = mix.to_node(&mut app_graph, hardware);
+            app_graph.insert_node(node);
+        }
+
         for control in config.controls {
             let node = control.to_node(&mut app_graph, hardware);
             app_graph.insert_node(node);
@@ -149,11 +298,16 @@ impl AppGraph {
             NodeTypeLight::Control => fl!("default_control"),
             NodeTypeLight::Fan => fl!("default_fan"),
             NodeTypeLight::Temp => fl!("default_temp"),
+            NodeTypeLight::CommandTemp => fl!("default_command_temp"),
+            NodeTypeLight::FileTemp => fl!("default_file_temp"),
             NodeTypeLight::CustomTemp => fl!("default_custom_temp"),
+            NodeTypeLight::Filter => fl!("default_filter"),
             NodeTypeLight::Graph => fl!("default_graph"),
             NodeTypeLight::Flat => fl!("default_flat"),
             NodeTypeLight::Linear => fl!("default_linear"),
             NodeTypeLight::Target => fl!("default_target"),
+            NodeTypeLight::Pid => fl!("default_pid"),
+            NodeTypeLight::Mix => fl!("default_mix"),
         };
 
         Self::find_unused_name(&self.nodes, &default_name, 1)
@@ -177,11 +331,16 @@ impl AppGraph {
             NodeTypeLight::Control => NodeType::Control(Default::default()),
             NodeTypeLight::Fan => NodeType::Fan(Default::default()),
             NodeTypeLight::Temp => NodeType::Temp(Default::default()),
+            NodeTypeLight::CommandTemp => NodeType::CommandTemp(Default::default()),
+            NodeTypeLight::FileTemp => NodeType::FileTemp(Default::default()),
             NodeTypeLight::CustomTemp => NodeType::CustomTemp(Default::default()),
+            NodeTypeLight::Filter => NodeType::Filter(Default::default()),
             NodeTypeLight::Graph => NodeType::Graph(Default::default()),
             NodeTypeLight::Flat => NodeType::Flat(Default::default()),
             NodeTypeLight::Linear => NodeType::Linear(Default::default()),
             NodeTypeLight::Target => NodeType::Target(Default::default()),
+            NodeTypeLight::Pid => NodeType::Pid(Default::default()),
+            NodeTypeLight::Mix => NodeType::Mix(Default::default()),
         };
 
         let new_name = self.generate_default_name(node_type_light);
@@ -215,3 +374,247 @@ impl AppGraph {
             .unwrap_or_else(|| panic!("can't find node {id} as ref mut in nodes"))
     }
 }
+
+enum TempNode {
+    Filter(Filter),
+    CustomTemp(CustomTemp),
+}
+
+impl TempNode {
+    fn name(&self) -> &String {
+        match self {
+            TempNode::Filter(filter) => &filter.name,
+            TempNode::CustomTemp(custom_temp) => &custom_temp.name,
+        }
+    }
+
+    fn inputs(&self) -> &[String] {
+        match self {
+            TempNode::Filter(filter) => filter.input.as_slice(),
+            TempNode::CustomTemp(custom_temp) => &custom_temp.inputs,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use hardware::{
+        snapshot::{HardwareSnapshot, SnapshotBridge},
+        HardwareBridge,
+    };
+
+    use super::{AppGraph, HardwareChanges, Rebound};
+    use crate::{
+        config::{control::Control, temp::Temp, Config},
+        node::NodeType,
+    };
+
+    fn bridge(content: &str) -> SnapshotBridge {
+        SnapshotBridge::from_snapshot(HardwareSnapshot::from_str(content).unwrap())
+    }
+
+    #[test]
+    fn test_rebind_hardware() {
+        let before = bridge(
+            r#"
+[[Temp]]
+name = "a"
+id = "a"
+"#,
+        );
+        let after = bridge(
+            r#"
+[[Temp]]
+name = "b"
+id = "b"
+"#,
+        );
+
+        let temp = |name: &str| Temp {
+            name: name.into(),
+            hardware_id: Some(name.into()),
+            ..Default::default()
+        };
+
+        let config = Config {
+            temps: vec![temp("a")],
+            ..Default::default()
+        };
+
+        let mut app_graph = AppGraph::from_config(config, before.hardware());
+        assert!(app_graph.rebind_hardware(before.hardware()).is_empty());
+
+        let changes = app_graph.rebind_hardware(after.hardware());
+        assert_eq!(
+            changes,
+            HardwareChanges {
+                bound: vec![],
+                unbound: vec!["a".into()],
+            }
+        );
+
+        // the id is kept
+        let changes = app_graph.rebind_hardware(before.hardware());
+        assert_eq!(
+            changes,
+            HardwareChanges {
+                bound: vec!["a".into()],
+                unbound: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn test_rebind_legacy_ids() {
+        let bridge = bridge(
+            r#"
+[[Control]]
+name = "pwm1"
+id = "platform/nct6775.656/nct6775/pwm1"
+legacyIds = ["pwm1-nct6775-isa-0290-pwm1"]
+
+[[Temp]]
+name = "SYSTIN"
+id = "platform/nct6775.656/nct6775/temp1_input"
+legacyIds = ["SYSTIN-nct6775-isa-0290-temp1_input"]
+"#,
+        );
+
+        let config = Config {
+            temps: vec![Temp {
+                name: "temp".into(),
+                hardware_id: Some("SYSTIN-nct6775-isa-0290-temp1_input".into()),
+                ..Default::default()
+            }],
+            controls: vec![Control::new(
+                "control".into(),
+                Some("pwm1-nct6775-isa-0290-pwm1".into()),
+                None,
+                true,
+                None,
+            )],
+            ..Default::default()
+        };
+
+        let app_graph = AppGraph::from_config(config, bridge.hardware());
+
+        assert_eq!(
+            app_graph.rebound,
+            vec![
+                Rebound {
+                    node_name: "temp".into(),
+                    previous_id: "SYSTIN-nct6775-isa-0290-temp1_input".into(),
+                    new_id: "platform/nct6775.656/nct6775/temp1_input".into(),
+                },
+                Rebound {
+                    node_name: "control".into(),
+                    previous_id: "pwm1-nct6775-isa-0290-pwm1".into(),
+                    new_id: "platform/nct6775.656/nct6775/pwm1".into(),
+                },
+            ]
+        );
+
+        for node in app_graph.nodes.values() {
+            match &node.node_type {
+                NodeType::Control(control) => {
+                    assert!(control.control_h.is_some());
+                    assert_eq!(
+                        control.hardware_id.as_deref(),
+                        Some("platform/nct6775.656/nct6775/pwm1")
+                    );
+                }
+                NodeType::Temp(temp) => {
+                    assert!(temp.temp_h.is_some());
+                    assert_eq!(
+                        temp.hardware_id.as_deref(),
+                        Some("platform/nct6775.656/nct6775/temp1_input")
+                    );
+                }
+                _ => {}
+            }
+        }
+
+        // the new ids are written in the config
+        let config = Config::from_app_graph(&app_graph);
+        assert_eq!(
+            config.temps[0].hardware_id.as_deref(),
+            Some("platform/nct6775.656/nct6775/temp1_input")
+        );
+    }
+
+    #[test]
+    fn test_hardware_missing_at_startup() {
+        let without = bridge("");
+        let with = || {
+            bridge(
+                r#"
+[[Temp]]
+name = "liquid"
+id = "aio/temp1_input"
+
+[[Control]]
+name = "pump"
+id = "aio/pwm1"
+"#,
+            )
+        };
+
+        let config = Config {
+            temps: vec![Temp {
+                name: "liquid".into(),
+                hardware_id: Some("aio/temp1_input".into()),
+                ..Default::default()
+            }],
+            controls: vec![Control::new(
+                "pump".into(),
+                Some("aio/pwm1".into()),
+                None,
+                true,
+                None,
+            )],
+            ..Default::default()
+        };
+
+        let mut app_graph = AppGraph::from_config(config, without.hardware());
+
+        // the ids are kept, in the graph and in the saved config
+        let config = Config::from_app_graph(&app_graph);
+        assert_eq!(
+            config.temps[0].hardware_id.as_deref(),
+            Some("aio/temp1_input")
+        );
+        assert_eq!(config.controls[0].hardware_id.as_deref(), Some("aio/pwm1"));
+
+        let changes = app_graph.rebind_hardware(with().hardware());
+        assert_eq!(
+            changes,
+            HardwareChanges {
+                bound: vec!["liquid".into(), "pump".into()],
+                unbound: vec![],
+            }
+        );
+
+        // a driver reload gives new handles, bound in the same rescan
+        let reloaded = with();
+        app_graph.rebind_hardware(reloaded.hardware());
+        for node in app_graph.nodes.values() {
+            match &node.node_type {
+                NodeType::Control(control) => {
+                    assert!(Arc::ptr_eq(
+                        control.control_h.as_ref().unwrap(),
+                        &reloaded.hardware().controls[0]
+                    ));
+                }
+                NodeType::Temp(temp) => {
+                    assert!(Arc::ptr_eq(
+                        temp.temp_h.as_ref().unwrap(),
+                        &reloaded.hardware().temps[0]
+                    ));
+                }
+                _ => {}
+            }
+        }
+    }
+}
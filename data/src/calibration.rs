@@ -0,0 +1,197 @@
+use std::time::Duration;
+
+use hardware::{HSensor, HardwareBridge, Mode, Value};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    config::control::{Control, OutputMapping},
+    update::UpdateError,
+};
+
+#[derive(Error, Debug)]
+pub enum CalibrationError {
+    #[error(transparent)]
+    Update(#[from] UpdateError),
+    #[error(transparent)]
+    Hardware(#[from] hardware::HardwareError),
+    #[error("Control {0} has no hardware")]
+    NoControl(String),
+    #[error("The fan never spinned")]
+    NeverSpinned,
+}
+
+type Result<T> = std::result::Result<T, CalibrationError>;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct CalibrationPoint {
+    pub duty: u8,
+    pub rpm: Value,
+}
+
+/// Measured relation between the duty of a control and the speed of its fan.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Calibration {
+    /// Lowest duty where the fan keeps spinning
+    pub stall: u8,
+    /// Lowest duty which starts the fan when it is stopped
+    #[serde(rename = "spinUp", alias = "spin_up")]
+    pub spin_up: u8,
+    /// Measured while decreasing the duty
+    pub curve: Vec<CalibrationPoint>,
+}
+
+impl Calibration {
+    pub fn max_rpm(&self) -> Value {
+        self.curve.iter().map(|p| p.rpm).fold(0.0, Value::max)
+    }
+
+    /// Output mapping which keeps the fan spinning, unless 0 is requested.
+    pub fn output_mapping(&self, previous: &OutputMapping) -> OutputMapping {
+        OutputMapping {
+            min_start: self.spin_up,
+            min_duty: self.stall.min(previous.max_duty),
+            ..previous.clone()
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CalibrationSettings {
+    /// Difference of duty between two measures
+    pub step: u8,
+    /// Time to wait after a change of duty
+    pub settle_delay: Duration,
+    /// Two consecutive measures closer than this are considered stable
+    pub tolerance: Value,
+    /// Maximum number of measures for each duty
+    pub max_measures: u8,
+}
+
+impl Default for CalibrationSettings {
+    fn default() -> Self {
+        Self {
+            step: 5,
+            settle_delay: Duration::from_secs(3),
+            tolerance: 30.0,
+            max_measures: 5,
+        }
+    }
+}
+
+/// Step the duty of `control` and measure the speed of `fan`.
+///
+/// `wait` is called each time we need to wait for the fan. It should also
+/// call [`HardwareBridge::update`].
+/// The previous mode of the control is restored at the end, even on error.
+/// A failure to restore it is logged, so the calibration is not lost.
+pub fn calibrate<H: HardwareBridge>(
+    control: &mut Control,
+    fan: &HSensor,
+    bridge: &mut H,
+    settings: &CalibrationSettings,
+    mut wait: impl FnMut(&mut H, Duration),
+) -> Result<Calibration> {
+    let previous_mode = control.mode_set.clone().unwrap_or(Mode::Auto);
+    let previous_value = control.output_set;
+
+    let res = calibrate_inner(control, fan, bridge, settings, &mut wait);
+
+    info!("Restoring mode {} for {}.", previous_mode, control.name);
+    if let Err(e) = restore(control, bridge, previous_mode, previous_value) {
+        error!("Can't restore the mode of {}: {}.", control.name, e);
+    }
+
+    res
+}
+
+fn restore<H: HardwareBridge>(
+    control: &mut Control,
+    bridge: &mut H,
+    mode: Mode,
+    value: Option<Value>,
+) -> Result<()> {
+    control.set_mode(mode.clone(), bridge)?;
+    if let (Mode::Manual, Some(value), Some(control_h)) = (mode, value, &control.control_h) {
+        bridge.set_value(control_h, value)?;
+        control.output_set = Some(value);
+    }
+    Ok(())
+}
+
+fn calibrate_inner<H: HardwareBridge>(
+    control: &mut Control,
+    fan: &HSensor,
+    bridge: &mut H,
+    settings: &CalibrationSettings,
+    wait: &mut impl FnMut(&mut H, Duration),
+) -> Result<Calibration> {
+    let Some(control_h) = control.control_h.clone() else {
+        return Err(CalibrationError::NoControl(control.name.clone()));
+    };
+
+    control.set_mode(Mode::Manual, bridge)?;
+
+    let mut measure = |bridge: &mut H, duty: u8| -> Result<Value> {
+        bridge.set_value(&control_h, duty.into())?;
+
+        let mut previous: Option<Value> = None;
+        for _ in 0..settings.max_measures.max(1) {
+            wait(bridge, settings.settle_delay);
+            let rpm = bridge.get_sensor_value(fan)?;
+
+            if let Some(previous) = previous {
+                if (rpm - previous).abs() <= settings.tolerance {
+                    return Ok(rpm);
+                }
+            }
+            previous = Some(rpm);
+        }
+        Ok(previous.unwrap_or_default())
+    };
+
+    let step = settings.step.clamp(1, 100);
+    let duties = (0..=100).rev().step_by(step.into()).collect::<Vec<u8>>();
+
+    let mut curve = Vec::new();
+    let mut stall = None;
+
+    for duty in &duties {
+        let rpm = measure(bridge, *duty)?;
+        debug!("calibration of {}: {}% -> {} rpm", control.name, duty, rpm);
+        curve.push(CalibrationPoint { duty: *duty, rpm });
+
+        if rpm > 0.0 {
+            stall = Some(*duty);
+        }
+    }
+
+    let Some(stall) = stall else {
+        return Err(CalibrationError::NeverSpinned);
+    };
+
+    // the fan is stopped, or at the lowest duty
+    let mut spin_up = None;
+    for duty in duties.iter().rev() {
+        if measure(bridge, *duty)? > 0.0 {
+            spin_up = Some(*duty);
+            break;
+        }
+    }
+
+    let calibration = Calibration {
+        stall,
+        spin_up: spin_up.unwrap_or(stall).max(stall),
+        curve,
+    };
+
+    info!(
+        "{} calibrated: stall at {}%, spin up at {}%, max speed {} rpm.",
+        control.name,
+        calibration.stall,
+        calibration.spin_up,
+        calibration.max_rpm()
+    );
+
+    Ok(calibration)
+}
@@ -0,0 +1,555 @@
+use std::{
+    fs::{self, File},
+    io::{Read, Seek},
+    path::Path,
+    process::{self, Child, Command, Stdio},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use hardware::{Hardware, Value};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    app_graph::AppGraph,
+    node::{IsValid, Node, NodeType, ToNode},
+    update::UpdateError,
+};
+
+use super::sensor_policy::{SensorHealth, SensorPolicy};
+
+#[derive(Error, Debug)]
+pub enum CommandError {
+    #[error("Can't run \"{0}\": {1}")]
+    Io(String, std::io::Error),
+    #[error("\"{0}\" didn't finish in {1:?}")]
+    Timeout(String, Duration),
+    #[error("\"{0}\" failed: {1}")]
+    Failed(String, std::process::ExitStatus),
+    #[error("Invalid json: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("No value found in \"{0}\"")]
+    NoValue(String),
+}
+
+/// Temperature printed by a command, for sensors without a driver
+/// (`nvidia-smi`, `smartctl`, a BMC, ...).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CommandTemp {
+    pub name: String,
+    /// Run by the shell
+    pub command: String,
+    /// The first capture group, or the whole match, is the value
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regex: Option<String>,
+    /// Parse the output as json, and take the value at this pointer, like `/gpus/0/temp`.
+    /// Applied before the regex.
+    #[serde(
+        default,
+        rename = "jsonPointer",
+        alias = "json_pointer",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub json_pointer: Option<String>,
+    #[serde(
+        default = "default_timeout",
+        rename = "timeoutMs",
+        alias = "timeout_ms"
+    )]
+    pub timeout_ms: u64,
+    /// The command is not run again before this delay
+    #[serde(
+        default = "default_interval",
+        rename = "intervalMs",
+        alias = "interval_ms"
+    )]
+    pub interval_ms: u64,
+
+    #[serde(flatten)]
+    pub policy: SensorPolicy,
+
+    /// Built from `regex` and `json_pointer` by `compile_parser`.
+    /// None when one of them is invalid.
+    #[serde(skip)]
+    pub parser: Option<Parser>,
+
+    #[serde(skip)]
+    pub cache: CommandCache,
+
+    #[serde(skip)]
+    pub health: SensorHealth,
+}
+
+/// How the output is parsed, built once from the config.
+#[derive(Debug, Clone, Default)]
+pub struct Parser {
+    regex: Option<Regex>,
+    json_pointer: Option<String>,
+}
+
+/// Last result of the command, shared with the thread which runs it.
+/// Failures are cached too, so a broken command doesn't run on each update.
+#[derive(Debug, Default)]
+pub struct CommandCache(Arc<Mutex<CommandState>>);
+
+#[derive(Debug, Default)]
+struct CommandState {
+    running: bool,
+    /// End of the last run, with its value
+    last: Option<(Instant, Option<Value>)>,
+}
+
+fn default_timeout() -> u64 {
+    2000
+}
+
+fn default_interval() -> u64 {
+    5000
+}
+
+impl Default for CommandTemp {
+    fn default() -> Self {
+        Self {
+            name: Default::default(),
+            command: Default::default(),
+            regex: None,
+            json_pointer: None,
+            timeout_ms: default_timeout(),
+            interval_ms: default_interval(),
+            policy: Default::default(),
+            parser: Some(Parser::default()),
+            cache: Default::default(),
+            health: Default::default(),
+        }
+    }
+}
+
+impl CommandTemp {
+    /// Compile `regex` and check `json_pointer`, after they changed.
+    pub fn compile_parser(&mut self) {
+        self.parser = match Parser::new(self.regex.as_deref(), self.json_pointer.as_deref()) {
+            Ok(parser) => Some(parser),
+            Err(e) => {
+                warn!("CommandTemp {}: {}", self.name, e);
+                None
+            }
+        };
+    }
+
+    /// Start the command in the background when the cached value is too old,
+    /// and use the cached value: the update never waits for the command.
+    /// Retries of the policy use the cached value too.
+    /// None before the first result, or after a failed run, like a missing sensor.
+    pub fn get_value(&mut self) -> Result<Option<Value>, UpdateError> {
+        let Some(parser) = &self.parser else {
+            return Err(UpdateError::NodeIsInvalid(self.name.clone()));
+        };
+
+        let last = {
+            let mut state = self.cache.state();
+            let is_due = !state.running
+                && state.last.is_none_or(|(time, _)| {
+                    time.elapsed() >= Duration::from_millis(self.interval_ms)
+                });
+            if is_due {
+                state.running = self.spawn_run(parser.clone());
+            }
+            state.last
+        };
+
+        // the first run didn't finish yet
+        let Some((_, value)) = last else {
+            return Ok(None);
+        };
+
+        let res = self.policy.read(&mut self.health, &self.name, || {
+            value.ok_or(UpdateError::ValueIsNone)
+        });
+        match res {
+            Ok(value) => Ok(Some(value)),
+            // the failure was logged once, by the run
+            Err(UpdateError::ValueIsNone) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Return true if the thread started.
+    fn spawn_run(&self, parser: Parser) -> bool {
+        let cache = self.cache.0.clone();
+        let name = self.name.clone();
+        let command = self.command.clone();
+        let timeout = Duration::from_millis(self.timeout_ms);
+
+        let res = thread::Builder::new()
+            .name("command".into())
+            .spawn(move || {
+                let value = run(&command, timeout).and_then(|output| parser.parse(&output));
+                if let Err(e) = &value {
+                    warn!("CommandTemp {}: {}", name, e);
+                }
+
+                let mut state = cache.lock().unwrap_or_else(PoisonError::into_inner);
+                state.running = false;
+                state.last = Some((Instant::now(), value.ok()));
+            });
+
+        match res {
+            Ok(_) => true,
+            Err(e) => {
+                error!("CommandTemp {}: can't spawn a thread: {}", self.name, e);
+                false
+            }
+        }
+    }
+}
+
+impl Parser {
+    pub fn new(regex: Option<&str>, json_pointer: Option<&str>) -> Result<Self, String> {
+        let regex = regex
+            .map(Regex::new)
+            .transpose()
+            .map_err(|e| format!("invalid regex: {}", e))?;
+
+        if let Some(pointer) = json_pointer {
+            if !pointer.is_empty() && !pointer.starts_with('/') {
+                return Err(format!("invalid json pointer \"{}\"", pointer));
+            }
+        }
+
+        Ok(Self {
+            regex,
+            json_pointer: json_pointer.map(str::to_owned),
+        })
+    }
+
+    /// Without regex or json pointer, the first number of the output is used.
+    pub fn parse(&self, output: &str) -> Result<Value, CommandError> {
+        let no_value = || CommandError::NoValue(output.trim().to_owned());
+
+        let text = match &self.json_pointer {
+            Some(pointer) => {
+                let json: serde_json::Value = serde_json::from_str(output)?;
+                match json.pointer(pointer) {
+                    Some(serde_json::Value::Number(number)) if self.regex.is_none() => {
+                        return number.as_f64().ok_or_else(no_value);
+                    }
+                    Some(serde_json::Value::String(string)) => string.clone(),
+                    Some(value) => value.to_string(),
+                    None => return Err(no_value()),
+                }
+            }
+            None => output.to_owned(),
+        };
+
+        let text = match &self.regex {
+            Some(regex) => {
+                let captures = regex.captures(&text).ok_or_else(no_value)?;
+                captures
+                    .get(1)
+                    .or_else(|| captures.get(0))
+                    .map(|m| m.as_str().to_owned())
+                    .ok_or_else(no_value)?
+            }
+            None => text,
+        };
+
+        text.split_whitespace()
+            .find_map(|word| word.parse::<Value>().ok())
+            .filter(|value| value.is_finite())
+            .ok_or_else(no_value)
+    }
+}
+
+impl CommandCache {
+    fn state(&self) -> MutexGuard<'_, CommandState> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Unlike a clone, receive the result of the run in progress.
+    pub fn share(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+/// A clone doesn't share the state: the runs started before only update the original.
+impl Clone for CommandCache {
+    fn clone(&self) -> Self {
+        let last = self.state().last;
+        Self(Arc::new(Mutex::new(CommandState {
+            running: false,
+            last,
+        })))
+    }
+}
+
+/// The output goes to a file rather than a pipe, so nothing is left
+/// blocked on a read when a child of the command keeps it open.
+fn run(command: &str, timeout: Duration) -> Result<String, CommandError> {
+    static RUNS: AtomicU32 = AtomicU32::new(0);
+
+    let path = std::env::temp_dir().join(format!(
+        "fan-control-command-{}-{}",
+        process::id(),
+        RUNS.fetch_add(1, Ordering::Relaxed)
+    ));
+    let res = run_to_file(command, timeout, &path);
+    let _ = fs::remove_file(&path);
+    res
+}
+
+fn run_to_file(command: &str, timeout: Duration, path: &Path) -> Result<String, CommandError> {
+    let io_error = |e| CommandError::Io(command.to_owned(), e);
+    let deadline = Instant::now() + timeout;
+
+    let mut file = File::options()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .map_err(io_error)?;
+
+    let mut child = shell_command(command)
+        .stdin(Stdio::null())
+        .stdout(file.try_clone().map_err(io_error)?)
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(io_error)?;
+
+    loop {
+        match child.try_wait().map_err(io_error)? {
+            Some(status) if status.success() => break,
+            Some(status) => return Err(CommandError::Failed(command.to_owned(), status)),
+            None if Instant::now() >= deadline => {
+                kill(&mut child);
+                return Err(CommandError::Timeout(command.to_owned(), timeout));
+            }
+            None => thread::sleep(Duration::from_millis(10)),
+        }
+    }
+
+    let mut output = String::new();
+    file.rewind().map_err(io_error)?;
+    file.read_to_string(&mut output).map_err(io_error)?;
+    Ok(output)
+}
+
+#[cfg(unix)]
+fn shell_command(command: &str) -> Command {
+    use std::os::unix::process::CommandExt;
+
+    let mut cmd = Command::new("sh");
+    // in its own process group, so the processes it starts can be killed with it
+    cmd.args(["-c", command]).process_group(0);
+    cmd
+}
+
+#[cfg(not(unix))]
+fn shell_command(command: &str) -> Command {
+    let mut cmd = Command::new("cmd");
+    cmd.args(["/C", command]);
+    cmd
+}
+
+/// Kill the shell and the processes it started, like `sleep` in `sleep 5; echo 40`.
+#[cfg(unix)]
+fn kill(child: &mut Child) {
+    // the id of the group is the id of the shell
+    // SAFETY: killpg doesn't access memory
+    if unsafe { libc::killpg(child.id() as libc::pid_t, libc::SIGKILL) } != 0 {
+        warn!("can't kill command: {}", std::io::Error::last_os_error());
+    }
+    let _ = child.wait();
+}
+
+#[cfg(not(unix))]
+fn kill(child: &mut Child) {
+    if let Err(e) = child.kill() {
+        warn!("can't kill command: {}", e);
+    }
+    let _ = child.wait();
+}
+
+impl IsValid for CommandTemp {
+    fn is_valid(&self) -> bool {
+        !self.command.trim().is_empty() && self.parser.is_some()
+    }
+}
+
+impl ToNode for CommandTemp {
+    fn to_node(mut self, app_graph: &mut AppGraph, _hardware: &Hardware) -> Node {
+        if !self.policy.is_valid() {
+            warn!(
+                "CommandTemp {}: invalid sensor policy, fall back to the default",
+                self.name
+            );
+            self.policy = SensorPolicy::default();
+        }
+        self.compile_parser();
+
+        Node::new(NodeType::CommandTemp(self), app_graph)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use hardware::{
+        snapshot::{HardwareSnapshot, SnapshotBridge},
+        HardwareBridge, Value,
+    };
+
+    use super::{CommandTemp, Parser};
+    use crate::{
+        app_graph::AppGraph,
+        config::Config,
+        update::{Update, UpdateError},
+    };
+
+    /// Start the command and wait for its result.
+    fn run_now(command_temp: &mut CommandTemp) -> Result<Option<Value>, UpdateError> {
+        command_temp.cache = Default::default();
+        assert_eq!(command_temp.get_value().unwrap(), None);
+
+        let start = Instant::now();
+        while command_temp.cache.state().running {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(10));
+        }
+        command_temp.get_value()
+    }
+
+    #[test]
+    fn test_parse() {
+        let parser = Parser::default();
+        assert_eq!(parser.parse("45\n").unwrap(), 45.0);
+        assert_eq!(parser.parse("  -3.5 C").unwrap(), -3.5);
+        assert!(parser.parse("N/A").is_err());
+        assert!(parser.parse("").is_err());
+
+        let parser = Parser::new(Some(r"(?m)^Temperature:\s+(\d+)"), None).unwrap();
+        assert_eq!(
+            parser
+                .parse("Current Drive Temperature:     38 C\nTemperature:     41 Celsius")
+                .unwrap(),
+            41.0
+        );
+        assert!(parser.parse("no temperature").is_err());
+
+        let parser = Parser::new(None, Some("/gpus/1/temp")).unwrap();
+        let json = r#"{ "gpus": [{ "temp": 40 }, { "temp": 52.5 }] }"#;
+        assert_eq!(parser.parse(json).unwrap(), 52.5);
+        let parser = Parser::new(None, Some("/gpus/2/temp")).unwrap();
+        assert!(parser.parse(json).is_err());
+        assert!(parser.parse("not json").is_err());
+
+        assert!(Parser::new(Some("(unclosed"), None).is_err());
+        assert!(Parser::new(None, Some("gpus/0")).is_err());
+    }
+
+    #[test]
+    fn test_invalid_parser() {
+        let config: Config = toml::from_str(
+            r#"
+[[CommandTemp]]
+name = "gpu"
+command = "echo 40"
+regex = "(unclosed"
+"#,
+        )
+        .unwrap();
+
+        let app_graph = AppGraph::from_config(config, &Default::default());
+        let node = app_graph.nodes.values().next().unwrap();
+        assert!(!node.node_type.is_valid());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run() {
+        let mut command_temp = CommandTemp {
+            command: "echo 42.5".into(),
+            ..Default::default()
+        };
+        assert_eq!(run_now(&mut command_temp).unwrap(), Some(42.5));
+
+        // cached
+        command_temp.command = "echo 50".into();
+        assert_eq!(command_temp.get_value().unwrap(), Some(42.5));
+
+        // the failure is cached, without value like a missing sensor
+        command_temp.command = "exit 1".into();
+        assert_eq!(run_now(&mut command_temp).unwrap(), None);
+        assert_eq!(command_temp.get_value().unwrap(), None);
+        assert!(!command_temp.cache.state().running);
+
+        command_temp.command = "sleep 5".into();
+        command_temp.timeout_ms = 100;
+        let start = Instant::now();
+        assert_eq!(run_now(&mut command_temp).unwrap(), None);
+        assert!(start.elapsed() < Duration::from_secs(2));
+
+        // the processes started by the command are killed with it
+        let path = std::env::temp_dir().join("fan-control-command-killed");
+        let _ = std::fs::remove_file(&path);
+        command_temp.command = format!("(sleep 1; touch {}) & sleep 5", path.display());
+        assert_eq!(run_now(&mut command_temp).unwrap(), None);
+        thread::sleep(Duration::from_millis(1500));
+        assert!(!path.exists());
+
+        // a child which keeps the output open doesn't block the read
+        command_temp.command = "sleep 5 & echo 45".into();
+        command_temp.timeout_ms = 2000;
+        let start = Instant::now();
+        assert_eq!(run_now(&mut command_temp).unwrap(), Some(45.0));
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_background() {
+        let config: Config = toml::from_str(
+            r#"
+[[CommandTemp]]
+name = "gpu"
+command = "sleep 1; echo 40"
+
+[[CommandTemp]]
+name = "nvme"
+command = "sleep 1; echo 50"
+"#,
+        )
+        .unwrap();
+
+        let mut bridge = SnapshotBridge::from_snapshot(HardwareSnapshot::default());
+        let mut app_graph = AppGraph::from_config(config, bridge.hardware());
+        let mut update = Update::new();
+
+        // the update doesn't wait for the commands
+        let start = Instant::now();
+        update.all(&mut app_graph.nodes, &mut bridge).unwrap();
+        assert!(start.elapsed() < Duration::from_millis(500));
+        assert!(app_graph.nodes.values().all(|node| node.value.is_none()));
+
+        // they run at the same time
+        thread::sleep(Duration::from_millis(1500));
+        update.all(&mut app_graph.nodes, &mut bridge).unwrap();
+
+        let mut values = app_graph
+            .nodes
+            .values()
+            .map(|node| node.value)
+            .collect::<Vec<_>>();
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(values, vec![Some(40.0), Some(50.0)]);
+    }
+}
@@ -1,14 +1,17 @@
-use std::rc::Rc;
+use std::{sync::Arc, time::Instant};
 
-use hardware::{HControl, Hardware, HardwareBridge, Mode, Value};
+use hardware::{HControl, Hardware, HardwareBridge, HardwareValues, Mode, Value};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    app_graph::AppGraph,
+    app_graph::{AppGraph, Nodes},
+    calibration::Calibration,
     node::{IsValid, Node, NodeType, ToNode},
     update::UpdateError,
 };
 
+use super::utils::{decimal, hardware_id::find_hardware};
+
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Control {
     // unique
@@ -19,12 +22,212 @@ pub struct Control {
     pub input: Option<String>,
     pub active: bool,
 
+    #[serde(flatten)]
+    pub output: OutputMapping,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub calibration: Option<Calibration>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failsafe: Option<Failsafe>,
+
+    #[serde(
+        default,
+        rename = "slewRate",
+        alias = "slew_rate",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub slew_rate: Option<SlewRate>,
+
     // E hardware.controls, only one ref in all controls
     #[serde(skip)]
-    pub control_h: Option<Rc<HControl>>,
+    pub control_h: Option<Arc<HControl>>,
 
     #[serde(skip)]
     pub mode_set: Option<Mode>,
+
+    /// Last value sent to the bridge
+    #[serde(skip)]
+    pub output_set: Option<Value>,
+
+    /// Consecutive cycles without input value
+    #[serde(skip)]
+    pub missed_cycles: u32,
+
+    /// Value read back after the last output was set. The hardware can
+    /// round the output, so this is compared, instead of the output.
+    #[serde(skip)]
+    pub output_read: Option<Value>,
+
+    /// Times the firmware took the control back
+    #[serde(skip)]
+    pub takeovers: u32,
+
+    /// Last value after the slew rate, and when it was computed
+    #[serde(skip)]
+    pub slewed: Option<(Value, Instant)>,
+}
+
+/// Difference between two reads of a control which is not a drift
+static DRIFT_TOLERANCE: Value = 1.0;
+
+/// What a control does when its input has no value,
+/// because a sensor disappeared or can't be read.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Failsafe {
+    pub action: FailsafeAction,
+    /// Cycles without input value before the action is applied
+    #[serde(
+        default = "default_after_cycles",
+        rename = "afterCycles",
+        alias = "after_cycles"
+    )]
+    pub after_cycles: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum FailsafeAction {
+    /// Give the control back to the firmware
+    Auto,
+    /// 100%
+    Max,
+    /// A percentage
+    Fixed(u8),
+}
+
+fn default_after_cycles() -> u32 {
+    3
+}
+
+/// Limit how fast the value requested by the input changes, so a short
+/// spike doesn't make the fan jump. Rates are in percent per second,
+/// so they don't depend on the update delay. None is unlimited.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct SlewRate {
+    #[serde(
+        default,
+        serialize_with = "decimal::serialize_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub up: Option<Value>,
+    #[serde(
+        default,
+        serialize_with = "decimal::serialize_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub down: Option<Value>,
+}
+
+impl SlewRate {
+    pub fn is_valid(&self) -> bool {
+        let is_valid = |rate: Option<Value>| rate.is_none_or(|rate| rate.is_finite() && rate > 0.0);
+
+        is_valid(self.up) && is_valid(self.down)
+    }
+
+    /// `elapsed` is the time since `last` was computed, in seconds.
+    fn limit(&self, last: Value, value: Value, elapsed: Value) -> Value {
+        match (self.up, self.down) {
+            (Some(up), _) if value > last => value.min(last + up * elapsed),
+            (_, Some(down)) if value < last => value.max(last - down * elapsed),
+            _ => value,
+        }
+    }
+}
+
+/// Map the percentage requested by the input to
+/// the duty cycle that is really sent to the hardware.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct OutputMapping {
+    /// Duty used for one cycle when the fan was stopped
+    #[serde(
+        default,
+        rename = "minStart",
+        alias = "min_start",
+        skip_serializing_if = "is_zero"
+    )]
+    pub min_start: u8,
+    /// Lowest duty while the fan is spinning
+    #[serde(
+        default,
+        rename = "minDuty",
+        alias = "min_duty",
+        skip_serializing_if = "is_zero"
+    )]
+    pub min_duty: u8,
+    #[serde(
+        default = "default_max_duty",
+        rename = "maxDuty",
+        alias = "max_duty",
+        skip_serializing_if = "is_max_duty"
+    )]
+    pub max_duty: u8,
+    /// A request of 0 stops the fan, instead of using the min duty
+    #[serde(
+        default = "default_allow_stop",
+        rename = "allowStop",
+        alias = "allow_stop",
+        skip_serializing_if = "is_true"
+    )]
+    pub allow_stop: bool,
+}
+
+fn is_zero(value: &u8) -> bool {
+    *value == 0
+}
+
+fn default_max_duty() -> u8 {
+    100
+}
+
+fn is_max_duty(value: &u8) -> bool {
+    *value == default_max_duty()
+}
+
+fn default_allow_stop() -> bool {
+    true
+}
+
+fn is_true(value: &bool) -> bool {
+    *value
+}
+
+impl Default for OutputMapping {
+    fn default() -> Self {
+        Self {
+            min_start: 0,
+            min_duty: 0,
+            max_duty: default_max_duty(),
+            allow_stop: default_allow_stop(),
+        }
+    }
+}
+
+impl OutputMapping {
+    pub fn is_valid(&self) -> bool {
+        self.max_duty <= 100 && self.min_start <= 100 && self.min_duty <= self.max_duty
+    }
+
+    /// Scale `value` (0 to 100) between the min and the max duty.
+    /// `previous` is the last duty sent, used to know if the fan is stopped.
+    pub fn apply(&self, value: Value, previous: Option<Value>) -> Value {
+        let value = value.clamp(0.0, 100.0);
+
+        if value == 0.0 && self.allow_stop {
+            return 0.0;
+        }
+
+        let min_duty = Value::from(self.min_duty);
+        let max_duty = Value::from(self.max_duty);
+
+        let duty = min_duty + (max_duty - min_duty) * value / 100.0;
+
+        match previous {
+            Some(previous) if previous > 0.0 => duty,
+            _ => duty.max(self.min_start.into()),
+        }
+    }
 }
 
 impl Control {
@@ -33,18 +236,48 @@ impl Control {
         hardware_id: Option<String>,
         input: Option<String>,
         active: bool,
-        control_h: Option<Rc<HControl>>,
+        control_h: Option<Arc<HControl>>,
     ) -> Self {
         Self {
             name: name.clone(),
             hardware_id,
             input,
             active,
+            output: OutputMapping::default(),
+            calibration: None,
+            failsafe: None,
             control_h,
             mode_set: None,
+            output_set: None,
+            missed_cycles: 0,
+            output_read: None,
+            takeovers: 0,
+            slew_rate: None,
+            slewed: None,
         }
     }
 
+    /// Value to set this cycle, after the slew rate.
+    pub fn slew(&mut self, value: Value) -> Value {
+        let now = Instant::now();
+
+        let value = match (&self.slew_rate, self.slewed) {
+            (Some(slew_rate), Some((last, at))) => {
+                slew_rate.limit(last, value, now.duration_since(at).as_secs_f64())
+            }
+            _ => value,
+        };
+        self.slewed = Some((value, now));
+        value
+    }
+
+    /// Store the calibration, and use it for the output mapping.
+    pub fn set_calibration(&mut self, calibration: Calibration) {
+        self.output = calibration.output_mapping(&self.output);
+        self.calibration = Some(calibration);
+    }
+
+    /// Return the value sent to the bridge, after the output mapping.
     pub fn set_value<H: HardwareBridge>(
         &mut self,
         value: Value,
@@ -54,10 +287,16 @@ impl Control {
             self.set_mode(Mode::Manual, bridge)?;
         }
 
+        let output = self.output.apply(value, self.output_set);
+
         match &self.control_h {
             Some(control_h) => {
-                bridge.set_value(control_h, value)?;
-                Ok(value)
+                bridge.set_value(control_h, output)?;
+                if self.output_set != Some(output) {
+                    self.output_read = None;
+                }
+                self.output_set = Some(output);
+                Ok(output)
             }
             None => Err(UpdateError::NodeIsInvalid(self.name.clone())),
         }
@@ -81,20 +320,141 @@ impl Control {
         };
 
         info!("Mode {} succefuly set for {}.", mode, self.name);
+        // the firmware may have changed the duty
+        self.output_set = None;
+        self.output_read = None;
         self.mode_set = Some(mode);
         Ok(())
     }
 
-    pub fn get_value<H: HardwareBridge>(&self, bridge: &mut H) -> Result<Value, UpdateError> {
+    pub fn is_failsafe_active(&self) -> bool {
+        self.failsafe
+            .as_ref()
+            .is_some_and(|failsafe| self.missed_cycles >= failsafe.after_cycles.max(1))
+    }
+
+    /// The input had no value during this cycle.
+    /// Without failsafe, the last value is kept.
+    pub fn input_missing<H: HardwareBridge>(&mut self, bridge: &mut H) -> Result<(), UpdateError> {
+        self.missed_cycles = self.missed_cycles.saturating_add(1);
+
+        let Some(failsafe) = &self.failsafe else {
+            return Ok(());
+        };
+        if !self.is_failsafe_active() {
+            return Ok(());
+        }
+        if self.missed_cycles == failsafe.after_cycles.max(1) {
+            warn!(
+                "{}: no input value during {} cycles, failsafe {:?} applied",
+                self.name, self.missed_cycles, failsafe.action
+            );
+        }
+
+        // values are sent again on each cycle, in case the firmware took the control back.
+        // The failsafe is not slowed down, the input ramps from it when it is back.
+        let value = match failsafe.action {
+            FailsafeAction::Auto => {
+                self.slewed = None;
+                return match self.mode_set {
+                    Some(Mode::Auto) => Ok(()),
+                    _ => self.set_mode(Mode::Auto, bridge),
+                };
+            }
+            FailsafeAction::Max => 100.0,
+            FailsafeAction::Fixed(percent) => percent.into(),
+        };
+        self.slewed = Some((value, Instant::now()));
+        self.set_value(value, bridge).map(|_| ())
+    }
+
+    /// The input has a value again. Return true if the failsafe was active,
+    /// so the value must be set even if it didn't change.
+    pub fn input_recovered(&mut self) -> bool {
+        let was_active = self.is_failsafe_active();
+        if was_active {
+            info!(
+                "{}: input value is back after {} cycles, failsafe ended",
+                self.name, self.missed_cycles
+            );
+        }
+        self.missed_cycles = 0;
+        was_active
+    }
+
+    /// Read back the mode and the value of a control in manual mode. If the firmware
+    /// changed them, set them again. Return true in this case.
+    pub fn verify<H: HardwareBridge>(&mut self, bridge: &mut H) -> Result<bool, UpdateError> {
+        let (Some(control_h), Some(Mode::Manual), Some(output)) =
+            (self.control_h.clone(), &self.mode_set, self.output_set)
+        else {
+            return Ok(false);
+        };
+
+        // the mode comes first: in auto mode, some drivers can't even report a value
+        let mode = bridge.get_mode(&control_h)?;
+        let (drift, value) = match mode {
+            Some(Mode::Manual) | None => {
+                let value = bridge.get_control_value(&control_h)?;
+                let drift = match self.output_read {
+                    Some(output_read) => (value - output_read).abs() > DRIFT_TOLERANCE,
+                    None => {
+                        self.output_read = Some(value);
+                        false
+                    }
+                };
+                (drift, Some(value))
+            }
+            Some(_) => (true, bridge.get_control_value(&control_h).ok()),
+        };
+        if !drift {
+            return Ok(false);
+        }
+
+        self.takeovers += 1;
+        warn!(
+            "{} was taken back by the firmware ({} times): mode {}, value {}. Set to {} again.",
+            self.name,
+            self.takeovers,
+            mode.map_or("unknown".to_owned(), |mode| mode.to_string()),
+            value.map_or("unknown".to_owned(), |value| value.to_string()),
+            output
+        );
+
+        self.mode_set = None;
+        self.set_mode(Mode::Manual, bridge)?;
+        bridge.set_value(&control_h, output)?;
+        self.output_set = Some(output);
+        Ok(true)
+    }
+
+    /// Use the value of `values`, when there is one.
+    pub fn get_value<H: HardwareBridge>(
+        &self,
+        values: &mut HardwareValues,
+        bridge: &mut H,
+    ) -> Result<Value, UpdateError> {
         match &self.control_h {
-            Some(control_h) => bridge
-                .get_control_value(control_h)
+            Some(control_h) => values
+                .take_control(control_h)
+                .unwrap_or_else(|| bridge.get_control_value(control_h))
                 .map_err(UpdateError::Hardware),
             None => Err(UpdateError::NodeIsInvalid(self.name.clone())),
         }
     }
 }
 
+/// Whether a control of `nodes` is already bound to the hardware with this id.
+pub fn is_control_used(nodes: &Nodes, hardware_id: &str) -> bool {
+    nodes.values().any(|node| match &node.node_type {
+        NodeType::Control(control) => control
+            .control_h
+            .as_ref()
+            .is_some_and(|control_h| control_h.hardware_id == hardware_id),
+        _ => false,
+    })
+}
+
 impl IsValid for Control {
     fn is_valid(&self) -> bool {
         self.active
@@ -106,29 +466,60 @@ impl IsValid for Control {
 
 impl ToNode for Control {
     fn to_node(mut self, app_graph: &mut AppGraph, hardware: &Hardware) -> Node {
-        match &self.hardware_id {
-            Some(hardware_id) => {
-                match hardware
-                    .controls
-                    .iter()
-                    .find(|control_h| &control_h.hardware_id == hardware_id)
-                {
-                    Some(control_h) => {
-                        if Rc::strong_count(control_h) > 1 {
-                            warn!("Control to Node, hardware id \"{}\" is already use by another control. {}.", hardware_id, self.name);
-                            self.hardware_id.take();
-                            self.control_h.take();
-                        } else {
-                            self.control_h = Some(control_h.clone());
-                        }
-                    }
-                    None => {
-                        warn!("Control to Node, hardware id \"{}\" was not found for {}. Fall back: hardware not used.", hardware_id, self.name);
-                        self.hardware_id.take();
+        if !self.output.is_valid() {
+            warn!(
+                "Control to Node: invalid output mapping for {}. Fall back: default mapping.",
+                self.name
+            );
+            self.output = OutputMapping::default();
+        }
+
+        if let Some(Failsafe {
+            action: FailsafeAction::Fixed(percent),
+            ..
+        }) = &mut self.failsafe
+        {
+            if *percent > 100 {
+                warn!(
+                    "Control to Node: invalid failsafe percent {} for {}. Fall back: 100%.",
+                    percent, self.name
+                );
+                *percent = 100;
+            }
+        }
+
+        if self
+            .slew_rate
+            .as_ref()
+            .is_some_and(|slew_rate| !slew_rate.is_valid())
+        {
+            warn!(
+                "Control to Node: invalid slew rate for {}. Fall back: no slew rate.",
+                self.name
+            );
+            self.slew_rate = None;
+        }
+
+        match self.hardware_id.clone() {
+            Some(hardware_id) => match find_hardware(&hardware_id, &hardware.controls) {
+                Some(control_h) => {
+                    if is_control_used(&app_graph.nodes, &control_h.hardware_id) {
+                        warn!("Control to Node, hardware id \"{}\" is already use by another control. {}.", hardware_id, self.name);
                         self.control_h.take();
+                    } else {
+                        if control_h.hardware_id != hardware_id {
+                            app_graph.add_rebound(&self.name, &hardware_id, &control_h.hardware_id);
+                            self.hardware_id = Some(control_h.hardware_id.clone());
+                        }
+                        self.control_h = Some(control_h.clone());
                     }
                 }
-            }
+                None => {
+                    // the id is kept, so the control is bound if it appears after a rescan
+                    warn!("Control to Node, hardware id \"{}\" was not found for {}. Fall back: hardware not used.", hardware_id, self.name);
+                    self.control_h.take();
+                }
+            },
             None => {
                 if self.control_h.is_some() {
                     warn!(
@@ -143,3 +534,391 @@ impl ToNode for Control {
         Node::new(NodeType::Control(self), app_graph)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use hardware::{
+        snapshot::{HardwareSnapshot, SnapshotBridge, SnapshotError},
+        HControl, HSensor, Hardware, HardwareBridge, HardwareError, Mode, Value,
+    };
+
+    use super::{Control, Failsafe, FailsafeAction, OutputMapping, SlewRate};
+    use crate::{
+        app_graph::AppGraph,
+        calibration::{Calibration, CalibrationPoint},
+        config::Config,
+        node::NodeType,
+        update::Update,
+    };
+
+    #[test]
+    fn test_output_mapping() {
+        let default = OutputMapping::default();
+        assert_eq!(default.apply(0.0, None), 0.0);
+        assert_eq!(default.apply(42.0, None), 42.0);
+        assert_eq!(default.apply(42.5, None), 42.5);
+        assert_eq!(default.apply(100.0, Some(42.0)), 100.0);
+        assert_eq!(default.apply(120.0, Some(42.0)), 100.0);
+
+        let mapping = OutputMapping {
+            min_start: 50,
+            min_duty: 20,
+            max_duty: 80,
+            allow_stop: true,
+        };
+
+        assert_eq!(mapping.apply(0.0, Some(40.0)), 0.0);
+        // the fan is stopped, so we use the min start duty
+        assert_eq!(mapping.apply(10.0, Some(0.0)), 50.0);
+        assert_eq!(mapping.apply(10.0, None), 50.0);
+        assert_eq!(mapping.apply(10.0, Some(50.0)), 26.0);
+        assert_eq!(mapping.apply(50.0, Some(50.0)), 50.0);
+        assert_eq!(mapping.apply(100.0, Some(50.0)), 80.0);
+
+        let mapping = OutputMapping {
+            allow_stop: false,
+            ..mapping
+        };
+        assert_eq!(mapping.apply(0.0, Some(40.0)), 20.0);
+        assert_eq!(mapping.apply(0.0, None), 50.0);
+    }
+
+    #[test]
+    fn test_output_mapping_update() {
+        let config: Config = toml::from_str(
+            r#"
+[[Control]]
+name = "control"
+id = "pwm1"
+input = "flat"
+active = true
+minDuty = 20
+
+[[Flat]]
+name = "flat"
+value = 60
+"#,
+        )
+        .unwrap();
+
+        let snapshot = HardwareSnapshot::from_str(
+            r#"
+[[Control]]
+name = "pwm1"
+id = "pwm1"
+value = 60
+"#,
+        )
+        .unwrap();
+        let mut bridge = SnapshotBridge::from_snapshot(snapshot);
+        let mut app_graph = AppGraph::from_config(config, bridge.hardware());
+        let pwm1 = bridge.hardware().controls[0].clone();
+        let mut update = Update::new();
+
+        // the value read back is the unmapped request, but the mapped one must be sent
+        update
+            .nodes_which_update_can_change(&mut app_graph.nodes, &mut bridge)
+            .unwrap();
+        update.all(&mut app_graph.nodes, &mut bridge).unwrap();
+        assert_eq!(bridge.get_control_value(&pwm1).unwrap(), 68.0);
+
+        // already set
+        bridge.set_value(&pwm1, 10.0).unwrap();
+        update.all(&mut app_graph.nodes, &mut bridge).unwrap();
+        assert_eq!(bridge.get_control_value(&pwm1).unwrap(), 10.0);
+    }
+
+    #[test]
+    fn test_serde() {
+        let mapping: OutputMapping = toml::from_str("minDuty = 20\nallowStop = false").unwrap();
+        assert_eq!(
+            mapping,
+            OutputMapping {
+                min_start: 0,
+                min_duty: 20,
+                max_duty: 100,
+                allow_stop: false,
+            }
+        );
+
+        assert_eq!(toml::to_string(&OutputMapping::default()).unwrap(), "");
+    }
+
+    #[test]
+    fn test_slew_rate() {
+        let slew_rate = SlewRate {
+            up: Some(10.0),
+            down: Some(5.0),
+        };
+        assert_eq!(slew_rate.limit(20.0, 100.0, 2.0), 40.0);
+        assert_eq!(slew_rate.limit(40.0, 100.0, 0.5), 45.0);
+        assert_eq!(slew_rate.limit(45.0, 0.0, 1.0), 40.0);
+        assert_eq!(slew_rate.limit(40.0, 38.0, 1.0), 38.0);
+
+        let slew_rate = SlewRate {
+            up: Some(10.0),
+            down: None,
+        };
+        assert_eq!(slew_rate.limit(80.0, 20.0, 1.0), 20.0);
+        assert!(slew_rate.is_valid());
+        assert!(!SlewRate {
+            up: Some(0.0),
+            down: None,
+        }
+        .is_valid());
+
+        let mut control: Control = toml::from_str(
+            r#"
+name = "control"
+active = true
+slewRate = { up = 10, down = 2.5 }
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            control.slew_rate,
+            Some(SlewRate {
+                up: Some(10.0),
+                down: Some(2.5),
+            })
+        );
+
+        // the first value is not limited
+        assert_eq!(control.slew(20.0), 20.0);
+        assert!(control.slew(100.0) < 30.0);
+    }
+
+    #[test]
+    fn test_calibration_serde() {
+        let mut control = Control::new("control1".into(), None, None, true, None);
+        control.set_calibration(Calibration {
+            stall: 15,
+            spin_up: 30,
+            curve: vec![
+                CalibrationPoint {
+                    duty: 100,
+                    rpm: 2000.0,
+                },
+                CalibrationPoint { duty: 0, rpm: 0.0 },
+            ],
+        });
+
+        let config = Config {
+            controls: vec![control.clone()],
+            ..Default::default()
+        };
+
+        let content = toml::to_string_pretty(&config).unwrap();
+        let config: Config = toml::from_str(&content).unwrap();
+
+        assert_eq!(config.controls[0].output, control.output);
+        assert_eq!(config.controls[0].calibration, control.calibration);
+    }
+
+    #[test]
+    fn test_failsafe() {
+        let path = std::env::temp_dir().join("fan-control-failsafe");
+        fs::write(&path, "40000\n").unwrap();
+
+        let mut config: Config = toml::from_str(&format!(
+            r#"
+[[Control]]
+name = "control"
+id = "pwm1"
+input = "linear"
+active = true
+failsafe = {{ action = {{ fixed = 80 }}, afterCycles = 2 }}
+
+[[FileTemp]]
+name = "temp"
+path = "{}"
+
+[[Linear]]
+name = "linear"
+input = "temp"
+minTemp = 10
+minSpeed = 10
+maxTemp = 70
+maxSpeed = 100
+"#,
+            path.display()
+        ))
+        .unwrap();
+        assert_eq!(
+            config.controls[0].failsafe,
+            Some(Failsafe {
+                action: FailsafeAction::Fixed(80),
+                after_cycles: 2,
+            })
+        );
+
+        let snapshot = HardwareSnapshot::from_str(
+            r#"
+[[Control]]
+name = "pwm1"
+id = "pwm1"
+value = 10
+"#,
+        )
+        .unwrap();
+        let mut bridge = SnapshotBridge::from_snapshot(snapshot);
+        let mut app_graph = AppGraph::from_config(config.clone(), bridge.hardware());
+        let pwm1 = bridge.hardware().controls[0].clone();
+
+        let mut update = || {
+            Update::new()
+                .all(&mut app_graph.nodes, &mut bridge)
+                .unwrap();
+            bridge.get_control_value(&pwm1).unwrap()
+        };
+
+        assert_eq!(update(), 55.0);
+
+        // the temperature can't be read
+        fs::remove_file(&path).unwrap();
+        assert_eq!(update(), 55.0);
+        assert_eq!(update(), 80.0);
+        assert_eq!(update(), 80.0);
+
+        // recovered
+        fs::write(&path, "40000\n").unwrap();
+        assert_eq!(update(), 55.0);
+
+        // back to the firmware
+        config.controls[0].failsafe = Some(Failsafe {
+            action: FailsafeAction::Auto,
+            after_cycles: 1,
+        });
+        let mut app_graph = AppGraph::from_config(config, bridge.hardware());
+        let mut update = || {
+            Update::new()
+                .all(&mut app_graph.nodes, &mut bridge)
+                .unwrap();
+        };
+        update();
+        fs::remove_file(&path).unwrap();
+        update();
+
+        let control = app_graph
+            .nodes
+            .values()
+            .find_map(|node| match &node.node_type {
+                NodeType::Control(control) => Some(control),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(control.mode_set, Some(Mode::Auto));
+        assert!(control.is_failsafe_active());
+    }
+
+    #[test]
+    fn test_verify() {
+        let snapshot = HardwareSnapshot::from_str(
+            r#"
+[[Control]]
+name = "pwm1"
+id = "pwm1"
+value = 10
+"#,
+        )
+        .unwrap();
+        let mut bridge = SnapshotBridge::from_snapshot(snapshot);
+        let pwm1 = bridge.hardware().controls[0].clone();
+
+        let mut control = Control::new("control".into(), None, None, true, Some(pwm1.clone()));
+        control.set_mode(Mode::Manual, &mut bridge).unwrap();
+        control.set_value(60.0, &mut bridge).unwrap();
+
+        // the first read back is the reference
+        assert!(!control.verify(&mut bridge).unwrap());
+        assert!(!control.verify(&mut bridge).unwrap());
+
+        // the firmware changed the value
+        bridge.set_value(&pwm1, 100.0).unwrap();
+        assert!(control.verify(&mut bridge).unwrap());
+        assert_eq!(bridge.get_control_value(&pwm1).unwrap(), 60.0);
+        assert_eq!(control.takeovers, 1);
+        assert!(!control.verify(&mut bridge).unwrap());
+
+        // not verified when the control is given back
+        control.set_mode(Mode::Auto, &mut bridge).unwrap();
+        bridge.set_value(&pwm1, 100.0).unwrap();
+        assert!(!control.verify(&mut bridge).unwrap());
+        assert_eq!(control.takeovers, 1);
+    }
+
+    /// Like the ThinkPad fan: the level can't be read as a value in auto mode.
+    struct AutoLevelBridge {
+        snapshot: SnapshotBridge,
+        mode: Mode,
+    }
+
+    impl HardwareBridge for AutoLevelBridge {
+        fn new() -> Result<Self, HardwareError> {
+            unimplemented!()
+        }
+
+        fn hardware(&self) -> &Hardware {
+            self.snapshot.hardware()
+        }
+
+        fn get_sensor_value(&mut self, sensor: &HSensor) -> Result<Value, HardwareError> {
+            self.snapshot.get_sensor_value(sensor)
+        }
+
+        fn get_control_value(&mut self, control: &HControl) -> Result<Value, HardwareError> {
+            match self.mode {
+                Mode::Manual => self.snapshot.get_control_value(control),
+                _ => Err(HardwareError::Snapshot(SnapshotError::NoValue(
+                    control.name.clone(),
+                ))),
+            }
+        }
+
+        fn set_value(&mut self, control: &HControl, value: Value) -> Result<(), HardwareError> {
+            self.snapshot.set_value(control, value)
+        }
+
+        fn set_mode(&mut self, _control: &HControl, mode: &Mode) -> Result<(), HardwareError> {
+            self.mode = mode.clone();
+            Ok(())
+        }
+
+        fn get_mode(&mut self, _control: &HControl) -> Result<Option<Mode>, HardwareError> {
+            Ok(Some(self.mode.clone()))
+        }
+    }
+
+    #[test]
+    fn test_verify_auto_without_value() {
+        let snapshot = HardwareSnapshot::from_str(
+            r#"
+[[Control]]
+name = "fan"
+id = "fan"
+value = 10
+"#,
+        )
+        .unwrap();
+        let mut bridge = AutoLevelBridge {
+            snapshot: SnapshotBridge::from_snapshot(snapshot),
+            mode: Mode::Auto,
+        };
+        let fan = bridge.hardware().controls[0].clone();
+
+        let mut control = Control::new("control".into(), None, None, true, Some(fan.clone()));
+        control.set_mode(Mode::Manual, &mut bridge).unwrap();
+        control.set_value(60.0, &mut bridge).unwrap();
+        assert!(!control.verify(&mut bridge).unwrap());
+
+        // the firmware took the fan back, its value can't be read anymore
+        bridge.mode = Mode::Auto;
+        assert!(control.verify(&mut bridge).unwrap());
+        assert_eq!(bridge.mode, Mode::Manual);
+        assert_eq!(bridge.get_control_value(&fan).unwrap(), 60.0);
+        assert_eq!(control.takeovers, 1);
+        assert!(!control.verify(&mut bridge).unwrap());
+    }
+}
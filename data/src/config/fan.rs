@@ -1,11 +1,16 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
 use crate::{
     app_graph::AppGraph,
     node::{IsValid, Node, NodeType, ToNode},
     update::UpdateError,
 };
-use hardware::{HSensor, Hardware, HardwareBridge, Value};
+
+use super::{
+    sensor_policy::{SensorHealth, SensorPolicy},
+    utils::hardware_id::find_hardware,
+};
+use hardware::{HSensor, Hardware, HardwareBridge, HardwareValues, Value};
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
@@ -17,16 +22,32 @@ pub struct Fan {
     pub hardware_id: Option<String>,
 
     // E hardware.fans
+    #[serde(flatten)]
+    pub policy: SensorPolicy,
+
+    #[serde(skip)]
+    pub fan_h: Option<Arc<HSensor>>,
+
     #[serde(skip)]
-    pub fan_h: Option<Rc<HSensor>>,
+    pub health: SensorHealth,
 }
 
 impl Fan {
-    pub fn get_value<H: HardwareBridge>(&self, bridge: &mut H) -> Result<Value, UpdateError> {
+    /// The first read uses the value of `values`, when there is one.
+    pub fn get_value<H: HardwareBridge>(
+        &mut self,
+        values: &mut HardwareValues,
+        bridge: &mut H,
+    ) -> Result<Value, UpdateError> {
         match &self.fan_h {
-            Some(fan_h) => bridge
-                .get_sensor_value(fan_h)
-                .map_err(UpdateError::Hardware),
+            Some(fan_h) => {
+                let mut read = values.take_sensor(fan_h);
+                self.policy.read(&mut self.health, &self.name, || {
+                    read.take()
+                        .unwrap_or_else(|| bridge.get_sensor_value(fan_h))
+                        .map_err(UpdateError::Hardware)
+                })
+            }
             None => Err(UpdateError::NodeIsInvalid(self.name.clone())),
         }
     }
@@ -40,21 +61,29 @@ impl IsValid for Fan {
 
 impl ToNode for Fan {
     fn to_node(mut self, app_graph: &mut AppGraph, hardware: &Hardware) -> Node {
-        match &self.hardware_id {
-            Some(hardware_id) => {
-                match hardware
-                    .fans
-                    .iter()
-                    .find(|fan_h| &fan_h.hardware_id == hardware_id)
-                {
-                    Some(fan_h) => self.fan_h = Some(fan_h.clone()),
-                    None => {
-                        warn!("Fan to Node, hardware_id not found. {} from config not found. Fall back to no id", hardware_id);
-                        self.hardware_id.take();
-                        self.fan_h.take();
+        if !self.policy.is_valid() {
+            warn!(
+                "Fan {}: invalid sensor policy, fall back to the default",
+                self.name
+            );
+            self.policy = SensorPolicy::default();
+        }
+
+        match self.hardware_id.clone() {
+            Some(hardware_id) => match find_hardware(&hardware_id, &hardware.fans) {
+                Some(fan_h) => {
+                    if fan_h.hardware_id != hardware_id {
+                        app_graph.add_rebound(&self.name, &hardware_id, &fan_h.hardware_id);
+                        self.hardware_id = Some(fan_h.hardware_id.clone());
                     }
+                    self.fan_h = Some(fan_h.clone())
                 }
-            }
+                None => {
+                    // the id is kept, so the fan is bound if it appears after a rescan
+                    warn!("Fan to Node, hardware_id not found. {} from config not found. Not bound until a rescan finds it", hardware_id);
+                    self.fan_h.take();
+                }
+            },
             None => {
                 debug_assert!(self.fan_h.is_none())
             }
@@ -0,0 +1,218 @@
+use std::fs;
+
+use hardware::{Hardware, Value};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    app_graph::AppGraph,
+    node::{IsValid, Node, NodeType, ToNode},
+    update::UpdateError,
+};
+
+use super::{
+    sensor_policy::{SensorHealth, SensorPolicy},
+    utils::decimal,
+};
+
+#[derive(Error, Debug)]
+pub enum FileTempError {
+    #[error("Can't read {0}: {1}")]
+    Io(String, std::io::Error),
+    #[error("Invalid value in {0}: \"{1}\"")]
+    InvalidData(String, String),
+}
+
+/// Temperature read from a file, for values that libsensors doesn't enumerate,
+/// like `/sys/class/thermal/thermal_zone0/temp`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FileTemp {
+    pub name: String,
+    pub path: String,
+    /// The value of the file is divided by this, 1000 for millidegrees
+    #[serde(default = "default_divisor", serialize_with = "decimal::serialize")]
+    pub divisor: f64,
+    /// Added after the division
+    #[serde(default, serialize_with = "decimal::serialize")]
+    pub offset: f64,
+
+    #[serde(flatten)]
+    pub policy: SensorPolicy,
+
+    #[serde(skip)]
+    pub health: SensorHealth,
+}
+
+fn default_divisor() -> f64 {
+    1000.0
+}
+
+impl Default for FileTemp {
+    fn default() -> Self {
+        Self {
+            name: Default::default(),
+            path: Default::default(),
+            divisor: default_divisor(),
+            offset: 0.0,
+            policy: Default::default(),
+            health: Default::default(),
+        }
+    }
+}
+
+impl FileTemp {
+    /// Read the file once. Can be done at the same time as the other reads.
+    pub fn read(&self) -> Result<Value, UpdateError> {
+        read_file(&self.path, self.divisor, self.offset)
+    }
+
+    /// The first read uses `read`, when there is one.
+    pub fn get_value(
+        &mut self,
+        read: Option<Result<Value, UpdateError>>,
+    ) -> Result<Value, UpdateError> {
+        let mut read = read;
+        let (path, divisor, offset) = (&self.path, self.divisor, self.offset);
+        self.policy.read(&mut self.health, &self.name, || {
+            read.take()
+                .unwrap_or_else(|| read_file(path, divisor, offset))
+        })
+    }
+}
+
+fn read_file(path: &str, divisor: f64, offset: f64) -> Result<Value, UpdateError> {
+    let content = fs::read_to_string(path).map_err(|e| FileTempError::Io(path.to_owned(), e))?;
+
+    let raw = content
+        .trim()
+        .parse::<Value>()
+        .ok()
+        .filter(|raw| raw.is_finite())
+        .ok_or_else(|| FileTempError::InvalidData(path.to_owned(), content.trim().to_owned()))?;
+
+    Ok(raw / divisor + offset)
+}
+
+impl IsValid for FileTemp {
+    fn is_valid(&self) -> bool {
+        !self.path.trim().is_empty()
+    }
+}
+
+impl ToNode for FileTemp {
+    fn to_node(mut self, app_graph: &mut AppGraph, _hardware: &Hardware) -> Node {
+        let default = Self::default();
+
+        if self.divisor == 0.0 || !self.divisor.is_finite() {
+            warn!("FileTemp {}: invalid divisor {}", self.name, self.divisor);
+            self.divisor = default.divisor;
+        }
+        if !self.offset.is_finite() {
+            warn!("FileTemp {}: invalid offset {}", self.name, self.offset);
+            self.offset = default.offset;
+        }
+        if !self.policy.is_valid() {
+            warn!(
+                "FileTemp {}: invalid sensor policy, fall back to the default",
+                self.name
+            );
+            self.policy = default.policy;
+        }
+
+        Node::new(NodeType::FileTemp(self), app_graph)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use hardware::snapshot::{HardwareSnapshot, SnapshotBridge};
+
+    use super::FileTemp;
+    use crate::{
+        app_graph::AppGraph,
+        config::{linear::Linear, Config},
+        update::Update,
+    };
+
+    fn temp_file(test_name: &str, content: &str) -> String {
+        let path = std::env::temp_dir().join(format!("fan-control-file-temp-{}", test_name));
+        fs::write(&path, content).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn test_get_value() {
+        let mut file_temp = FileTemp {
+            name: "zone0".into(),
+            path: temp_file("get-value", "45500\n"),
+            ..Default::default()
+        };
+        assert_eq!(file_temp.read().unwrap(), 45.5);
+
+        file_temp.divisor = 1.0;
+        file_temp.offset = -10.0;
+        assert_eq!(file_temp.read().unwrap(), 45490.0);
+
+        fs::write(&file_temp.path, "N/A\n").unwrap();
+        assert!(file_temp.read().is_err());
+
+        fs::remove_file(&file_temp.path).unwrap();
+        assert!(file_temp.read().is_err());
+
+        // the last good value is held, like for a sensor
+        file_temp.policy.hold_cycles = 1;
+        fs::write(&file_temp.path, "45500\n").unwrap();
+        assert_eq!(file_temp.get_value(None).unwrap(), 45490.0);
+        fs::remove_file(&file_temp.path).unwrap();
+        assert_eq!(file_temp.get_value(None).unwrap(), 45490.0);
+        assert!(file_temp.get_value(None).is_err());
+        assert_eq!(file_temp.health.stats.held, 1);
+    }
+
+    #[test]
+    fn test_update() {
+        let path = temp_file("update", "40000\n");
+
+        let config = Config {
+            file_temps: vec![FileTemp {
+                name: "zone0".into(),
+                path: path.clone(),
+                ..Default::default()
+            }],
+            linears: vec![Linear {
+                name: "linear".into(),
+                input: Some("zone0".into()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let mut bridge = SnapshotBridge::from_snapshot(HardwareSnapshot::default());
+        let mut app_graph = AppGraph::from_config(config, &Default::default());
+
+        let value_of = |app_graph: &AppGraph, name: &str| {
+            app_graph
+                .nodes
+                .values()
+                .find(|node| node.name() == name)
+                .unwrap()
+                .value
+        };
+
+        Update::new()
+            .all(&mut app_graph.nodes, &mut bridge)
+            .unwrap();
+        assert_eq!(value_of(&app_graph, "zone0"), Some(40.0));
+        assert_eq!(value_of(&app_graph, "linear"), Some(55.0));
+
+        // a missing file is like a missing sensor
+        fs::remove_file(&path).unwrap();
+        Update::new()
+            .all(&mut app_graph.nodes, &mut bridge)
+            .unwrap();
+        assert_eq!(value_of(&app_graph, "zone0"), None);
+        assert_eq!(value_of(&app_graph, "linear"), None);
+    }
+}
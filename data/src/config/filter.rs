@@ -0,0 +1,235 @@
+use std::{
+    collections::VecDeque,
+    fmt::Display,
+    time::{Duration, Instant},
+};
+
+use hardware::{Hardware, Value};
+use light_enum::Values;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_graph::AppGraph,
+    node::{IsValid, Node, NodeType, ToNode},
+    update::UpdateError,
+};
+
+use super::utils::decimal;
+
+/// One hour
+pub static MAX_PERIOD: f64 = 3600.0;
+
+/// Smooth the value of one temperature over time.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Filter {
+    pub name: String,
+    pub kind: FilterKind,
+    /// In seconds: the window of the moving average and the median,
+    /// the time constant of the exponential
+    #[serde(serialize_with = "decimal::serialize")]
+    pub period: f64,
+    pub input: Option<String>,
+
+    #[serde(skip)]
+    pub state: FilterState,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Values, Default, PartialEq, Eq)]
+pub enum FilterKind {
+    #[default]
+    MovingAverage,
+    Exponential,
+    Median,
+}
+
+/// Kept between update cycles
+#[derive(Debug, Clone, Default)]
+pub struct FilterState {
+    /// Values of the last period, the oldest first
+    history: VecDeque<(Instant, Value)>,
+    /// Last value of the exponential
+    smoothed: Option<(Instant, Value)>,
+}
+
+impl Filter {
+    pub fn get_value(&mut self, value: Value) -> Result<Value, UpdateError> {
+        Ok(self.compute(value, Instant::now()))
+    }
+
+    fn compute(&mut self, value: Value, now: Instant) -> Value {
+        if self.kind == FilterKind::Exponential {
+            let smoothed = match self.state.smoothed {
+                Some((last_update, smoothed)) if self.period > 0.0 => {
+                    let dt = now.duration_since(last_update).as_secs_f64();
+                    smoothed + (value - smoothed) * (1.0 - (-dt / self.period).exp())
+                }
+                _ => value,
+            };
+            self.state.smoothed = Some((now, smoothed));
+            return smoothed;
+        }
+
+        let history = &mut self.state.history;
+        history.push_back((now, value));
+        let period = Duration::from_secs_f64(self.period);
+        while history
+            .front()
+            .is_some_and(|(instant, _)| now.duration_since(*instant) > period)
+        {
+            history.pop_front();
+        }
+
+        let mut values = history.iter().map(|(_, value)| *value).collect::<Vec<_>>();
+
+        match self.kind {
+            FilterKind::Median => {
+                values.sort_by(Value::total_cmp);
+                let middle = values.len() / 2;
+                if values.len() % 2 == 0 {
+                    (values[middle - 1] + values[middle]) / 2.0
+                } else {
+                    values[middle]
+                }
+            }
+            _ => values.iter().sum::<Value>() / values.len() as Value,
+        }
+    }
+
+    /// Forget the previous values, when the input has no value.
+    pub fn reset(&mut self) {
+        self.state = FilterState::default();
+    }
+}
+
+impl IsValid for Filter {
+    fn is_valid(&self) -> bool {
+        self.input.is_some()
+    }
+}
+
+impl ToNode for Filter {
+    fn to_node(mut self, app_graph: &mut AppGraph, _hardware: &Hardware) -> Node {
+        if !(0.0..=MAX_PERIOD).contains(&self.period) {
+            warn!(
+                "Filter {}: period {} is out of range",
+                self.name, self.period
+            );
+            self.period = Self::default().period;
+        }
+
+        Node::new(NodeType::Filter(self), app_graph)
+    }
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Self {
+            name: Default::default(),
+            kind: Default::default(),
+            period: 10.0,
+            input: Default::default(),
+            state: Default::default(),
+        }
+    }
+}
+
+impl Display for FilterKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match self {
+            FilterKind::MovingAverage => fl!("moving_average"),
+            FilterKind::Exponential => fl!("exponential"),
+            FilterKind::Median => fl!("median"),
+        };
+        write!(f, "{}", str)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use hardware::Value;
+
+    use super::{Filter, FilterKind};
+
+    /// Filter `values`, read one per second.
+    fn run(kind: FilterKind, period: f64, values: &[Value]) -> Vec<Value> {
+        let mut filter = Filter {
+            name: "filter".into(),
+            kind,
+            period,
+            input: Some("temp1".into()),
+            ..Default::default()
+        };
+        let start = Instant::now();
+
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| filter.compute(*value, start + Duration::from_secs(i as u64)))
+            .collect()
+    }
+
+    #[test]
+    fn test_moving_average() {
+        assert_eq!(
+            run(FilterKind::MovingAverage, 2.0, &[40.0, 50.0, 60.0, 70.0]),
+            vec![40.0, 45.0, 50.0, 60.0]
+        );
+    }
+
+    #[test]
+    fn test_median() {
+        // the spike is ignored
+        assert_eq!(
+            run(FilterKind::Median, 2.0, &[40.0, 41.0, 90.0, 42.0, 43.0]),
+            vec![40.0, 40.5, 41.0, 42.0, 43.0]
+        );
+    }
+
+    #[test]
+    fn test_exponential() {
+        let values = run(FilterKind::Exponential, 1.0, &[40.0, 80.0, 80.0]);
+        assert_eq!(values[0], 40.0);
+        let alpha = 1.0 - (-1.0 as Value).exp();
+        assert_eq!(values[1], 40.0 + 40.0 * alpha);
+        assert!(values[1] < values[2] && values[2] < 80.0);
+
+        // without time constant, the value is not filtered
+        assert_eq!(
+            run(FilterKind::Exponential, 0.0, &[40.0, 80.0]),
+            vec![40.0, 80.0]
+        );
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut filter = Filter {
+            input: Some("temp1".into()),
+            ..Default::default()
+        };
+        filter.get_value(40.0).unwrap();
+        assert_eq!(filter.get_value(50.0).unwrap(), 45.0);
+
+        filter.reset();
+        assert_eq!(filter.get_value(60.0).unwrap(), 60.0);
+    }
+
+    #[test]
+    fn test_serde() {
+        let filter: Filter = toml::from_str(
+            r#"
+name = "filter"
+kind = "Median"
+period = 5
+input = "temp1"
+"#,
+        )
+        .unwrap();
+        assert_eq!(filter.kind, FilterKind::Median);
+        assert_eq!(filter.period, 5.0);
+
+        let content = toml::to_string(&filter).unwrap();
+        assert!(content.contains("period = 5\n"));
+    }
+}
@@ -10,26 +10,32 @@ use crate::{
     utils::{has_duplicate, is_sorted, InsertSorted, RemoveElem},
 };
 
-use super::utils::affine::Affine;
+use super::{
+    hysteresis::{Behavior, Hysteresis, HysteresisState},
+    utils::{affine::Affine, decimal, temperature::is_valid_temp},
+};
 
-#[derive(Serialize, Deserialize, Debug, Clone, Eq, Copy)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
 pub struct Coord {
-    pub temp: u8,
+    #[serde(serialize_with = "decimal::serialize")]
+    pub temp: f64,
     pub percent: u8,
 }
 
 impl Hash for Coord {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.temp.hash(state);
+        self.temp.to_bits().hash(state);
     }
 }
 
 impl PartialEq for Coord {
     fn eq(&self, other: &Self) -> bool {
-        self.temp == other.temp
+        self.cmp(other).is_eq()
     }
 }
 
+impl Eq for Coord {}
+
 impl PartialOrd for Coord {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         Some(self.cmp(other))
@@ -38,7 +44,7 @@ impl PartialOrd for Coord {
 
 impl Ord for Coord {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.temp.cmp(&other.temp)
+        self.temp.total_cmp(&other.temp)
     }
 }
 
@@ -58,6 +64,29 @@ pub struct Graph {
     #[serde(rename = "coord")]
     pub coords: Vec<Coord>,
     pub input: Option<String>, // Temp or CustomTemp
+
+    #[serde(flatten)]
+    pub hysteresis: Hysteresis,
+
+    #[serde(skip)]
+    pub hysteresis_state: HysteresisState,
+}
+
+impl Behavior for Graph {
+    fn hysteresis(&mut self) -> (&Hysteresis, &mut HysteresisState) {
+        (&self.hysteresis, &mut self.hysteresis_state)
+    }
+
+    fn bounds(&self) -> (Value, Value) {
+        let percents = self.coords.iter().map(|coord| Value::from(coord.percent));
+        let min = percents.clone().fold(Value::INFINITY, Value::min);
+        let max = percents.fold(Value::NEG_INFINITY, Value::max);
+        (min, max)
+    }
+
+    fn raw_output(&mut self, inputs: &[Value]) -> Result<Value, UpdateError> {
+        Ok(self.curve(inputs[0]))
+    }
 }
 
 impl Default for Graph {
@@ -66,15 +95,17 @@ impl Default for Graph {
             name: Default::default(),
             coords: vec![
                 Coord {
-                    temp: 10,
+                    temp: 10.0,
                     percent: 10,
                 },
                 Coord {
-                    temp: 70,
+                    temp: 70.0,
                     percent: 100,
                 },
             ],
             input: Default::default(),
+            hysteresis: Default::default(),
+            hysteresis_state: Default::default(),
         }
     }
 }
@@ -84,6 +115,10 @@ impl ToNode for Graph {
         let mut deduplicator = BTreeSet::new();
 
         for mut c in self.coords.clone() {
+            if !is_valid_temp(c.temp) {
+                warn!("coord temp {} is out of range", c.temp);
+                continue;
+            }
             if c.percent > 100 {
                 warn!("coord percent is superior to 100");
                 c.percent = 100;
@@ -98,6 +133,14 @@ impl ToNode for Graph {
         debug_assert!(!has_duplicate(&self.coords));
         debug_assert!(is_sorted(&self.coords));
 
+        if !self.hysteresis.is_valid() {
+            warn!(
+                "Graph {}: invalid hysteresis, fall back to the default",
+                self.name
+            );
+            self.hysteresis = Hysteresis::default();
+        }
+
         Node::new(NodeType::Graph(self), app_graph)
     }
 }
@@ -116,7 +159,11 @@ impl Graph {
         temp: &str,
         percent: &str,
     ) -> Result<Coord, Box<dyn std::error::Error>> {
-        let temp = temp.parse::<u8>()?;
+        let temp = temp.parse::<f64>()?;
+
+        if !is_valid_temp(temp) {
+            return Err("Temp is out of range".into());
+        }
 
         let percent = percent.parse::<u8>()?;
 
@@ -137,38 +184,36 @@ impl Graph {
         Ok(coord)
     }
 
-    pub fn get_value(&self, value: Value) -> Result<Value, UpdateError> {
+    fn curve(&self, value: Value) -> Value {
         debug_assert!(!has_duplicate(&self.coords));
         debug_assert!(is_sorted(&self.coords));
 
         let dummy_coord = Coord {
-            temp: value as u8,
+            temp: value,
             percent: 0,
         };
 
-        let res = match self.coords.binary_search(&dummy_coord) {
-            Ok(index) => self.coords[index].percent as Value,
+        match self.coords.binary_search(&dummy_coord) {
+            Ok(index) => self.coords[index].percent.into(),
             Err(index) => {
                 if index == 0 {
-                    self.coords[index].percent as Value
+                    self.coords[index].percent.into()
                 } else if index == self.coords.len() {
-                    self.coords[index - 1].percent as Value
+                    self.coords[index - 1].percent.into()
                 } else {
                     let coord1 = &self.coords[index - 1];
                     let coord2 = &self.coords[index];
 
                     Affine {
-                        xa: coord1.temp.into(),
+                        xa: coord1.temp,
                         ya: coord1.percent.into(),
-                        xb: coord2.temp.into(),
+                        xb: coord2.temp,
                         yb: coord2.percent.into(),
                     }
-                    .calcule(value) as Value
+                    .calcule(value)
                 }
             }
-        };
-
-        Ok(res)
+        }
     }
 
     pub fn add_coord(&mut self, new: Coord) {
@@ -185,46 +230,102 @@ impl Graph {
 
 #[cfg(test)]
 mod test {
-    use crate::{config::graph::Coord, node::IsValid};
+    use crate::{
+        config::{graph::Coord, hysteresis::Behavior},
+        node::IsValid,
+    };
 
     use super::Graph;
 
     #[test]
     fn test_logic() {
-        let graph = Graph {
+        let mut graph = Graph {
             name: "name".into(),
             coords: vec![
                 Coord {
-                    temp: 10,
+                    temp: 10.0,
                     percent: 10,
                 },
                 Coord {
-                    temp: 20,
+                    temp: 20.0,
                     percent: 30,
                 },
                 Coord {
-                    temp: 25,
+                    temp: 25.0,
                     percent: 20,
                 },
                 Coord {
-                    temp: 30,
+                    temp: 30.0,
                     percent: 25,
                 },
                 Coord {
-                    temp: 40,
+                    temp: 40.0,
                     percent: 5,
                 },
             ],
             input: None,
+            ..Default::default()
         };
 
         graph.is_valid();
 
-        assert_eq!(graph.get_value(9).unwrap(), 10);
-        assert_eq!(graph.get_value(50).unwrap(), 5);
+        assert_eq!(graph.get_value(&[9.0]).unwrap(), 10.0);
+        assert_eq!(graph.get_value(&[50.0]).unwrap(), 5.0);
+
+        assert_eq!(graph.get_value(&[22.0]).unwrap(), 26.0);
+        assert_eq!(graph.get_value(&[22.5]).unwrap(), 25.0);
+        assert_eq!(graph.get_value(&[27.0]).unwrap(), 22.0);
+        assert_eq!(graph.get_value(&[35.0]).unwrap(), 15.0);
+    }
+
+    #[test]
+    fn test_wide_range() {
+        let mut graph = Graph {
+            name: "name".into(),
+            coords: vec![
+                Coord {
+                    temp: -20.0,
+                    percent: 0,
+                },
+                Coord {
+                    temp: 0.0,
+                    percent: 20,
+                },
+                Coord {
+                    temp: 400.0,
+                    percent: 100,
+                },
+            ],
+            input: None,
+            ..Default::default()
+        };
+
+        assert_eq!(graph.get_value(&[-30.0]).unwrap(), 0.0);
+        assert_eq!(graph.get_value(&[-5.0]).unwrap(), 15.0);
+        assert!((graph.get_value(&[260.0]).unwrap() - 72.0).abs() < 0.001);
+        assert_eq!(graph.get_value(&[420.0]).unwrap(), 100.0);
 
-        assert_eq!(graph.get_value(22).unwrap(), 26);
-        assert_eq!(graph.get_value(27).unwrap(), 22);
-        assert_eq!(graph.get_value(35).unwrap(), 15);
+        assert!(graph.try_new_coord("-12.5", "10").is_ok());
+        assert!(graph.try_new_coord("-300", "10").is_err());
+    }
+
+    #[test]
+    fn test_serde() {
+        let content = r#"
+name = "Graph"
+coord = [
+    { temp = -15, percent = 0 },
+    { temp = 260.5, percent = 100 },
+]
+"#;
+
+        let graph: Graph = toml::from_str(content).unwrap();
+        assert_eq!(graph.coords[0].temp, -15.0);
+        assert_eq!(graph.coords[1].temp, 260.5);
+
+        let content = toml::to_string(&graph).unwrap();
+        let graph2: Graph = toml::from_str(&content).unwrap();
+        assert!(graph2.coords[0].exact_same(&graph.coords[0]));
+        assert!(graph2.coords[1].exact_same(&graph.coords[1]));
     }
 }
@@ -0,0 +1,225 @@
+use hardware::Value;
+use serde::{Deserialize, Serialize};
+
+use crate::update::UpdateError;
+
+use super::utils::decimal;
+
+/// Keep the output of a behavior stable when its input is noisy.
+/// The default applies every change.
+/// The input is in degrees, or in percents for a Mix.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Hysteresis {
+    /// Change the input must rise, above the last input used, to be used
+    #[serde(
+        default,
+        rename = "hysteresisUp",
+        alias = "hysteresis_up",
+        serialize_with = "decimal::serialize",
+        skip_serializing_if = "is_zero"
+    )]
+    pub up: Value,
+    /// Change the input must fall, below the last input used, to be used
+    #[serde(
+        default,
+        rename = "hysteresisDown",
+        alias = "hysteresis_down",
+        serialize_with = "decimal::serialize",
+        skip_serializing_if = "is_zero"
+    )]
+    pub down: Value,
+    /// Minimum change of the output, in percent, before a new value is emitted.
+    /// The bounds of the behavior are always emitted.
+    #[serde(
+        default,
+        serialize_with = "decimal::serialize",
+        skip_serializing_if = "is_zero"
+    )]
+    pub deadband: Value,
+}
+
+fn is_zero(value: &Value) -> bool {
+    *value == 0.0
+}
+
+/// Kept between update cycles
+#[derive(Debug, Clone, Default)]
+pub struct HysteresisState {
+    inputs: Vec<Value>,
+    output: Option<Value>,
+}
+
+/// A behavior whose output goes through a hysteresis.
+pub trait Behavior {
+    fn hysteresis(&mut self) -> (&Hysteresis, &mut HysteresisState);
+
+    /// Bounds of the output, which are always emitted
+    fn bounds(&self) -> (Value, Value);
+
+    /// Output for the inputs used this cycle, before the deadband
+    fn raw_output(&mut self, inputs: &[Value]) -> Result<Value, UpdateError>;
+
+    fn get_value(&mut self, inputs: &[Value]) -> Result<Value, UpdateError> {
+        let inputs = {
+            let (hysteresis, state) = self.hysteresis();
+            hysteresis.inputs(state, inputs)
+        };
+        let value = self.raw_output(&inputs)?;
+
+        let (min, max) = self.bounds();
+        let (hysteresis, state) = self.hysteresis();
+        Ok(hysteresis.output(state, value, min, max))
+    }
+}
+
+impl Hysteresis {
+    pub fn is_valid(&self) -> bool {
+        let is_valid = |value: Value| value.is_finite() && value >= 0.0;
+
+        is_valid(self.up)
+            && is_valid(self.down)
+            && is_valid(self.deadband)
+            && self.deadband <= 100.0
+    }
+
+    /// Inputs to use this cycle
+    fn inputs(&self, state: &mut HysteresisState, values: &[Value]) -> Vec<Value> {
+        if state.inputs.len() != values.len() {
+            state.inputs.clear();
+        }
+
+        let inputs = values
+            .iter()
+            .enumerate()
+            .map(|(i, &value)| match state.inputs.get(i) {
+                Some(&last) if value > last && value - last < self.up => last,
+                Some(&last) if value < last && last - value < self.down => last,
+                _ => value,
+            })
+            .collect::<Vec<_>>();
+        state.inputs.clone_from(&inputs);
+        inputs
+    }
+
+    /// Output to emit this cycle. `min` and `max` are the bounds of the behavior,
+    /// so a small change doesn't stop the output right before them.
+    fn output(&self, state: &mut HysteresisState, value: Value, min: Value, max: Value) -> Value {
+        let value = match state.output {
+            Some(last) if (value - last).abs() < self.deadband && value != min && value != max => {
+                last
+            }
+            _ => value,
+        };
+        state.output = Some(value);
+        value
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Behavior, Hysteresis, HysteresisState};
+    use crate::config::{linear::Linear, mix::Mix, pid::Pid};
+
+    #[test]
+    fn test_input() {
+        let hysteresis = Hysteresis {
+            up: 1.0,
+            down: 3.0,
+            ..Default::default()
+        };
+        let mut state = HysteresisState::default();
+
+        let inputs = [60.0, 60.5, 59.0, 61.0, 58.5, 57.9, 60.0]
+            .map(|value| hysteresis.inputs(&mut state, &[value])[0]);
+        assert_eq!(inputs, [60.0, 60.0, 60.0, 61.0, 61.0, 57.9, 60.0]);
+
+        // each input has its own last value
+        assert_eq!(hysteresis.inputs(&mut state, &[60.5, 70.0]), [60.5, 70.0]);
+        assert_eq!(hysteresis.inputs(&mut state, &[61.0, 69.0]), [60.5, 70.0]);
+    }
+
+    #[test]
+    fn test_deadband() {
+        let hysteresis = Hysteresis {
+            deadband: 5.0,
+            ..Default::default()
+        };
+        let mut state = HysteresisState::default();
+
+        let outputs = [50.0, 54.0, 46.0, 56.0, 98.0, 100.0]
+            .map(|value| hysteresis.output(&mut state, value, 10.0, 100.0));
+        assert_eq!(outputs, [50.0, 50.0, 50.0, 56.0, 98.0, 100.0]);
+    }
+
+    #[test]
+    fn test_is_valid() {
+        assert!(Hysteresis::default().is_valid());
+        assert!(!Hysteresis {
+            up: -1.0,
+            ..Default::default()
+        }
+        .is_valid());
+        assert!(!Hysteresis {
+            deadband: 101.0,
+            ..Default::default()
+        }
+        .is_valid());
+    }
+
+    #[test]
+    fn test_serde() {
+        let linear: Linear = toml::from_str(
+            r#"
+name = "Linear"
+minTemp = 10
+minSpeed = 10
+maxTemp = 70
+maxSpeed = 100
+hysteresisUp = 1
+hysteresisDown = 2.5
+"#,
+        )
+        .unwrap();
+
+        let hysteresis = Hysteresis {
+            up: 1.0,
+            down: 2.5,
+            deadband: 0.0,
+        };
+        assert_eq!(linear.hysteresis, hysteresis);
+
+        let content = toml::to_string(&linear).unwrap();
+        assert!(content.contains("hysteresisUp = 1\n"));
+        assert!(!content.contains("deadband"));
+        assert_eq!(
+            toml::from_str::<Linear>(&content).unwrap().hysteresis,
+            hysteresis
+        );
+    }
+
+    #[test]
+    fn test_behaviors() {
+        let hysteresis = Hysteresis {
+            deadband: 5.0,
+            ..Default::default()
+        };
+
+        let mut pid = Pid {
+            kp: 1.0,
+            ki: 0.0,
+            setpoint: 50.0,
+            hysteresis: hysteresis.clone(),
+            ..Default::default()
+        };
+        let outputs = [70.0, 73.0, 76.0].map(|value| pid.get_value(&[value]).unwrap());
+        assert_eq!(outputs, [20.0, 20.0, 26.0]);
+
+        let mut mix = Mix {
+            hysteresis,
+            ..Default::default()
+        };
+        let outputs = [[40.0, 50.0], [40.0, 52.0], [56.0, 10.0]]
+            .map(|values| mix.get_value(&values).unwrap());
+        assert_eq!(outputs, [50.0, 50.0, 56.0]);
+    }
+}
@@ -6,20 +6,37 @@ use crate::{
 use hardware::{Hardware, Value};
 use serde::{Deserialize, Serialize};
 
-use super::utils::affine::Affine;
+use super::{
+    hysteresis::{Behavior, Hysteresis, HysteresisState},
+    utils::{affine::Affine, decimal, temperature::is_valid_temp},
+};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Linear {
     pub name: String,
-    #[serde(rename = "minTemp", alias = "min_temp")]
-    pub min_temp: u8,
+    #[serde(
+        rename = "minTemp",
+        alias = "min_temp",
+        serialize_with = "decimal::serialize"
+    )]
+    pub min_temp: f64,
     #[serde(rename = "minSpeed", alias = "min_speed")]
     pub min_speed: u8,
-    #[serde(rename = "maxTemp", alias = "max_temp")]
-    pub max_temp: u8,
+    #[serde(
+        rename = "maxTemp",
+        alias = "max_temp",
+        serialize_with = "decimal::serialize"
+    )]
+    pub max_temp: f64,
     #[serde(rename = "maxSpeed", alias = "max_speed")]
     pub max_speed: u8,
     pub input: Option<String>,
+
+    #[serde(flatten)]
+    pub hysteresis: Hysteresis,
+
+    #[serde(skip)]
+    pub hysteresis_state: HysteresisState,
 }
 
 impl IsValid for Linear {
@@ -28,25 +45,37 @@ impl IsValid for Linear {
     }
 }
 
+impl Behavior for Linear {
+    fn hysteresis(&mut self) -> (&Hysteresis, &mut HysteresisState) {
+        (&self.hysteresis, &mut self.hysteresis_state)
+    }
+
+    fn bounds(&self) -> (Value, Value) {
+        (self.min_speed.into(), self.max_speed.into())
+    }
+
+    fn raw_output(&mut self, inputs: &[Value]) -> Result<Value, UpdateError> {
+        Ok(self.curve(inputs[0]))
+    }
+}
+
 impl Linear {
-    pub fn get_value(&self, value: Value) -> Result<Value, UpdateError> {
-        if value <= self.min_temp.into() {
-            return Ok(self.min_speed.into());
+    fn curve(&self, value: Value) -> Value {
+        if value <= self.min_temp {
+            return self.min_speed.into();
         }
 
-        if value >= self.max_temp.into() {
-            return Ok(self.max_speed.into());
+        if value >= self.max_temp {
+            return self.max_speed.into();
         }
 
-        let res = Affine {
-            xa: self.min_temp.into(),
+        Affine {
+            xa: self.min_temp,
             ya: self.min_speed.into(),
-            xb: self.max_temp.into(),
+            xb: self.max_temp,
             yb: self.max_speed.into(),
         }
-        .calcule(value) as Value;
-
-        Ok(res)
+        .calcule(value)
     }
 }
 
@@ -54,29 +83,38 @@ impl ToNode for Linear {
     fn to_node(mut self, app_graph: &mut AppGraph, _hardware: &Hardware) -> Node {
         let default = Self::default();
 
-        if self.max_temp < self.min_temp {
+        if !is_valid_temp(self.min_temp) {
             self.min_temp = default.min_temp;
-            self.max_temp = default.max_temp;
         }
-
-        if self.max_speed < self.min_speed {
+        if self.min_speed > 100 {
             self.min_speed = default.min_speed;
+        }
+        if !is_valid_temp(self.max_temp) {
+            self.max_temp = default.max_temp;
+        }
+        if self.max_speed > 100 {
             self.max_speed = default.max_speed;
         }
 
-        if self.min_temp > 100 {
+        // checked after the ranges, because they can change a bound
+        if self.max_temp < self.min_temp {
             self.min_temp = default.min_temp;
-        }
-        if self.min_speed > 100 {
-            self.min_speed = default.min_speed;
-        }
-        if self.max_temp > 100 {
             self.max_temp = default.max_temp;
         }
-        if self.max_speed > 100 {
+
+        if self.max_speed < self.min_speed {
+            self.min_speed = default.min_speed;
             self.max_speed = default.max_speed;
         }
 
+        if !self.hysteresis.is_valid() {
+            warn!(
+                "Linear {}: invalid hysteresis, fall back to the default",
+                self.name
+            );
+            self.hysteresis = Hysteresis::default();
+        }
+
         Node::new(NodeType::Linear(self), app_graph)
     }
 }
@@ -85,18 +123,20 @@ impl Default for Linear {
     fn default() -> Self {
         Self {
             name: Default::default(),
-            min_temp: 10,
+            min_temp: 10.0,
             min_speed: 10,
-            max_temp: 70,
+            max_temp: 70.0,
             max_speed: 100,
             input: Default::default(),
+            hysteresis: Default::default(),
+            hysteresis_state: Default::default(),
         }
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::utils::init_test_logging;
+    use crate::{config::hysteresis::Behavior, utils::init_test_logging};
 
     use super::Linear;
 
@@ -104,17 +144,58 @@ mod test {
     fn test_update() {
         init_test_logging();
 
-        let linear = Linear {
+        let mut linear = Linear {
             name: "Linear".into(),
-            min_temp: 10,
+            min_temp: 10.0,
             min_speed: 10,
-            max_temp: 70,
+            max_temp: 70.0,
             max_speed: 100,
             input: Some("temp1".into()),
+            ..Default::default()
         };
 
-        assert!(linear.get_value(9).unwrap() == 10);
-        assert!(linear.get_value(70).unwrap() == 100);
-        assert!(linear.get_value(40).unwrap() == 55);
+        assert!(linear.get_value(&[9.0]).unwrap() == 10.0);
+        assert!(linear.get_value(&[70.0]).unwrap() == 100.0);
+        assert!(linear.get_value(&[40.0]).unwrap() == 55.0);
+        assert!(linear.get_value(&[40.5]).unwrap() == 55.75);
+    }
+
+    #[test]
+    fn test_wide_range() {
+        let mut linear = Linear {
+            name: "Linear".into(),
+            min_temp: -20.0,
+            min_speed: 0,
+            max_temp: 300.0,
+            max_speed: 80,
+            input: Some("temp1".into()),
+            ..Default::default()
+        };
+
+        assert!(linear.get_value(&[-40.0]).unwrap() == 0.0);
+        assert!(linear.get_value(&[-20.0]).unwrap() == 0.0);
+        assert!(linear.get_value(&[140.0]).unwrap() == 40.0);
+        assert!(linear.get_value(&[300.0]).unwrap() == 80.0);
+        assert!(linear.get_value(&[310.0]).unwrap() == 80.0);
+    }
+
+    #[test]
+    fn test_serde() {
+        let content = r#"
+name = "Linear"
+minTemp = 10
+minSpeed = 10
+maxTemp = 70.5
+maxSpeed = 100
+"#;
+
+        let linear: Linear = toml::from_str(content).unwrap();
+        assert_eq!(linear.min_temp, 10.0);
+        assert_eq!(linear.max_temp, 70.5);
+
+        // whole values are still written as integers
+        let content = toml::to_string(&linear).unwrap();
+        assert!(content.contains("minTemp = 10\n"));
+        assert!(content.contains("maxTemp = 70.5\n"));
     }
 }
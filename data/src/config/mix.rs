@@ -0,0 +1,304 @@
+use std::fmt::Display;
+
+use hardware::{Hardware, Value};
+use light_enum::Values;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_graph::AppGraph,
+    node::{IsValid, Node, NodeType, ToNode},
+    update::UpdateError,
+};
+
+use super::{
+    hysteresis::{Behavior, Hysteresis, HysteresisState},
+    utils::decimal,
+};
+
+/// Combine the speeds of several behaviors, to drive one control.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Mix {
+    pub name: String,
+    pub kind: MixKind,
+    pub inputs: Vec<String>,
+    /// Weight of each input, by position, for `Weighted`. Missing weights are 1.
+    #[serde(
+        default,
+        skip_serializing_if = "Vec::is_empty",
+        serialize_with = "decimal::serialize_vec"
+    )]
+    pub weights: Vec<f64>,
+
+    #[serde(flatten)]
+    pub hysteresis: Hysteresis,
+
+    #[serde(skip)]
+    pub hysteresis_state: HysteresisState,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Values, Default, PartialEq, Eq)]
+pub enum MixKind {
+    #[default]
+    Max,
+    Min,
+    Average,
+    /// Clamped to 100%
+    Sum,
+    Weighted,
+}
+
+impl Mix {
+    pub fn weight(&self, index: usize) -> f64 {
+        self.weights.get(index).copied().unwrap_or(1.0)
+    }
+
+    fn mix(&self, values: &[Value]) -> Result<Value, UpdateError> {
+        if values.is_empty() {
+            return Err(UpdateError::NoInputData);
+        }
+
+        let value = match self.kind {
+            MixKind::Max => values.iter().copied().fold(Value::NEG_INFINITY, Value::max),
+            MixKind::Min => values.iter().copied().fold(Value::INFINITY, Value::min),
+            MixKind::Average => values.iter().sum::<Value>() / values.len() as Value,
+            MixKind::Sum => values.iter().sum(),
+            MixKind::Weighted => {
+                let total_weight = (0..values.len()).map(|i| self.weight(i)).sum::<Value>();
+                if total_weight <= 0.0 {
+                    return Err(UpdateError::NoInputData);
+                }
+
+                values
+                    .iter()
+                    .enumerate()
+                    .map(|(i, value)| value * self.weight(i))
+                    .sum::<Value>()
+                    / total_weight
+            }
+        };
+
+        Ok(value.clamp(0.0, 100.0))
+    }
+
+    /// Replace the inputs. When an input is renamed, the weights stay at the same position.
+    /// Otherwise, each input keeps its weight.
+    pub fn set_inputs(&mut self, inputs: Vec<String>) {
+        if inputs.len() != self.inputs.len() {
+            self.weights = inputs
+                .iter()
+                .map(|input| {
+                    self.inputs
+                        .iter()
+                        .position(|previous| previous == input)
+                        .map_or(1.0, |index| self.weight(index))
+                })
+                .collect();
+        }
+        if self.weights.iter().all(|weight| *weight == 1.0) {
+            self.weights.clear();
+        }
+        self.inputs = inputs;
+    }
+
+    pub fn add_input(&mut self, input: String) {
+        let mut inputs = self.inputs.clone();
+        inputs.push(input);
+        self.set_inputs(inputs);
+    }
+
+    pub fn remove_input(&mut self, input: &str) {
+        let inputs = self
+            .inputs
+            .iter()
+            .filter(|previous| *previous != input)
+            .cloned()
+            .collect();
+        self.set_inputs(inputs);
+    }
+}
+
+impl Behavior for Mix {
+    fn hysteresis(&mut self) -> (&Hysteresis, &mut HysteresisState) {
+        (&self.hysteresis, &mut self.hysteresis_state)
+    }
+
+    fn bounds(&self) -> (Value, Value) {
+        (0.0, 100.0)
+    }
+
+    fn raw_output(&mut self, inputs: &[Value]) -> Result<Value, UpdateError> {
+        self.mix(inputs)
+    }
+}
+
+impl IsValid for Mix {
+    fn is_valid(&self) -> bool {
+        !self.inputs.is_empty()
+    }
+}
+
+impl ToNode for Mix {
+    fn to_node(mut self, app_graph: &mut AppGraph, _hardware: &Hardware) -> Node {
+        for weight in &mut self.weights {
+            if !weight.is_finite() || *weight < 0.0 {
+                warn!(
+                    "Mix {}: invalid weight {}, fall back to 1",
+                    self.name, weight
+                );
+                *weight = 1.0;
+            }
+        }
+        if !self.hysteresis.is_valid() {
+            warn!(
+                "Mix {}: invalid hysteresis, fall back to the default",
+                self.name
+            );
+            self.hysteresis = Hysteresis::default();
+        }
+
+        Node::new(NodeType::Mix(self), app_graph)
+    }
+}
+
+impl Display for MixKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match self {
+            MixKind::Max => fl!("max"),
+            MixKind::Min => fl!("min"),
+            MixKind::Average => fl!("average"),
+            MixKind::Sum => fl!("sum"),
+            MixKind::Weighted => fl!("weighted"),
+        };
+        write!(f, "{}", str)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hardware::{
+        snapshot::{HardwareSnapshot, SnapshotBridge},
+        HardwareBridge,
+    };
+
+    use super::{Mix, MixKind};
+    use crate::{
+        app_graph::AppGraph,
+        config::{hysteresis::Behavior, Config},
+        update::Update,
+    };
+
+    fn new_mix(kind: MixKind, weights: Vec<f64>) -> Mix {
+        Mix {
+            name: "mix".into(),
+            kind,
+            inputs: vec!["cpu".into(), "gpu".into()],
+            weights,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_get_value() {
+        let values = [30.0, 80.0];
+
+        let value = |kind| new_mix(kind, Vec::new()).get_value(&values).unwrap();
+        assert_eq!(value(MixKind::Max), 80.0);
+        assert_eq!(value(MixKind::Min), 30.0);
+        assert_eq!(value(MixKind::Average), 55.0);
+        assert_eq!(value(MixKind::Sum), 100.0);
+
+        let mut mix = new_mix(MixKind::Weighted, vec![3.0]);
+        assert_eq!(mix.get_value(&values).unwrap(), 42.5);
+
+        let mut mix = new_mix(MixKind::Weighted, vec![0.0, 0.0]);
+        assert!(mix.get_value(&values).is_err());
+        assert!(mix.get_value(&[]).is_err());
+    }
+
+    #[test]
+    fn test_weights() {
+        let mut mix = new_mix(MixKind::Weighted, vec![3.0, 2.0]);
+
+        // renamed
+        mix.set_inputs(vec!["cpu".into(), "gpu 2".into()]);
+        assert_eq!(mix.weights, vec![3.0, 2.0]);
+
+        mix.remove_input("cpu");
+        assert_eq!(mix.inputs, vec!["gpu 2".to_string()]);
+        assert_eq!(mix.weights, vec![2.0]);
+
+        mix.add_input("nvme".into());
+        assert_eq!(mix.weights, vec![2.0, 1.0]);
+
+        mix.remove_input("gpu 2");
+        assert!(mix.weights.is_empty());
+    }
+
+    #[test]
+    fn test_control_input() {
+        let config: Config = toml::from_str(
+            r#"
+[[Control]]
+name = "control"
+id = "pwm1"
+input = "mix"
+active = true
+
+[[Mix]]
+name = "mix"
+kind = "Max"
+inputs = ["cpu", "gpu"]
+
+[[Flat]]
+name = "cpu"
+value = 40
+
+[[Flat]]
+name = "gpu"
+value = 70
+"#,
+        )
+        .unwrap();
+
+        let snapshot = HardwareSnapshot::from_str(
+            r#"
+[[Control]]
+name = "pwm1"
+id = "pwm1"
+value = 10
+"#,
+        )
+        .unwrap();
+        let mut bridge = SnapshotBridge::from_snapshot(snapshot);
+        let mut app_graph = AppGraph::from_config(config, bridge.hardware());
+        let pwm1 = bridge.hardware().controls[0].clone();
+
+        Update::new()
+            .all(&mut app_graph.nodes, &mut bridge)
+            .unwrap();
+        assert_eq!(bridge.get_control_value(&pwm1).unwrap(), 70.0);
+    }
+
+    #[test]
+    fn test_serde() {
+        let mix: Mix = toml::from_str(
+            r#"
+name = "case"
+kind = "Weighted"
+inputs = ["cpu", "gpu"]
+weights = [2, 1]
+"#,
+        )
+        .unwrap();
+        assert_eq!(mix.kind, MixKind::Weighted);
+        assert_eq!(mix.weights, vec![2.0, 1.0]);
+
+        let mix = new_mix(MixKind::Weighted, vec![2.0, 0.5]);
+        assert!(toml::to_string(&mix)
+            .unwrap()
+            .contains("weights = [2, 0.5]"));
+
+        let mix = new_mix(MixKind::Max, Vec::new());
+        assert!(!toml::to_string(&mix).unwrap().contains("weights"));
+    }
+}
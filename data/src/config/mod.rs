@@ -1,13 +1,22 @@
+pub mod command_temp;
 pub mod control;
 pub mod custom_temp;
 pub mod fan;
+pub mod file_temp;
+pub mod filter;
 pub mod flat;
 pub mod graph;
+pub mod hysteresis;
 pub mod linear;
+pub mod mix;
+pub mod pid;
+pub mod sensor_policy;
 pub mod target;
 pub mod temp;
 
-mod utils;
+pub(crate) mod utils;
+
+pub use utils::temperature::TEMP_RANGE;
 
 #[cfg(test)]
 mod serde_test;
@@ -15,8 +24,9 @@ mod serde_test;
 use crate::{
     app_graph::AppGraph,
     config::{
-        control::Control, custom_temp::CustomTemp, fan::Fan, flat::Flat, graph::Graph,
-        linear::Linear, target::Target, temp::Temp,
+        command_temp::CommandTemp, control::Control, custom_temp::CustomTemp, fan::Fan,
+        file_temp::FileTemp, filter::Filter, flat::Flat, graph::Graph, linear::Linear, mix::Mix,
+        pid::Pid, target::Target, temp::Temp,
     },
     node::{self},
 };
@@ -31,8 +41,14 @@ pub struct Config {
     pub fans: Vec<Fan>,
     #[serde(default, rename = "Temp")]
     pub temps: Vec<Temp>,
+    #[serde(default, rename = "CommandTemp")]
+    pub command_temps: Vec<CommandTemp>,
+    #[serde(default, rename = "FileTemp")]
+    pub file_temps: Vec<FileTemp>,
     #[serde(default, rename = "CustomTemp")]
     pub custom_temps: Vec<CustomTemp>,
+    #[serde(default, rename = "Filter")]
+    pub filters: Vec<Filter>,
     #[serde(default, rename = "Graph")]
     pub graphs: Vec<Graph>,
     #[serde(default, rename = "Flat")]
@@ -41,6 +57,10 @@ pub struct Config {
     pub linears: Vec<Linear>,
     #[serde(default, rename = "Target")]
     pub targets: Vec<Target>,
+    #[serde(default, rename = "Pid")]
+    pub pids: Vec<Pid>,
+    #[serde(default, rename = "Mix")]
+    pub mixes: Vec<Mix>,
 }
 
 impl Config {
@@ -51,13 +71,20 @@ impl Config {
                 node::NodeType::Control(control) => config.controls.push(control.clone()),
                 node::NodeType::Fan(fan) => config.fans.push(fan.clone()),
                 node::NodeType::Temp(temp) => config.temps.push(temp.clone()),
+                node::NodeType::CommandTemp(command_temp) => {
+                    config.command_temps.push(command_temp.clone())
+                }
+                node::NodeType::FileTemp(file_temp) => config.file_temps.push(file_temp.clone()),
                 node::NodeType::CustomTemp(custom_temp) => {
                     config.custom_temps.push(custom_temp.clone())
                 }
+                node::NodeType::Filter(filter) => config.filters.push(filter.clone()),
                 node::NodeType::Graph(graph) => config.graphs.push(graph.clone()),
                 node::NodeType::Flat(flat) => config.flats.push(flat.clone()),
                 node::NodeType::Linear(linear, ..) => config.linears.push(linear.clone()),
                 node::NodeType::Target(target, ..) => config.targets.push(target.clone()),
+                node::NodeType::Pid(pid) => config.pids.push(pid.clone()),
+                node::NodeType::Mix(mix) => config.mixes.push(mix.clone()),
             }
         }
         config
@@ -0,0 +1,326 @@
+use std::time::Instant;
+
+use crate::{
+    app_graph::AppGraph,
+    node::{IsValid, Node, NodeType, ToNode},
+    update::UpdateError,
+};
+use hardware::{Hardware, Value};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    hysteresis::{Behavior, Hysteresis, HysteresisState},
+    utils::{decimal, temperature::is_valid_temp},
+};
+
+/// Closed loop control, which holds its input at `setpoint`.
+/// The output increases when the input is above the setpoint.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Pid {
+    pub name: String,
+    #[serde(serialize_with = "decimal::serialize")]
+    pub setpoint: f64,
+    /// % per °C
+    #[serde(serialize_with = "decimal::serialize")]
+    pub kp: f64,
+    /// % per °C and per second
+    #[serde(serialize_with = "decimal::serialize")]
+    pub ki: f64,
+    /// % per °C/s
+    #[serde(serialize_with = "decimal::serialize")]
+    pub kd: f64,
+    #[serde(rename = "minSpeed", alias = "min_speed")]
+    pub min_speed: u8,
+    #[serde(rename = "maxSpeed", alias = "max_speed")]
+    pub max_speed: u8,
+    pub input: Option<String>,
+
+    #[serde(flatten)]
+    pub hysteresis: Hysteresis,
+
+    #[serde(skip)]
+    pub state: PidState,
+
+    #[serde(skip)]
+    pub hysteresis_state: HysteresisState,
+}
+
+/// Kept between update cycles
+#[derive(Debug, Clone, Default)]
+pub struct PidState {
+    integral: Value,
+    last_error: Option<Value>,
+    last_update: Option<Instant>,
+}
+
+impl Behavior for Pid {
+    fn hysteresis(&mut self) -> (&Hysteresis, &mut HysteresisState) {
+        (&self.hysteresis, &mut self.hysteresis_state)
+    }
+
+    fn bounds(&self) -> (Value, Value) {
+        (self.min_speed.into(), self.max_speed.into())
+    }
+
+    fn raw_output(&mut self, inputs: &[Value]) -> Result<Value, UpdateError> {
+        let now = Instant::now();
+        let dt = self
+            .state
+            .last_update
+            .map(|last_update| now.duration_since(last_update).as_secs_f64());
+        self.state.last_update = Some(now);
+
+        Ok(self.compute(inputs[0], dt))
+    }
+}
+
+impl Pid {
+    /// `dt` is the time since the last cycle, in seconds.
+    /// None on the first cycle, which only uses the proportional term.
+    fn compute(&mut self, value: Value, dt: Option<Value>) -> Value {
+        let min_speed = Value::from(self.min_speed);
+        let max_speed = Value::from(self.max_speed);
+
+        let error = value - self.setpoint;
+
+        let (integral, rate) = match (dt, self.state.last_error) {
+            (Some(dt), Some(last_error)) if dt > 0.0 => {
+                (self.state.integral + error * dt, (error - last_error) / dt)
+            }
+            _ => (self.state.integral, 0.0),
+        };
+
+        let proportional = self.kp * error;
+        let derivative = self.kd * rate;
+
+        // anti-windup: the integral doesn't grow beyond the value
+        // which puts the output on a bound
+        let integral = if self.ki > 0.0 {
+            let bound = |speed: Value| (speed - proportional - derivative) / self.ki;
+            let output = proportional + self.ki * integral + derivative;
+
+            if output > max_speed && error > 0.0 {
+                integral.min(bound(max_speed).max(self.state.integral))
+            } else if output < min_speed && error < 0.0 {
+                integral.max(bound(min_speed).min(self.state.integral))
+            } else {
+                integral
+            }
+        } else {
+            0.0
+        };
+        self.state.integral = integral;
+        self.state.last_error = Some(error);
+
+        let output = proportional + self.ki * integral + derivative;
+        output.clamp(min_speed, max_speed)
+    }
+
+    /// Forget the previous cycles, when the input has no value.
+    pub fn reset(&mut self) {
+        self.state = PidState::default();
+        self.hysteresis_state = HysteresisState::default();
+    }
+}
+
+impl IsValid for Pid {
+    fn is_valid(&self) -> bool {
+        self.input.is_some()
+    }
+}
+
+impl ToNode for Pid {
+    fn to_node(mut self, app_graph: &mut AppGraph, _hardware: &Hardware) -> Node {
+        let default = Self::default();
+
+        let is_valid_gain = |gain: f64| gain.is_finite() && gain >= 0.0;
+
+        if !is_valid_temp(self.setpoint) {
+            self.setpoint = default.setpoint;
+        }
+        if !is_valid_gain(self.kp) {
+            self.kp = default.kp;
+        }
+        if !is_valid_gain(self.ki) {
+            self.ki = default.ki;
+        }
+        if !is_valid_gain(self.kd) {
+            self.kd = default.kd;
+        }
+        if self.min_speed > 100 {
+            self.min_speed = default.min_speed;
+        }
+        if self.max_speed > 100 {
+            self.max_speed = default.max_speed;
+        }
+
+        // checked after the ranges, because they can change a bound
+        if self.max_speed < self.min_speed {
+            self.min_speed = default.min_speed;
+            self.max_speed = default.max_speed;
+        }
+
+        if !self.hysteresis.is_valid() {
+            warn!(
+                "Pid {}: invalid hysteresis, fall back to the default",
+                self.name
+            );
+            self.hysteresis = Hysteresis::default();
+        }
+
+        Node::new(NodeType::Pid(self), app_graph)
+    }
+}
+
+impl Default for Pid {
+    fn default() -> Self {
+        Self {
+            name: Default::default(),
+            setpoint: 60.0,
+            kp: 5.0,
+            ki: 0.1,
+            kd: 0.0,
+            min_speed: 0,
+            max_speed: 100,
+            input: Default::default(),
+            hysteresis: Default::default(),
+            state: Default::default(),
+            hysteresis_state: Default::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use hardware::{
+        snapshot::{HardwareSnapshot, SnapshotBridge},
+        HardwareBridge,
+    };
+
+    use super::Pid;
+    use crate::{app_graph::AppGraph, config::Config, node::NodeType, update::Update};
+
+    fn new_pid(kp: f64, ki: f64, kd: f64) -> Pid {
+        Pid {
+            name: "pid".to_string(),
+            input: Some("temp1".into()),
+            setpoint: 60.0,
+            kp,
+            ki,
+            kd,
+            min_speed: 20,
+            max_speed: 100,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_proportional() {
+        let mut pid = new_pid(5.0, 0.0, 0.0);
+
+        assert_eq!(pid.compute(70.0, None), 50.0);
+        assert_eq!(pid.compute(90.0, Some(1.0)), 100.0);
+        assert_eq!(pid.compute(61.0, Some(1.0)), 20.0);
+        assert_eq!(pid.compute(40.0, Some(1.0)), 20.0);
+    }
+
+    #[test]
+    fn test_integral_and_derivative() {
+        let mut pid = new_pid(0.0, 1.0, 0.0);
+
+        // the first cycle has no time step
+        assert_eq!(pid.compute(90.0, None), 20.0);
+        assert_eq!(pid.compute(90.0, Some(2.0)), 60.0);
+        assert_eq!(pid.compute(90.0, Some(1.0)), 90.0);
+
+        let mut pid = new_pid(0.0, 0.0, 10.0);
+        pid.compute(60.0, None);
+        assert_eq!(pid.compute(64.0, Some(1.0)), 40.0);
+        assert_eq!(pid.compute(64.0, Some(1.0)), 20.0);
+    }
+
+    #[test]
+    fn test_anti_windup() {
+        let mut pid = new_pid(1.0, 1.0, 0.0);
+
+        pid.compute(80.0, None);
+        // saturated for a long time
+        for _ in 0..100 {
+            assert_eq!(pid.compute(80.0, Some(10.0)), 100.0);
+        }
+
+        // the integral stopped where the output reaches the bound: (100 - 1 * 20) / 1
+        assert!(pid.state.integral <= 80.0);
+
+        // the output falls within 3 cycles once the input is below the setpoint,
+        // and keeps falling
+        let outputs = (0..3)
+            .map(|_| pid.compute(55.0, Some(1.0)))
+            .collect::<Vec<_>>();
+        assert!(outputs[2] < 100.0);
+        assert!(outputs.windows(2).all(|pair| pair[1] <= pair[0]));
+        assert!(pid.state.integral <= 80.0);
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut pid = new_pid(0.0, 1.0, 0.0);
+
+        pid.compute(70.0, None);
+        assert_eq!(pid.compute(70.0, Some(5.0)), 50.0);
+
+        pid.reset();
+        assert_eq!(pid.compute(70.0, None), 20.0);
+    }
+
+    #[test]
+    fn test_reset_when_input_has_no_value() {
+        let path = std::env::temp_dir().join("fan-control-pid");
+        fs::write(&path, "70000\n").unwrap();
+
+        let config: Config = toml::from_str(&format!(
+            r#"
+[[FileTemp]]
+name = "temp"
+path = "{}"
+
+[[Pid]]
+name = "pid"
+input = "temp"
+setpoint = 60
+kp = 2
+ki = 1
+kd = 0
+minSpeed = 0
+maxSpeed = 100
+"#,
+            path.display()
+        ))
+        .unwrap();
+
+        let mut bridge = SnapshotBridge::from_snapshot(HardwareSnapshot::default());
+        let mut app_graph = AppGraph::from_config(config, bridge.hardware());
+        let mut update = Update::new();
+
+        let mut update = || {
+            update.all(&mut app_graph.nodes, &mut bridge).unwrap();
+            let node = app_graph
+                .nodes
+                .values()
+                .find(|node| node.name() == "pid")
+                .unwrap();
+            let NodeType::Pid(pid) = &node.node_type else {
+                panic!("not a pid");
+            };
+            (node.value, pid.state.last_error)
+        };
+
+        assert_eq!(update(), (Some(20.0), Some(10.0)));
+        assert!(update().0.unwrap() >= 20.0);
+
+        fs::remove_file(&path).unwrap();
+        assert_eq!(update(), (None, None));
+    }
+}
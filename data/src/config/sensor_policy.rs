@@ -0,0 +1,332 @@
+use hardware::Value;
+use serde::{Deserialize, Serialize};
+
+use crate::update::UpdateError;
+
+use super::utils::decimal;
+
+/// What to do when a sensor fails, or returns an absurd value.
+/// The default keeps every value, and doesn't retry.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct SensorPolicy {
+    /// Reads done again in the same cycle, after a failure
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub retries: u32,
+    /// Cycles during which the last good value is used,
+    /// when the sensor fails or its value is rejected
+    #[serde(
+        default,
+        rename = "holdCycles",
+        alias = "hold_cycles",
+        skip_serializing_if = "is_zero"
+    )]
+    pub hold_cycles: u32,
+    /// Values below are rejected
+    #[serde(
+        default,
+        rename = "minValue",
+        alias = "min_value",
+        serialize_with = "decimal::serialize_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub min_value: Option<Value>,
+    /// Values above are rejected
+    #[serde(
+        default,
+        rename = "maxValue",
+        alias = "max_value",
+        serialize_with = "decimal::serialize_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub max_value: Option<Value>,
+    /// Values further than this from the last good value are rejected.
+    /// A change that lasts more than `holdCycles` cycles (at least one) is accepted.
+    #[serde(
+        default,
+        rename = "maxDelta",
+        alias = "max_delta",
+        serialize_with = "decimal::serialize_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub max_delta: Option<Value>,
+}
+
+fn is_zero(value: &u32) -> bool {
+    *value == 0
+}
+
+/// Counters since the config was loaded, to find flaky sensors.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SensorStats {
+    /// Failed reads, retries included
+    pub failures: u64,
+    /// Values out of bounds, or changing too fast
+    pub rejected: u64,
+    /// Cycles which used the last good value
+    pub held: u64,
+}
+
+impl SensorStats {
+    pub fn is_flaky(&self) -> bool {
+        self.failures > 0 || self.rejected > 0
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SensorHealth {
+    last_good: Option<Value>,
+    /// Cycles since the last good value
+    missed: u32,
+    pub stats: SensorStats,
+}
+
+impl SensorPolicy {
+    pub fn is_valid(&self) -> bool {
+        let finite = |value: Option<Value>| value.is_none_or(Value::is_finite);
+
+        finite(self.min_value)
+            && finite(self.max_value)
+            && finite(self.max_delta)
+            && self.max_delta.is_none_or(|max_delta| max_delta >= 0.0)
+            && match (self.min_value, self.max_value) {
+                (Some(min_value), Some(max_value)) => min_value <= max_value,
+                _ => true,
+            }
+    }
+
+    fn check(&self, health: &SensorHealth, value: Value) -> bool {
+        if !value.is_finite()
+            || self.min_value.is_some_and(|min_value| value < min_value)
+            || self.max_value.is_some_and(|max_value| value > max_value)
+        {
+            return false;
+        }
+
+        match (self.max_delta, health.last_good) {
+            (Some(max_delta), Some(last_good)) => {
+                (value - last_good).abs() <= max_delta || health.missed >= self.hold_cycles.max(1)
+            }
+            _ => true,
+        }
+    }
+
+    /// Apply the policy to `read`, which reads the sensor once.
+    pub fn read(
+        &self,
+        health: &mut SensorHealth,
+        name: &str,
+        mut read: impl FnMut() -> Result<Value, UpdateError>,
+    ) -> Result<Value, UpdateError> {
+        let mut res = read();
+        for _ in 0..self.retries {
+            if res.is_ok() {
+                break;
+            }
+            health.stats.failures += 1;
+            res = read();
+        }
+
+        let res = match res {
+            Ok(value) if self.check(health, value) => Ok(value),
+            Ok(value) => {
+                warn!("{}: value {} rejected", name, value);
+                health.stats.rejected += 1;
+                Err(UpdateError::Rejected(name.to_owned(), value))
+            }
+            Err(e) => {
+                health.stats.failures += 1;
+                Err(e)
+            }
+        };
+
+        match res {
+            Ok(value) => {
+                health.last_good = Some(value);
+                health.missed = 0;
+                Ok(value)
+            }
+            Err(e) => {
+                health.missed = health.missed.saturating_add(1);
+                match health.last_good {
+                    Some(last_good) if health.missed <= self.hold_cycles => {
+                        debug!("{}: hold {} after: {}", name, last_good, e);
+                        health.stats.held += 1;
+                        Ok(last_good)
+                    }
+                    _ => Err(e),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::VecDeque;
+
+    use hardware::Value;
+
+    use super::{SensorHealth, SensorPolicy};
+    use crate::{config::temp::Temp, update::UpdateError};
+
+    /// Apply the policy to each cycle, a cycle being the reads it does.
+    fn run(
+        policy: &SensorPolicy,
+        cycles: Vec<Vec<Option<Value>>>,
+    ) -> (Vec<Option<Value>>, SensorHealth) {
+        let mut health = SensorHealth::default();
+
+        let values = cycles
+            .into_iter()
+            .map(|reads| {
+                let mut reads = VecDeque::from(reads);
+                policy
+                    .read(&mut health, "temp", || {
+                        reads
+                            .pop_front()
+                            .expect("too many reads")
+                            .ok_or(UpdateError::ValueIsNone)
+                    })
+                    .ok()
+            })
+            .collect();
+
+        (values, health)
+    }
+
+    #[test]
+    fn test_default() {
+        let (values, health) = run(
+            &SensorPolicy::default(),
+            vec![vec![Some(40.0)], vec![None], vec![Some(127.0)]],
+        );
+        assert_eq!(values, vec![Some(40.0), None, Some(127.0)]);
+        assert_eq!(health.stats.failures, 1);
+        assert_eq!(health.stats.held, 0);
+    }
+
+    #[test]
+    fn test_retries_and_hold() {
+        let policy = SensorPolicy {
+            retries: 1,
+            hold_cycles: 2,
+            ..Default::default()
+        };
+
+        let (values, health) = run(
+            &policy,
+            vec![
+                vec![Some(40.0)],
+                // the retry succeeds
+                vec![None, Some(41.0)],
+                vec![None, None],
+                vec![None, None],
+                vec![None, None],
+                vec![Some(45.0)],
+            ],
+        );
+        assert_eq!(
+            values,
+            vec![
+                Some(40.0),
+                Some(41.0),
+                Some(41.0),
+                Some(41.0),
+                None,
+                Some(45.0)
+            ]
+        );
+        assert_eq!(health.stats.failures, 7);
+        assert_eq!(health.stats.held, 2);
+        assert!(health.stats.is_flaky());
+    }
+
+    #[test]
+    fn test_rejection() {
+        let policy = SensorPolicy {
+            hold_cycles: 1,
+            min_value: Some(0.0),
+            max_value: Some(110.0),
+            max_delta: Some(10.0),
+            ..Default::default()
+        };
+
+        let (values, health) = run(
+            &policy,
+            vec![
+                vec![Some(40.0)],
+                // a spike
+                vec![Some(90.0)],
+                vec![Some(42.0)],
+                // a real change is accepted after one cycle
+                vec![Some(60.0)],
+                vec![Some(61.0)],
+                vec![Some(127.0)],
+                vec![Some(-1.0)],
+            ],
+        );
+        assert_eq!(
+            values,
+            vec![
+                Some(40.0),
+                Some(40.0),
+                Some(42.0),
+                Some(42.0),
+                Some(61.0),
+                Some(61.0),
+                None
+            ]
+        );
+        assert_eq!(health.stats.rejected, 4);
+        assert_eq!(health.stats.failures, 0);
+    }
+
+    #[test]
+    fn test_is_valid() {
+        assert!(SensorPolicy::default().is_valid());
+        assert!(!SensorPolicy {
+            min_value: Some(50.0),
+            max_value: Some(40.0),
+            ..Default::default()
+        }
+        .is_valid());
+        assert!(!SensorPolicy {
+            max_delta: Some(-1.0),
+            ..Default::default()
+        }
+        .is_valid());
+    }
+
+    #[test]
+    fn test_serde() {
+        let temp: Temp = toml::from_str(
+            r#"
+name = "CPU"
+id = "coretemp"
+retries = 1
+holdCycles = 2
+maxValue = 110
+maxDelta = 12.5
+"#,
+        )
+        .unwrap();
+
+        let policy = SensorPolicy {
+            retries: 1,
+            hold_cycles: 2,
+            min_value: None,
+            max_value: Some(110.0),
+            max_delta: Some(12.5),
+        };
+        assert_eq!(temp.policy, policy);
+
+        let content = toml::to_string(&temp).unwrap();
+        assert!(content.contains("maxValue = 110\n"));
+        assert!(!content.contains("minValue"));
+        assert_eq!(toml::from_str::<Temp>(&content).unwrap().policy, policy);
+
+        // the default is not written
+        let temp: Temp = toml::from_str("name = \"CPU\"").unwrap();
+        assert_eq!(toml::to_string(&temp).unwrap(), "name = \"CPU\"\n");
+    }
+}
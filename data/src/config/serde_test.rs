@@ -13,13 +13,20 @@ use crate::config::Config;
 
 use crate::settings::Settings;
 
+use super::command_temp::CommandTemp;
 use super::control::Control;
 use super::custom_temp::{CustomTemp, CustomTempKind};
 
 use super::fan::Fan;
+use super::file_temp::FileTemp;
+use super::filter::{Filter, FilterKind};
 use super::flat::Flat;
 use super::graph::{Coord, Graph};
+use super::hysteresis::Hysteresis;
 use super::linear::Linear;
+use super::mix::{Mix, MixKind};
+use super::pid::Pid;
+use super::sensor_policy::SensorPolicy;
 use super::target::Target;
 use super::temp::Temp;
 
@@ -140,31 +147,56 @@ fn config1() -> Config {
         temps: vec![Temp {
             name: "Temp".into(),
             hardware_id: Some("temp".into()),
-            temp_h: None,
+            policy: SensorPolicy {
+                retries: 2,
+                hold_cycles: 3,
+                min_value: Some(0.0),
+                max_value: Some(110.0),
+                max_delta: Some(15.5),
+            },
+            ..Default::default()
         }],
         fans: vec![Fan {
             name: "Fan".into(),
             hardware_id: None,
-            fan_h: None,
+            ..Default::default()
+        }],
+        command_temps: vec![CommandTemp {
+            name: "CommandTemp".into(),
+            command: "nvidia-smi --query-gpu=temperature.gpu --format=csv,noheader".into(),
+            ..Default::default()
+        }],
+        file_temps: vec![FileTemp {
+            name: "FileTemp".into(),
+            path: "/sys/class/thermal/thermal_zone0/temp".into(),
+            ..Default::default()
         }],
         custom_temps: vec![CustomTemp::new(
             "CustomTemp".into(),
             CustomTempKind::Max,
             vec!["temp1".into(), "temp2".into()],
         )],
+        filters: vec![Filter {
+            name: "Filter".into(),
+            kind: FilterKind::Median,
+            period: 5.0,
+            input: Some("temp1".into()),
+            ..Default::default()
+        }],
         graphs: vec![Graph {
             name: "Graph".into(),
             coords: vec![
                 Coord {
-                    temp: 10,
+                    temp: 10.0,
                     percent: 10,
                 },
                 Coord {
-                    temp: 50,
+                    temp: 50.0,
                     percent: 30,
                 },
             ],
             input: Some("max".into()),
+            ..Default::default()
         }],
         flats: vec![Flat {
             name: "flat1".into(),
@@ -172,20 +204,38 @@ fn config1() -> Config {
         }],
         linears: vec![Linear {
             name: "Linear".into(),
-            min_temp: 10,
+            min_temp: 10.0,
             min_speed: 10,
-            max_temp: 70,
+            max_temp: 70.0,
             max_speed: 100,
             input: Some("temp1".into()),
+            hysteresis: Hysteresis {
+                up: 1.0,
+                down: 3.0,
+                deadband: 2.0,
+            },
+            ..Default::default()
         }],
         targets: vec![Target {
             name: "Target".into(),
-            idle_temp: 40,
+            idle_temp: 40.0,
             idle_speed: 10,
-            load_temp: 70,
+            load_temp: 70.0,
             load_speed: 100,
             input: Some("temp3".into()),
             idle_has_been_reatch: false,
         }],
+        pids: vec![Pid {
+            name: "Pid".into(),
+            input: Some("temp1".into()),
+            ..Default::default()
+        }],
+        mixes: vec![Mix {
+            name: "Mix".into(),
+            kind: MixKind::Weighted,
+            inputs: vec!["Linear".into(), "Target".into()],
+            weights: vec![2.0, 1.0],
+            ..Default::default()
+        }],
     }
 }
@@ -6,15 +6,25 @@ use crate::{
 use hardware::{Hardware, Value};
 use serde::{Deserialize, Serialize};
 
+use super::utils::{decimal, temperature::is_valid_temp};
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Target {
     pub name: String,
-    #[serde(rename = "idleTemp", alias = "idle_temp")]
-    pub idle_temp: u8,
+    #[serde(
+        rename = "idleTemp",
+        alias = "idle_temp",
+        serialize_with = "decimal::serialize"
+    )]
+    pub idle_temp: f64,
     #[serde(rename = "idleSpeed", alias = "idle_speed")]
     pub idle_speed: u8,
-    #[serde(rename = "loadTemp", alias = "load_temp")]
-    pub load_temp: u8,
+    #[serde(
+        rename = "loadTemp",
+        alias = "load_temp",
+        serialize_with = "decimal::serialize"
+    )]
+    pub load_temp: f64,
     #[serde(rename = "loadSpeed", alias = "load_speed")]
     pub load_speed: u8,
     pub input: Option<String>,
@@ -26,7 +36,7 @@ pub struct Target {
 impl Target {
     pub fn get_value(&mut self, value: Value) -> Result<Value, UpdateError> {
         if self.idle_has_been_reatch {
-            if value < self.load_temp.into() {
+            if value < self.load_temp {
                 return Ok(self.idle_speed.into());
             }
 
@@ -34,7 +44,7 @@ impl Target {
             return Ok(self.load_speed.into());
         }
 
-        if value > self.idle_temp.into() {
+        if value > self.idle_temp {
             return Ok(self.load_speed.into());
         }
 
@@ -53,13 +63,13 @@ impl ToNode for Target {
     fn to_node(mut self, app_graph: &mut AppGraph, _hardware: &Hardware) -> Node {
         let default = Self::default();
 
-        if self.idle_temp > 100 {
+        if !is_valid_temp(self.idle_temp) {
             self.idle_temp = default.idle_temp;
         }
         if self.idle_speed > 100 {
             self.idle_speed = default.idle_speed;
         }
-        if self.load_temp > 100 {
+        if !is_valid_temp(self.load_temp) {
             self.load_temp = default.load_temp;
         }
         if self.load_speed > 100 {
@@ -74,9 +84,9 @@ impl Default for Target {
     fn default() -> Self {
         Self {
             name: Default::default(),
-            idle_temp: 40,
+            idle_temp: 40.0,
             idle_speed: 10,
-            load_temp: 70,
+            load_temp: 70.0,
             load_speed: 100,
             input: Default::default(),
             idle_has_been_reatch: false,
@@ -98,16 +108,35 @@ mod test {
         let mut target = Target {
             name: "linear".to_string(),
             input: Some("temp1".into()),
-            idle_temp: 40,
+            idle_temp: 40.0,
             idle_speed: 10,
-            load_temp: 70,
+            load_temp: 70.0,
             load_speed: 100,
             idle_has_been_reatch: false,
         };
 
-        assert!(target.get_value(55).unwrap() == 100);
-        assert!(target.get_value(30).unwrap() == 10);
-        assert!(target.get_value(55).unwrap() == 10);
-        assert!(target.get_value(70).unwrap() == 100);
+        assert!(target.get_value(55.0).unwrap() == 100.0);
+        assert!(target.get_value(30.0).unwrap() == 10.0);
+        assert!(target.get_value(55.0).unwrap() == 10.0);
+        assert!(target.get_value(69.5).unwrap() == 10.0);
+        assert!(target.get_value(70.0).unwrap() == 100.0);
+    }
+
+    #[test]
+    fn test_below_zero() {
+        let mut target = Target {
+            name: "target".to_string(),
+            input: Some("temp1".into()),
+            idle_temp: -10.0,
+            idle_speed: 0,
+            load_temp: 5.0,
+            load_speed: 50,
+            idle_has_been_reatch: false,
+        };
+
+        assert!(target.get_value(0.0).unwrap() == 50.0);
+        assert!(target.get_value(-15.0).unwrap() == 0.0);
+        assert!(target.get_value(0.0).unwrap() == 0.0);
+        assert!(target.get_value(5.0).unwrap() == 50.0);
     }
 }
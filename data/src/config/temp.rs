@@ -1,6 +1,6 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
-use hardware::{HSensor, Hardware, HardwareBridge, Value};
+use hardware::{HSensor, Hardware, HardwareBridge, HardwareValues, Value};
 use serde::{Deserialize, Serialize};
 
 use crate::{
@@ -9,22 +9,43 @@ use crate::{
     update::UpdateError,
 };
 
+use super::{
+    sensor_policy::{SensorHealth, SensorPolicy},
+    utils::hardware_id::find_hardware,
+};
+
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Temp {
     pub name: String,
     #[serde(rename = "id")]
     pub hardware_id: Option<String>,
 
+    #[serde(flatten)]
+    pub policy: SensorPolicy,
+
     #[serde(skip)]
-    pub temp_h: Option<Rc<HSensor>>,
+    pub temp_h: Option<Arc<HSensor>>,
+
+    #[serde(skip)]
+    pub health: SensorHealth,
 }
 
 impl Temp {
-    pub fn get_value<H: HardwareBridge>(&self, bridge: &mut H) -> Result<Value, UpdateError> {
+    /// The first read uses the value of `values`, when there is one.
+    pub fn get_value<H: HardwareBridge>(
+        &mut self,
+        values: &mut HardwareValues,
+        bridge: &mut H,
+    ) -> Result<Value, UpdateError> {
         match &self.temp_h {
-            Some(temp_h) => bridge
-                .get_sensor_value(temp_h)
-                .map_err(UpdateError::Hardware),
+            Some(temp_h) => {
+                let mut read = values.take_sensor(temp_h);
+                self.policy.read(&mut self.health, &self.name, || {
+                    read.take()
+                        .unwrap_or_else(|| bridge.get_sensor_value(temp_h))
+                        .map_err(UpdateError::Hardware)
+                })
+            }
             None => Err(UpdateError::NodeIsInvalid(self.name.clone())),
         }
     }
@@ -38,21 +59,29 @@ impl IsValid for Temp {
 
 impl ToNode for Temp {
     fn to_node(mut self, app_graph: &mut AppGraph, hardware: &Hardware) -> Node {
-        match &self.hardware_id {
-            Some(hardware_id) => {
-                match hardware
-                    .temps
-                    .iter()
-                    .find(|temp_h| &temp_h.hardware_id == hardware_id)
-                {
-                    Some(temp_h) => self.temp_h = Some(temp_h.clone()),
-                    None => {
-                        warn!("Temp to Node, hardware_id not found. {} from config not found. Fall back to no id", hardware_id);
-                        self.hardware_id.take();
-                        self.temp_h.take();
+        if !self.policy.is_valid() {
+            warn!(
+                "Temp {}: invalid sensor policy, fall back to the default",
+                self.name
+            );
+            self.policy = SensorPolicy::default();
+        }
+
+        match self.hardware_id.clone() {
+            Some(hardware_id) => match find_hardware(&hardware_id, &hardware.temps) {
+                Some(temp_h) => {
+                    if temp_h.hardware_id != hardware_id {
+                        app_graph.add_rebound(&self.name, &hardware_id, &temp_h.hardware_id);
+                        self.hardware_id = Some(temp_h.hardware_id.clone());
                     }
+                    self.temp_h = Some(temp_h.clone())
                 }
-            }
+                None => {
+                    // the id is kept, so the temp is bound if it appears after a rescan
+                    warn!("Temp to Node, hardware_id not found. {} from config not found. Not bound until a rescan finds it", hardware_id);
+                    self.temp_h.take();
+                }
+            },
             None => {
                 if self.temp_h.is_some() {
                     warn!("Temp to Node: inconsistent internal index");
@@ -0,0 +1,110 @@
+use std::sync::Arc;
+
+use hardware::HItem;
+
+/// Find the hardware with this id. If none match, fall back to
+/// the hardware which previously used this id, if any.
+pub fn find_hardware<'a, H: HItem>(
+    hardware_id: &str,
+    hardwares: &'a [Arc<H>],
+) -> Option<&'a Arc<H>> {
+    hardwares
+        .iter()
+        .find(|h| h.id() == hardware_id)
+        .or_else(|| {
+            hardwares
+                .iter()
+                .find(|h| h.legacy_ids().iter().any(|id| id == hardware_id))
+        })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Binding {
+    Bound,
+    Unbound,
+}
+
+/// After a rescan, bind `hardware_h` if the hardware with `hardware_id` appeared,
+/// or unbind it if it disappeared. The id is kept, so the node can be bound
+/// again when the hardware comes back.
+pub fn rebind_hardware<H: HItem>(
+    hardware_id: &Option<String>,
+    hardware_h: &mut Option<Arc<H>>,
+    hardwares: &[Arc<H>],
+    is_used: impl Fn(&H) -> bool,
+) -> Option<Binding> {
+    // the hardware could be back in the same rescan, with another handle
+    let unbound = match hardware_h {
+        Some(current) if hardwares.iter().any(|h| Arc::ptr_eq(h, current)) => return None,
+        Some(_) => {
+            hardware_h.take();
+            Some(Binding::Unbound)
+        }
+        None => None,
+    };
+
+    let Some(found) = hardware_id
+        .as_ref()
+        .and_then(|hardware_id| find_hardware(hardware_id, hardwares))
+    else {
+        return unbound;
+    };
+
+    if is_used(found) {
+        warn!(
+            "Hardware id \"{}\" is already used by another control.",
+            found.id()
+        );
+        return unbound;
+    }
+
+    *hardware_h = Some(found.clone());
+    Some(Binding::Bound)
+}
+
+#[cfg(test)]
+mod test {
+    use hardware::{
+        snapshot::{HardwareSnapshot, SnapshotBridge},
+        HardwareBridge,
+    };
+
+    use super::find_hardware;
+
+    #[test]
+    fn test_find_hardware() {
+        let bridge = SnapshotBridge::from_snapshot(
+            HardwareSnapshot::from_str(
+                r#"
+[[Temp]]
+name = "temp1"
+id = "platform/nct6775.656/nct6775/temp1_input"
+legacyIds = ["SYSTIN-nct6775-isa-0290-temp1_input"]
+
+[[Temp]]
+name = "temp2"
+id = "SYSTIN-nct6775-isa-0290-temp1_input"
+"#,
+            )
+            .unwrap(),
+        );
+        let temps = &bridge.hardware().temps;
+
+        let find = |id: &str| find_hardware(id, temps).map(|temp| temp.name.as_str());
+
+        assert_eq!(
+            find("platform/nct6775.656/nct6775/temp1_input"),
+            Some("temp1")
+        );
+        // the current id comes before the legacy ones
+        assert_eq!(find("SYSTIN-nct6775-isa-0290-temp1_input"), Some("temp2"));
+        assert_eq!(find("unknown"), None);
+
+        let temps = &temps[..1];
+        assert_eq!(
+            find_hardware("SYSTIN-nct6775-isa-0290-temp1_input", temps)
+                .map(|temp| temp.name.as_str()),
+            Some("temp1")
+        );
+    }
+}
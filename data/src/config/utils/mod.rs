@@ -1 +1,4 @@
 pub mod affine;
+pub mod decimal;
+pub mod hardware_id;
+pub mod temperature;
@@ -0,0 +1,9 @@
+use std::ops::RangeInclusive;
+
+/// Temperatures accepted in the config, in °C.
+/// Sensors outside can go below zero, and some probes above 255.
+pub const TEMP_RANGE: RangeInclusive<f64> = -273.15..=1000.0;
+
+pub fn is_valid_temp(temp: f64) -> bool {
+    TEMP_RANGE.contains(&temp)
+}
@@ -4,7 +4,7 @@ use std::{
 };
 
 use directories::ProjectDirs;
-use hardware::Hardware;
+use hardware::snapshot::HardwareSnapshot;
 
 use thiserror::Error;
 use utils::{APP, ORG, QUALIFIER};
@@ -139,10 +139,10 @@ impl DirManager {
         }
     }
 
-    pub fn serialize_hardware(&self, hardware: &Hardware) {
+    pub fn serialize_hardware(&self, snapshot: &HardwareSnapshot) {
         let hardware_file_path = self.hardware_file_path();
 
-        if let Err(e) = serialize(&hardware_file_path, hardware) {
+        if let Err(e) = serialize(&hardware_file_path, snapshot) {
             warn!("{}", e);
         } else {
             println!(
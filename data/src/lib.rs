@@ -5,16 +5,18 @@ extern crate log;
 pub mod localize;
 
 pub mod app_graph;
+pub mod calibration;
 pub mod config;
 pub mod dir_manager;
 pub mod id;
 pub mod node;
 pub mod settings;
 pub mod update;
+pub mod update_loop;
 pub mod utils;
 
-use crate::app_graph::AppGraph;
-use hardware::HardwareBridge;
+use crate::app_graph::{AppGraph, HardwareChanges};
+use hardware::{HardwareBridge, HardwareError};
 use update::Update;
 
 use crate::dir_manager::DirManager;
@@ -24,4 +26,17 @@ pub struct AppState<H: HardwareBridge> {
     pub bridge: H,
     pub app_graph: AppGraph,
     pub update: Update,
+    /// The bridge doesn't write to the hardware
+    pub dry_run: bool,
+}
+
+impl<H: HardwareBridge> AppState<H> {
+    /// Look for hardware that appeared or disappeared, and bind the nodes again.
+    pub fn rescan_hardware(&mut self) -> Result<HardwareChanges, HardwareError> {
+        if !self.bridge.rescan()? {
+            return Ok(HardwareChanges::default());
+        }
+
+        Ok(self.app_graph.rebind_hardware(self.bridge.hardware()))
+    }
 }
@@ -1,4 +1,3 @@
-use std::cmp::Ordering;
 use std::vec;
 
 use derive_more::{Display, Unwrap};
@@ -8,8 +7,9 @@ use light_enum::LightEnum;
 use crate::app_graph::{AppGraph, Nodes};
 
 use crate::config::{
-    control::Control, custom_temp::CustomTemp, fan::Fan, flat::Flat, graph::Graph, linear::Linear,
-    target::Target, temp::Temp,
+    command_temp::CommandTemp, control::Control, custom_temp::CustomTemp, fan::Fan,
+    file_temp::FileTemp, filter::Filter, flat::Flat, graph::Graph, linear::Linear, mix::Mix,
+    pid::Pid, target::Target, temp::Temp,
 };
 
 use crate::id::Id;
@@ -20,11 +20,16 @@ pub enum NodeType {
     Control(Control),
     Fan(Fan),
     Temp(Temp),
+    CommandTemp(CommandTemp),
+    FileTemp(FileTemp),
     CustomTemp(CustomTemp),
+    Filter(Filter),
     Graph(Graph),
     Flat(Flat),
     Linear(Linear),
     Target(Target),
+    Pid(Pid),
+    Mix(Mix),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -190,9 +195,9 @@ impl Node {
     pub fn value_text(&self, kind: &ValueKind) -> String {
         match self.value {
             Some(val) => match kind {
-                ValueKind::Celsius => fl!("value_celsius", value = val),
-                ValueKind::Porcentage => fl!("value_percentage", value = val),
-                ValueKind::RPM => fl!("value_rpm", value = val),
+                ValueKind::Celsius => fl!("value_celsius", value = format!("{:.1}", val)),
+                ValueKind::Porcentage => fl!("value_percentage", value = val.round()),
+                ValueKind::RPM => fl!("value_rpm", value = val.round()),
             },
             None => fl!("no_value"),
         }
@@ -215,11 +220,16 @@ impl NodeType {
             NodeType::Control(control) => &control.name,
             NodeType::Fan(fan) => &fan.name,
             NodeType::Temp(temp) => &temp.name,
+            NodeType::CommandTemp(command_temp) => &command_temp.name,
+            NodeType::FileTemp(file_temp) => &file_temp.name,
             NodeType::CustomTemp(custom_temp) => &custom_temp.name,
+            NodeType::Filter(filter) => &filter.name,
             NodeType::Graph(graph) => &graph.name,
             NodeType::Flat(flat) => &flat.name,
             NodeType::Linear(linear, ..) => &linear.name,
             NodeType::Target(target, ..) => &target.name,
+            NodeType::Pid(pid) => &pid.name,
+            NodeType::Mix(mix) => &mix.name,
         }
     }
     pub fn set_name(&mut self, name: String) {
@@ -227,11 +237,16 @@ impl NodeType {
             NodeType::Control(i) => i.name = name,
             NodeType::Fan(i) => i.name = name,
             NodeType::Temp(i) => i.name = name,
+            NodeType::CommandTemp(i) => i.name = name,
+            NodeType::FileTemp(i) => i.name = name,
             NodeType::CustomTemp(i) => i.name = name,
+            NodeType::Filter(i) => i.name = name,
             NodeType::Graph(i) => i.name = name,
             NodeType::Flat(i) => i.name = name,
             NodeType::Linear(i, ..) => i.name = name,
             NodeType::Target(i, ..) => i.name = name,
+            NodeType::Pid(i) => i.name = name,
+            NodeType::Mix(i) => i.name = name,
         }
     }
 
@@ -240,11 +255,16 @@ impl NodeType {
             NodeType::Control(control) => control.is_valid(),
             NodeType::Fan(fan) => fan.is_valid(),
             NodeType::Temp(temp) => temp.is_valid(),
+            NodeType::CommandTemp(command_temp) => command_temp.is_valid(),
+            NodeType::FileTemp(file_temp) => file_temp.is_valid(),
             NodeType::CustomTemp(custom_temp) => custom_temp.is_valid(),
+            NodeType::Filter(filter) => filter.is_valid(),
             NodeType::Graph(graph) => graph.is_valid(),
             NodeType::Flat(flat) => flat.is_valid(),
             NodeType::Linear(linear, ..) => linear.is_valid(),
             NodeType::Target(target, ..) => target.is_valid(),
+            NodeType::Pid(pid) => pid.is_valid(),
+            NodeType::Mix(mix) => mix.is_valid(),
         }
     }
 
@@ -253,11 +273,16 @@ impl NodeType {
             NodeType::Control(i) => i.input.clone().map_or(Vec::new(), |i| vec![i]),
             NodeType::Fan(_) => Vec::new(),
             NodeType::Temp(_) => Vec::new(),
+            NodeType::CommandTemp(_) => Vec::new(),
+            NodeType::FileTemp(_) => Vec::new(),
             NodeType::CustomTemp(i) => i.inputs.clone(),
+            NodeType::Filter(i) => i.input.clone().map_or(Vec::new(), |i| vec![i]),
             NodeType::Graph(i) => i.input.clone().map_or(Vec::new(), |i| vec![i]),
             NodeType::Flat(_) => Vec::new(),
             NodeType::Linear(i, ..) => i.input.clone().map_or(Vec::new(), |i| vec![i]),
             NodeType::Target(i, ..) => i.input.clone().map_or(Vec::new(), |i| vec![i]),
+            NodeType::Pid(i) => i.input.clone().map_or(Vec::new(), |i| vec![i]),
+            NodeType::Mix(i) => i.inputs.clone(),
         }
     }
 
@@ -274,6 +299,14 @@ impl NodeType {
             NodeType::CustomTemp(i) => {
                 i.inputs = inputs;
             }
+            NodeType::Filter(i) => match inputs.first() {
+                Some(input) => {
+                    let _ = i.input.insert(input.clone());
+                }
+                None => {
+                    i.input.take();
+                }
+            },
             NodeType::Graph(i) => match inputs.first() {
                 Some(input) => {
                     let _ = i.input.insert(input.clone());
@@ -298,8 +331,19 @@ impl NodeType {
                     i.input.take();
                 }
             },
+            NodeType::Pid(i) => match inputs.first() {
+                Some(input) => {
+                    let _ = i.input.insert(input.clone());
+                }
+                None => {
+                    i.input.take();
+                }
+            },
+            NodeType::Mix(i) => i.set_inputs(inputs),
             NodeType::Fan(_) => {}
             NodeType::Temp(_) => {}
+            NodeType::CommandTemp(_) => {}
+            NodeType::FileTemp(_) => {}
             NodeType::Flat(_) => {}
         };
     }
@@ -311,14 +355,61 @@ impl NodeType {
                 NodeTypeLight::Graph,
                 NodeTypeLight::Target,
                 NodeTypeLight::Linear,
+                NodeTypeLight::Pid,
+                NodeTypeLight::Mix,
             ],
             NodeType::Fan(..) => &[],
             NodeType::Temp(..) => &[],
-            NodeType::CustomTemp(..) => &[NodeTypeLight::Temp],
-            NodeType::Graph(..) => &[NodeTypeLight::Temp, NodeTypeLight::CustomTemp],
+            NodeType::CommandTemp(..) => &[],
+            NodeType::FileTemp(..) => &[],
+            NodeType::CustomTemp(..) => &[
+                NodeTypeLight::Temp,
+                NodeTypeLight::CommandTemp,
+                NodeTypeLight::FileTemp,
+                NodeTypeLight::Filter,
+            ],
+            NodeType::Filter(..) => &[
+                NodeTypeLight::Temp,
+                NodeTypeLight::CommandTemp,
+                NodeTypeLight::FileTemp,
+                NodeTypeLight::CustomTemp,
+            ],
+            NodeType::Graph(..) => &[
+                NodeTypeLight::Temp,
+                NodeTypeLight::CommandTemp,
+                NodeTypeLight::FileTemp,
+                NodeTypeLight::CustomTemp,
+                NodeTypeLight::Filter,
+            ],
             NodeType::Flat(..) => &[],
-            NodeType::Linear(..) => &[NodeTypeLight::Temp, NodeTypeLight::CustomTemp],
-            NodeType::Target(..) => &[NodeTypeLight::Temp, NodeTypeLight::CustomTemp],
+            NodeType::Linear(..) => &[
+                NodeTypeLight::Temp,
+                NodeTypeLight::CommandTemp,
+                NodeTypeLight::FileTemp,
+                NodeTypeLight::CustomTemp,
+                NodeTypeLight::Filter,
+            ],
+            NodeType::Target(..) => &[
+                NodeTypeLight::Temp,
+                NodeTypeLight::CommandTemp,
+                NodeTypeLight::FileTemp,
+                NodeTypeLight::CustomTemp,
+                NodeTypeLight::Filter,
+            ],
+            NodeType::Pid(..) => &[
+                NodeTypeLight::Temp,
+                NodeTypeLight::CommandTemp,
+                NodeTypeLight::FileTemp,
+                NodeTypeLight::CustomTemp,
+                NodeTypeLight::Filter,
+            ],
+            NodeType::Mix(..) => &[
+                NodeTypeLight::Flat,
+                NodeTypeLight::Graph,
+                NodeTypeLight::Target,
+                NodeTypeLight::Linear,
+                NodeTypeLight::Pid,
+            ],
         }
     }
 
@@ -327,22 +418,38 @@ impl NodeType {
             NodeType::Control(..) => NbInput::One,
             NodeType::Fan(..) => NbInput::Zero,
             NodeType::Temp(..) => NbInput::Zero,
+            NodeType::CommandTemp(..) => NbInput::Zero,
+            NodeType::FileTemp(..) => NbInput::Zero,
             NodeType::CustomTemp(..) => NbInput::Infinity,
+            NodeType::Filter(..) => NbInput::One,
             NodeType::Graph(..) => NbInput::One,
             NodeType::Flat(..) => NbInput::Zero,
             NodeType::Linear(..) => NbInput::One,
             NodeType::Target(..) => NbInput::One,
+            NodeType::Pid(..) => NbInput::One,
+            NodeType::Mix(..) => NbInput::Infinity,
         }
     }
 
     pub fn is_sensor(&self) -> bool {
-        matches!(self, NodeType::Fan(..) | NodeType::Temp(..))
+        matches!(
+            self,
+            NodeType::Fan(..)
+                | NodeType::Temp(..)
+                | NodeType::CommandTemp(..)
+                | NodeType::FileTemp(..)
+        )
     }
 
     pub fn is_behavior(&self) -> bool {
         matches!(
             self,
-            NodeType::Graph(..) | NodeType::Flat(..) | NodeType::Linear(..) | NodeType::Target(..)
+            NodeType::Graph(..)
+                | NodeType::Flat(..)
+                | NodeType::Linear(..)
+                | NodeType::Target(..)
+                | NodeType::Pid(..)
+                | NodeType::Mix(..)
         )
     }
 
@@ -350,41 +457,408 @@ impl NodeType {
         matches!(self, NodeType::Control(..))
     }
 
-    pub fn compare_update_priority(&self, other: &Self) -> Ordering {
+    /// Nodes are updated by increasing rank, so the inputs of a node
+    /// are updated before it.
+    pub fn rank(&self) -> u8 {
         match self {
-            NodeType::Control(_) => match other {
-                NodeType::Control(_) => Ordering::Equal,
-                _ => Ordering::Greater,
-            },
-            NodeType::Fan(_) => {
-                if other.is_sensor() {
-                    Ordering::Equal
-                } else {
-                    Ordering::Less
-                }
+            NodeType::Fan(..)
+            | NodeType::Temp(..)
+            | NodeType::CommandTemp(..)
+            | NodeType::FileTemp(..) => 0,
+            // a filter of a custom temp is updated after it by the recursion
+            NodeType::Filter(..) => 1,
+            NodeType::CustomTemp(..) => 2,
+            NodeType::Graph(..)
+            | NodeType::Flat(..)
+            | NodeType::Linear(..)
+            | NodeType::Target(..)
+            | NodeType::Pid(..) => 3,
+            // after the behaviors it combines
+            NodeType::Mix(..) => 4,
+            NodeType::Control(..) => 5,
+        }
+    }
+
+    /// Keep the state of `previous`, this node before the graph was replaced,
+    /// when it still applies. Return false when it doesn't, for example
+    /// when a control doesn't use the same hardware anymore.
+    pub fn carry_state(&mut self, previous: &NodeType) -> bool {
+        match (self, previous) {
+            (NodeType::Control(control), NodeType::Control(previous))
+                if control.control_h.is_some() && control.hardware_id == previous.hardware_id =>
+            {
+                control.mode_set.clone_from(&previous.mode_set);
+                control.output_set = previous.output_set;
+                control.missed_cycles = previous.missed_cycles;
+                control.output_read = previous.output_read;
+                control.takeovers = previous.takeovers;
+                control.slewed = previous.slewed;
+            }
+            (NodeType::Fan(fan), NodeType::Fan(previous))
+                if fan.hardware_id == previous.hardware_id =>
+            {
+                fan.health.clone_from(&previous.health);
             }
-            NodeType::Temp(_) => {
-                if other.is_sensor() {
-                    Ordering::Equal
-                } else {
-                    Ordering::Less
+            (NodeType::Temp(temp), NodeType::Temp(previous))
+                if temp.hardware_id == previous.hardware_id =>
+            {
+                temp.health.clone_from(&previous.health);
+            }
+            // don't run the command again before its interval
+            (NodeType::CommandTemp(command), NodeType::CommandTemp(previous))
+                if command.command == previous.command
+                    && command.regex == previous.regex
+                    && command.json_pointer == previous.json_pointer =>
+            {
+                command.cache = previous.cache.share();
+                command.health.clone_from(&previous.health);
+            }
+            (NodeType::FileTemp(file), NodeType::FileTemp(previous))
+                if file.path == previous.path =>
+            {
+                file.health.clone_from(&previous.health);
+            }
+            (NodeType::Filter(filter), NodeType::Filter(previous))
+                if filter.input == previous.input =>
+            {
+                filter.state.clone_from(&previous.state);
+            }
+            (NodeType::Graph(graph), NodeType::Graph(previous))
+                if graph.input == previous.input =>
+            {
+                graph
+                    .hysteresis_state
+                    .clone_from(&previous.hysteresis_state);
+            }
+            (NodeType::Linear(linear), NodeType::Linear(previous))
+                if linear.input == previous.input =>
+            {
+                linear
+                    .hysteresis_state
+                    .clone_from(&previous.hysteresis_state);
+            }
+            (NodeType::Target(target), NodeType::Target(previous))
+                if target.input == previous.input =>
+            {
+                target.idle_has_been_reatch = previous.idle_has_been_reatch;
+            }
+            (NodeType::Pid(pid), NodeType::Pid(previous)) if pid.input == previous.input => {
+                pid.state.clone_from(&previous.state);
+                pid.hysteresis_state.clone_from(&previous.hysteresis_state);
+            }
+            (NodeType::Mix(mix), NodeType::Mix(previous)) if mix.inputs == previous.inputs => {
+                mix.hysteresis_state.clone_from(&previous.hysteresis_state);
+            }
+            (NodeType::CustomTemp(_), NodeType::CustomTemp(_))
+            | (NodeType::Flat(_), NodeType::Flat(_)) => {}
+            _ => return false,
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use hardware::{
+        snapshot::{HardwareSnapshot, SnapshotBridge},
+        Hardware, HardwareBridge,
+    };
+
+    use crate::{app_graph::AppGraph, config::Config, node::NodeType, update::Update};
+
+    #[test]
+    fn test_rank() {
+        let config: Config = toml::from_str(
+            r#"
+[[Fan]]
+name = "fan1"
+
+[[Temp]]
+name = "temp1"
+
+[[Temp]]
+name = "temp2"
+
+[[CommandTemp]]
+name = "command"
+command = "echo 40"
+
+[[FileTemp]]
+name = "file"
+path = "/tmp/temp"
+
+[[CustomTemp]]
+name = "custom1"
+kind = "Average"
+inputs = ["filter2", "temp1"]
+
+[[CustomTemp]]
+name = "custom2"
+kind = "Max"
+inputs = ["command", "file"]
+
+[[Filter]]
+name = "filter1"
+kind = "Median"
+period = 5
+input = "temp1"
+
+[[Filter]]
+name = "filter2"
+kind = "Exponential"
+period = 3
+input = "temp2"
+
+[[Graph]]
+name = "graph1"
+input = "filter1"
+
+[[Graph.coord]]
+temp = 50
+percent = 30
+
+[[Linear]]
+name = "linear1"
+minTemp = 10
+minSpeed = 10
+maxTemp = 70
+maxSpeed = 100
+input = "custom2"
+
+[[Linear]]
+name = "linear2"
+minTemp = 10
+minSpeed = 10
+maxTemp = 70
+maxSpeed = 100
+input = "filter2"
+
+[[Target]]
+name = "target1"
+idleTemp = 40
+idleSpeed = 10
+loadTemp = 70
+loadSpeed = 100
+input = "temp1"
+
+[[Pid]]
+name = "pid1"
+setpoint = 60
+kp = 2
+ki = 0.5
+kd = 0
+minSpeed = 0
+maxSpeed = 100
+input = "filter1"
+
+[[Flat]]
+name = "flat1"
+value = 50
+
+[[Flat]]
+name = "flat2"
+value = 70
+
+[[Mix]]
+name = "mix1"
+kind = "Max"
+inputs = ["graph1", "linear1"]
+
+[[Mix]]
+name = "mix2"
+kind = "Average"
+inputs = ["pid1", "flat1", "target1"]
+
+[[Control]]
+name = "control1"
+input = "mix1"
+active = true
+
+[[Control]]
+name = "control2"
+input = "mix2"
+active = true
+
+[[Control]]
+name = "control3"
+input = "linear2"
+active = true
+
+[[Control]]
+name = "control4"
+input = "flat2"
+active = true
+"#,
+        )
+        .unwrap();
+
+        let app_graph = AppGraph::from_config(config, &Hardware::default());
+        let mut nodes = app_graph.nodes.values().collect::<Vec<_>>();
+        assert_eq!(nodes.len(), 22);
+        assert_eq!(
+            nodes.iter().map(|node| node.inputs.len()).sum::<usize>(),
+            20
+        );
+
+        let mut seed = 7u64;
+        for _ in 0..50 {
+            // Fisher-Yates, with a linear congruential generator
+            for i in (1..nodes.len()).rev() {
+                seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+                nodes.swap(i, (seed >> 33) as usize % (i + 1));
+            }
+
+            let mut sorted = nodes.clone();
+            sorted.sort_by_key(|node| node.node_type.rank());
+
+            let mut updated = HashSet::new();
+            for node in sorted {
+                for input in &node.inputs {
+                    assert!(
+                        updated.contains(&input.id),
+                        "{} is updated before its input {}",
+                        node.name(),
+                        input.name
+                    );
                 }
+                updated.insert(node.id);
             }
-            NodeType::CustomTemp(_) => match other {
-                NodeType::CustomTemp(_) => Ordering::Equal,
-                NodeType::Fan(_) => Ordering::Greater,
-                NodeType::Temp(_) => Ordering::Greater,
-                _ => Ordering::Less,
-            },
-            NodeType::Flat(_) => Ordering::Equal,
-
-            NodeType::Graph(_) | NodeType::Linear(..) | NodeType::Target(..) => match other {
-                NodeType::Control(_) => Ordering::Less,
-                NodeType::Fan(_) => Ordering::Greater,
-                NodeType::Temp(_) => Ordering::Greater,
-                NodeType::CustomTemp(_) => Ordering::Greater,
-                _ => Ordering::Equal,
-            },
         }
     }
+
+    #[test]
+    fn test_filter_and_custom_temp() {
+        let mut bridge = SnapshotBridge::from_snapshot(
+            HardwareSnapshot::from_str(
+                r#"
+[[Temp]]
+name = "temp1"
+id = "temp1"
+value = 40
+
+[[Temp]]
+name = "temp2"
+id = "temp2"
+value = 60
+"#,
+            )
+            .unwrap(),
+        );
+
+        // the filter of a custom temp comes first in the config
+        let config: Config = toml::from_str(
+            r#"
+[[Temp]]
+name = "temp1"
+id = "temp1"
+
+[[Temp]]
+name = "temp2"
+id = "temp2"
+
+[[Filter]]
+name = "smooth_max"
+kind = "MovingAverage"
+period = 5
+input = "max"
+
+[[CustomTemp]]
+name = "max"
+kind = "Max"
+inputs = ["filter1", "filter2"]
+
+[[Filter]]
+name = "filter1"
+kind = "Median"
+period = 5
+input = "temp1"
+
+[[Filter]]
+name = "filter2"
+kind = "Median"
+period = 5
+input = "temp2"
+"#,
+        )
+        .unwrap();
+
+        let mut app_graph = AppGraph::from_config(config, bridge.hardware());
+        let node = |app_graph: &AppGraph, name: &str| {
+            app_graph
+                .nodes
+                .values()
+                .find(|node| node.name() == name)
+                .unwrap()
+                .clone()
+        };
+        assert_eq!(node(&app_graph, "max").inputs.len(), 2);
+        assert_eq!(node(&app_graph, "smooth_max").inputs.len(), 1);
+
+        Update::new()
+            .all(&mut app_graph.nodes, &mut bridge)
+            .unwrap();
+        assert_eq!(node(&app_graph, "filter1").value, Some(40.0));
+        assert_eq!(node(&app_graph, "max").value, Some(60.0));
+        assert_eq!(node(&app_graph, "smooth_max").value, Some(60.0));
+    }
+
+    #[test]
+    fn test_carry_state() {
+        let graph = |config: &str| {
+            let config: Config = toml::from_str(config).unwrap();
+            AppGraph::from_config(config, &Hardware::default())
+        };
+        let node_type = |app_graph: &AppGraph, name: &str| {
+            app_graph
+                .nodes
+                .values()
+                .find(|node| node.name() == name)
+                .unwrap()
+                .node_type
+                .clone()
+        };
+        let config = r#"
+[[Temp]]
+name = "temp1"
+
+[[Target]]
+name = "target"
+idleTemp = 40
+idleSpeed = 10
+loadTemp = 70
+loadSpeed = 100
+input = "temp1"
+
+[[Control]]
+name = "control"
+id = "pwm1"
+input = "target"
+active = true
+"#;
+
+        let previous = graph(config);
+        let NodeType::Target(mut target) = node_type(&previous, "target") else {
+            panic!()
+        };
+        target.idle_has_been_reatch = true;
+        let target = NodeType::Target(target);
+
+        let mut new_target = node_type(&graph(config), "target");
+        assert!(new_target.carry_state(&target));
+        let NodeType::Target(new_target) = new_target else {
+            panic!()
+        };
+        assert!(new_target.idle_has_been_reatch);
+
+        // the state doesn't apply to another input, or another kind of node
+        let mut other_input = node_type(&graph(&config.replace("input = \"temp1\"", "")), "target");
+        assert!(!other_input.carry_state(&target));
+        assert!(!node_type(&previous, "temp1").carry_state(&target));
+
+        // a control which is not bound to the hardware has no state
+        let mut control = node_type(&previous, "control");
+        assert!(!control.carry_state(&node_type(&previous, "control")));
+    }
 }
@@ -1,11 +1,16 @@
-use std::collections::HashSet;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use hardware::{HardwareBridge, Mode, Value};
+use hardware::{HControl, HSensor, HardwareBridge, HardwareValues, Mode, Value};
 
 use thiserror::Error;
 
 use crate::{
     app_graph::{Nodes, RootNodes},
+    config::{file_temp::FileTemp, hysteresis::Behavior},
     id::Id,
     node::{Node, NodeType},
 };
@@ -22,13 +27,31 @@ pub enum UpdateError {
     NoInputData,
     #[error("Can't set mode")]
     CantSetMode,
+    #[error("Value {1} of {0} was rejected")]
+    Rejected(String, Value),
     #[error(transparent)]
     Hardware(#[from] hardware::HardwareError),
+    #[error(transparent)]
+    File(#[from] crate::config::file_temp::FileTempError),
 }
 
 type Result<T> = std::result::Result<T, UpdateError>;
 
-pub struct Update {}
+/// Controls are read back every this number of cycles
+const VERIFY_INTERVAL: u32 = 5;
+
+/// Values read at the start of a cycle, in one batch.
+#[derive(Debug, Default)]
+struct CycleValues {
+    hardware: HardwareValues,
+    /// Values of the FileTemp nodes, by node
+    files: HashMap<Id, Result<Value>>,
+}
+
+pub struct Update {
+    cycles_since_verify: u32,
+    read_latency: Option<Duration>,
+}
 
 impl Default for Update {
     fn default() -> Self {
@@ -38,7 +61,79 @@ impl Default for Update {
 
 impl Update {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            cycles_since_verify: 0,
+            read_latency: None,
+        }
+    }
+
+    /// Time taken by the last batch read of the hardware
+    pub fn read_latency(&self) -> Option<Duration> {
+        self.read_latency
+    }
+
+    /// Read the hardware of these nodes in one call to the bridge,
+    /// and read their files. Commands run in the background.
+    /// Controls are only read when `with_controls` is true.
+    fn read_values<'a, H: HardwareBridge>(
+        &mut self,
+        nodes: impl Iterator<Item = &'a Node>,
+        with_controls: bool,
+        bridge: &mut H,
+    ) -> CycleValues {
+        let mut sensors: Vec<Arc<HSensor>> = Vec::new();
+        let mut controls: Vec<Arc<HControl>> = Vec::new();
+        let mut file_temps: Vec<(Id, &FileTemp)> = Vec::new();
+
+        for node in nodes.filter(|node| node.node_type.is_valid()) {
+            match &node.node_type {
+                NodeType::Temp(temp) => sensors.extend(temp.temp_h.clone()),
+                NodeType::Fan(fan) => sensors.extend(fan.fan_h.clone()),
+                NodeType::Control(control) if with_controls => {
+                    controls.extend(control.control_h.clone())
+                }
+                NodeType::FileTemp(file_temp) => file_temps.push((node.id, file_temp)),
+                _ => {}
+            }
+        }
+
+        let start = Instant::now();
+        let hardware = bridge.get_values(&sensors, &controls);
+
+        let files = file_temps
+            .iter()
+            .map(|&(id, file_temp)| (id, file_temp.read()))
+            .collect();
+
+        let elapsed = start.elapsed();
+        debug!(
+            "Read {} sensors, {} controls and {} files in {:?}.",
+            sensors.len(),
+            controls.len(),
+            file_temps.len(),
+            elapsed
+        );
+        self.read_latency = Some(elapsed);
+
+        CycleValues { hardware, files }
+    }
+
+    /// Read back the controls every few cycles, and set them again
+    /// if the firmware took them back. Should be called after each update.
+    pub fn verify_controls<H: HardwareBridge>(&mut self, nodes: &mut Nodes, bridge: &mut H) {
+        self.cycles_since_verify += 1;
+        if self.cycles_since_verify < VERIFY_INTERVAL {
+            return;
+        }
+        self.cycles_since_verify = 0;
+
+        for node in nodes.values_mut() {
+            if let NodeType::Control(control) = &mut node.node_type {
+                if let Err(e) = control.verify(bridge) {
+                    error!("Can't verify control {}: {}.", control.name, e);
+                }
+            }
+        }
     }
 
     // todo: remember what nodes are valid
@@ -51,9 +146,19 @@ impl Update {
         root_nodes: &RootNodes,
         bridge: &mut H,
     ) -> Result<()> {
+        let mut used = HashSet::new();
+        for node_id in root_nodes {
+            Self::used_rec(nodes, node_id, &mut used);
+        }
+        let mut values = self.read_values(
+            nodes.values().filter(|node| used.contains(&node.id)),
+            false,
+            bridge,
+        );
+
         let mut updated: HashSet<Id> = HashSet::new();
         for node_id in root_nodes {
-            if let Err(e) = Self::update_rec(nodes, node_id, &mut updated, bridge) {
+            if let Err(e) = Self::update_rec(nodes, node_id, &mut updated, &mut values, bridge) {
                 error!("Can't update node: {}.", e);
             }
         }
@@ -67,16 +172,16 @@ impl Update {
         {
             let mut key_values = nodes.iter().collect::<Vec<_>>();
 
-            key_values.sort_by(|(_, first), (_, other)| {
-                first.node_type.compare_update_priority(&other.node_type)
-            });
+            key_values.sort_by_key(|(_, node)| node.node_type.rank());
 
             ids_to_update_sorted = key_values.iter().map(|(id, _)| **id).collect();
         }
 
+        let mut values = self.read_values(nodes.values(), false, bridge);
+
         let mut updated = HashSet::new();
         for id in ids_to_update_sorted {
-            if let Err(e) = Self::update_rec(nodes, &id, &mut updated, bridge) {
+            if let Err(e) = Self::update_rec(nodes, &id, &mut updated, &mut values, bridge) {
                 error!("can't update node: {}", e);
             }
         }
@@ -84,35 +189,41 @@ impl Update {
         Ok(())
     }
 
+    /// Read back the controls after the update. Fans are not read again:
+    /// they were read in `all`, through their policy, once per cycle.
     pub fn nodes_which_update_can_change<H: HardwareBridge>(
         &mut self,
         nodes: &mut Nodes,
         bridge: &mut H,
     ) -> Result<()> {
+        let mut values = self.read_values(
+            nodes
+                .values()
+                .filter(|node| matches!(node.node_type, NodeType::Control(_))),
+            true,
+            bridge,
+        );
+
         for node in nodes.values_mut() {
-            let value = match &mut node.node_type {
-                crate::node::NodeType::Control(control) => Some(control.get_value(bridge)),
-                crate::node::NodeType::Fan(fan) => Some(fan.get_value(bridge)),
-                _ => None,
+            let NodeType::Control(control) = &mut node.node_type else {
+                continue;
             };
 
-            if let Some(value) = value {
-                match value {
-                    Ok(value) => {
-                        debug!("Node {} value is {}.", node.name(), value);
-                        node.value.replace(value);
-                    }
-                    Err(UpdateError::NodeIsInvalid(_)) => {
-                        node.value.take();
-                    }
-                    Err(e) => {
-                        node.value.take();
-                        error!(
-                            "Can't get the value of the root node {}: {}.",
-                            node.name(),
-                            e
-                        );
-                    }
+            match control.get_value(&mut values.hardware, bridge) {
+                Ok(value) => {
+                    debug!("Node {} value is {}.", node.name(), value);
+                    node.value.replace(value);
+                }
+                Err(UpdateError::NodeIsInvalid(_)) => {
+                    node.value.take();
+                }
+                Err(e) => {
+                    node.value.take();
+                    error!(
+                        "Can't get the value of the root node {}: {}.",
+                        node.name(),
+                        e
+                    );
                 }
             }
         }
@@ -189,10 +300,23 @@ impl Update {
         true
     }
 
+    /// Collect the nodes used by this node, itself included.
+    fn used_rec(nodes: &Nodes, node_id: &Id, used: &mut HashSet<Id>) {
+        if !used.insert(*node_id) {
+            return;
+        }
+        if let Some(node) = nodes.get(node_id) {
+            for input in &node.inputs {
+                Self::used_rec(nodes, &input.id, used);
+            }
+        }
+    }
+
     fn update_rec<H: HardwareBridge>(
         nodes: &mut Nodes,
         node_id: &Id,
         updated: &mut HashSet<Id>,
+        values: &mut CycleValues,
         bridge: &mut H,
     ) -> Result<Option<Value>> {
         if updated.contains(node_id) {
@@ -213,6 +337,7 @@ impl Update {
                 if !node.is_root() {
                     node.value = None;
                 }
+                reset_state(&mut node.node_type);
                 return Ok(None);
             }
             input_ids = node.inputs.iter().map(|i| i.id).collect();
@@ -220,7 +345,16 @@ impl Update {
 
         let mut input_values = Vec::new();
         for id in &input_ids {
-            match Self::update_rec(nodes, id, updated, bridge)? {
+            // an input which can't be updated has no value
+            let value = match Self::update_rec(nodes, id, updated, values, bridge) {
+                Ok(value) => value,
+                Err(e) => {
+                    error!("can't update node: {}", e);
+                    None
+                }
+            };
+
+            match value {
                 Some(value) => input_values.push(value),
                 None => {
                     return match nodes.get_mut(node_id) {
@@ -228,6 +362,10 @@ impl Update {
                             if !node.is_root() {
                                 node.value = None;
                             }
+                            match &mut node.node_type {
+                                NodeType::Control(control) => control.input_missing(bridge)?,
+                                node_type => reset_state(node_type),
+                            }
                             Ok(None)
                         }
                         None => Err(UpdateError::NodeNotFound(*node_id)),
@@ -240,32 +378,67 @@ impl Update {
             return Err(UpdateError::NodeNotFound(*node_id));
         };
 
-        node.update(&input_values, bridge)?;
+        node.update(&input_values, values, bridge)?;
 
         Ok(node.value)
     }
 }
 
+/// Forget the previous cycles of a node, when its input has no value.
+fn reset_state(node_type: &mut NodeType) {
+    match node_type {
+        NodeType::Filter(filter) => filter.reset(),
+        NodeType::Graph(graph) => graph.hysteresis_state = Default::default(),
+        NodeType::Linear(linear) => linear.hysteresis_state = Default::default(),
+        NodeType::Mix(mix) => mix.hysteresis_state = Default::default(),
+        NodeType::Pid(pid) => pid.reset(),
+        _ => {}
+    }
+}
+
 impl Node {
-    fn update<H: HardwareBridge>(&mut self, input_values: &[Value], bridge: &mut H) -> Result<()> {
+    fn update<H: HardwareBridge>(
+        &mut self,
+        input_values: &[Value],
+        values: &mut CycleValues,
+        bridge: &mut H,
+    ) -> Result<()> {
         let value = match &mut self.node_type {
             crate::node::NodeType::Control(control) => {
-                let input_value = input_values[0];
-                return if self.value == Some(input_value) {
-                    debug!("Control {} already set to {}", control.name, input_value);
+                let recovered = control.input_recovered();
+                let input_value = control.slew(input_values[0]);
+                // the value sent is after the output mapping
+                let output = control.output.apply(input_value, control.output_set);
+                return if control.output_set == Some(output) && !recovered {
+                    debug!("Control {} already set to {}", control.name, output);
                     Ok(())
                 } else {
                     debug!("Before setting control {} to {}", control.name, input_value);
                     control.set_value(input_value, bridge).map(|_| ())
                 };
             }
-            crate::node::NodeType::Fan(fan) => fan.get_value(bridge),
-            crate::node::NodeType::Temp(temp) => temp.get_value(bridge),
+            crate::node::NodeType::Fan(fan) => fan.get_value(&mut values.hardware, bridge),
+            crate::node::NodeType::Temp(temp) => temp.get_value(&mut values.hardware, bridge),
+            crate::node::NodeType::CommandTemp(command_temp) => match command_temp.get_value() {
+                Ok(Some(value)) => Ok(value),
+                // no result yet, or the run failed
+                Ok(None) => {
+                    self.value = None;
+                    return Ok(());
+                }
+                Err(e) => Err(e),
+            },
+            crate::node::NodeType::FileTemp(file_temp) => {
+                file_temp.get_value(values.files.remove(&self.id))
+            }
             crate::node::NodeType::CustomTemp(custom_temp) => custom_temp.get_value(input_values),
-            crate::node::NodeType::Graph(graph) => graph.get_value(input_values[0]),
+            crate::node::NodeType::Filter(filter) => filter.get_value(input_values[0]),
+            crate::node::NodeType::Graph(graph) => graph.get_value(input_values),
             crate::node::NodeType::Flat(flat) => Ok(flat.value.into()),
-            crate::node::NodeType::Linear(linear, ..) => linear.get_value(input_values[0]),
+            crate::node::NodeType::Linear(linear, ..) => linear.get_value(input_values),
             crate::node::NodeType::Target(target, ..) => target.get_value(input_values[0]),
+            crate::node::NodeType::Pid(pid) => pid.get_value(input_values),
+            crate::node::NodeType::Mix(mix) => mix.get_value(input_values),
         };
 
         match value {
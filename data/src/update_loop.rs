@@ -0,0 +1,393 @@
+//! Update the nodes in a dedicated thread, so a sensor which is slow to read
+//! never blocks the interface.
+//!
+//! The thread owns the bridge and its own copy of the graph. The interface
+//! sends the graph each time it is modified, and receive the values of the nodes
+//! after each update.
+
+use std::{
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use hardware::{Hardware, HardwareBridge, Mode, Value};
+
+use crate::{
+    app_graph::{AppGraph, HardwareChanges},
+    config::sensor_policy::{SensorHealth, SensorStats},
+    id::Id,
+    node::NodeType,
+    update::Update,
+};
+
+#[derive(Debug)]
+pub enum UpdateCommand {
+    /// The graph was modified. Controls which are not used anymore are set to auto.
+    Graph(AppGraph),
+    Delay(Duration),
+    Rescan,
+    Shutdown,
+}
+
+#[derive(Debug, Clone)]
+pub enum UpdateEvent {
+    /// Value of each node after an update
+    Values(Vec<(Id, Option<Value>)>),
+    /// Counters of the sensors which had problems, sent before the values
+    SensorStats(Vec<(Id, SensorStats)>),
+    /// Times each control was taken back by the firmware, sent before the values
+    Takeovers(Vec<(Id, u32)>),
+    /// The hardware changed after a rescan. The graph of the thread is already bound to it.
+    Rescanned {
+        hardware: Hardware,
+        changes: HardwareChanges,
+    },
+}
+
+pub struct UpdateLoop {
+    commands: Sender<UpdateCommand>,
+    events: Receiver<UpdateEvent>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl UpdateLoop {
+    pub fn spawn<H: HardwareBridge + 'static>(
+        bridge: H,
+        app_graph: AppGraph,
+        delay: Duration,
+    ) -> Self {
+        let (commands_tx, commands_rx) = mpsc::channel();
+        let (events_tx, events_rx) = mpsc::channel();
+
+        let worker = Worker {
+            bridge,
+            app_graph,
+            update: Update::new(),
+            events: events_tx,
+        };
+
+        let handle = thread::Builder::new()
+            .name("update".into())
+            .spawn(move || worker.run(commands_rx, delay))
+            .expect("can't spawn the update thread");
+
+        Self {
+            commands: commands_tx,
+            events: events_rx,
+            handle: Some(handle),
+        }
+    }
+
+    pub fn send(&self, command: UpdateCommand) {
+        if self.commands.send(command).is_err() {
+            error!("The update thread has stopped.");
+        }
+    }
+
+    /// Events received since the last call. Never blocks.
+    pub fn events(&self) -> impl Iterator<Item = UpdateEvent> + '_ {
+        self.events.try_iter()
+    }
+
+    /// Shutdown the bridge and wait for the thread.
+    pub fn shutdown(&mut self) {
+        let Some(handle) = self.handle.take() else {
+            return;
+        };
+        let _ = self.commands.send(UpdateCommand::Shutdown);
+        if handle.join().is_err() {
+            error!("The update thread panicked.");
+        }
+    }
+}
+
+impl Drop for UpdateLoop {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+struct Worker<H: HardwareBridge> {
+    bridge: H,
+    app_graph: AppGraph,
+    update: Update,
+    events: Sender<UpdateEvent>,
+}
+
+impl<H: HardwareBridge> Worker<H> {
+    fn run(mut self, commands: Receiver<UpdateCommand>, mut delay: Duration) {
+        let mut next_update = Instant::now();
+
+        loop {
+            let timeout = next_update.saturating_duration_since(Instant::now());
+
+            match commands.recv_timeout(timeout) {
+                Ok(UpdateCommand::Graph(app_graph)) => {
+                    self.replace_graph(app_graph);
+                    next_update = Instant::now();
+                }
+                Ok(UpdateCommand::Delay(new_delay)) => delay = new_delay,
+                Ok(UpdateCommand::Rescan) => self.rescan(),
+                Ok(UpdateCommand::Shutdown) | Err(RecvTimeoutError::Disconnected) => break,
+                Err(RecvTimeoutError::Timeout) => {
+                    self.update();
+                    next_update = Instant::now() + delay;
+                }
+            }
+        }
+
+        if let Err(e) = self.bridge.shutdown() {
+            error!("shutdown hardware: {}", e);
+        }
+    }
+
+    fn update(&mut self) {
+        if let Err(e) = self.bridge.update() {
+            error!("{}", e);
+            return;
+        }
+
+        let nodes = &mut self.app_graph.nodes;
+
+        if let Err(e) = self.update.all(nodes, &mut self.bridge) {
+            error!("{}", e);
+            return;
+        }
+
+        self.update.verify_controls(nodes, &mut self.bridge);
+
+        if let Err(e) = self
+            .update
+            .nodes_which_update_can_change(nodes, &mut self.bridge)
+        {
+            error!("{}", e);
+            return;
+        }
+
+        let stats = nodes
+            .values()
+            .filter_map(|node| {
+                let health = sensor_health(&node.node_type)?;
+                health
+                    .stats
+                    .is_flaky()
+                    .then(|| (node.id, health.stats.clone()))
+            })
+            .collect::<Vec<_>>();
+        if !stats.is_empty() {
+            let _ = self.events.send(UpdateEvent::SensorStats(stats));
+        }
+
+        let takeovers = nodes
+            .values()
+            .filter_map(|node| match &node.node_type {
+                NodeType::Control(control) if control.takeovers > 0 => {
+                    Some((node.id, control.takeovers))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if !takeovers.is_empty() {
+            let _ = self.events.send(UpdateEvent::Takeovers(takeovers));
+        }
+
+        let values = nodes.values().map(|node| (node.id, node.value)).collect();
+        // the receiver is gone when the interface quits
+        let _ = self.events.send(UpdateEvent::Values(values));
+    }
+
+    /// Keep the state of the nodes which still apply, see [`NodeType::carry_state`],
+    /// and set the controls which are not used anymore to auto.
+    fn replace_graph(&mut self, mut app_graph: AppGraph) {
+        // the graph could have been bound to the hardware before a rescan
+        app_graph.rebind_hardware(self.bridge.hardware());
+
+        let mut previous = std::mem::replace(&mut self.app_graph, app_graph);
+
+        for (id, node) in &mut previous.nodes {
+            let carried = self
+                .app_graph
+                .nodes
+                .get_mut(id)
+                .is_some_and(|new_node| new_node.node_type.carry_state(&node.node_type));
+            if carried {
+                continue;
+            }
+
+            let NodeType::Control(previous_control) = &mut node.node_type else {
+                continue;
+            };
+
+            if previous_control.control_h.is_some() {
+                if let Err(e) = previous_control.set_mode(Mode::Auto, &mut self.bridge) {
+                    error!(
+                        "Can't set {} to auto when it is not used anymore: {}.",
+                        previous_control.name, e
+                    );
+                }
+            }
+        }
+
+        self.update.set_invalid_root_nodes_to_auto(
+            &mut self.app_graph.nodes,
+            &self.app_graph.root_nodes,
+            &mut self.bridge,
+        );
+    }
+
+    fn rescan(&mut self) {
+        match self.bridge.rescan() {
+            Ok(true) => {
+                let changes = self.app_graph.rebind_hardware(self.bridge.hardware());
+                let _ = self.events.send(UpdateEvent::Rescanned {
+                    hardware: self.bridge.hardware().clone(),
+                    changes,
+                });
+            }
+            Ok(false) => {}
+            Err(e) => error!("can't rescan hardware: {}", e),
+        }
+    }
+}
+
+fn sensor_health(node_type: &NodeType) -> Option<&SensorHealth> {
+    match node_type {
+        NodeType::Temp(temp) => Some(&temp.health),
+        NodeType::Fan(fan) => Some(&fan.health),
+        NodeType::CommandTemp(command_temp) => Some(&command_temp.health),
+        NodeType::FileTemp(file_temp) => Some(&file_temp.health),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use hardware::{
+        snapshot::{HardwareSnapshot, SnapshotBridge},
+        HardwareBridge,
+    };
+
+    use super::{UpdateCommand, UpdateEvent, UpdateLoop};
+    use crate::{
+        app_graph::AppGraph,
+        config::{control::Control, fan::Fan, flat::Flat, Config},
+        id::Id,
+        node::Node,
+    };
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_send_sync() {
+        assert_send_sync::<hardware::Hardware>();
+        assert_send_sync::<AppGraph>();
+        assert_send_sync::<Node>();
+    }
+
+    fn next_values(update_loop: &UpdateLoop) -> Vec<(Id, Option<hardware::Value>)> {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(5) {
+            if let Some(UpdateEvent::Values(values)) = update_loop.events().last() {
+                return values;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        panic!("no values received");
+    }
+
+    #[test]
+    fn test_update_loop() {
+        let bridge = SnapshotBridge::from_snapshot(
+            HardwareSnapshot::from_str(
+                r#"
+[[Control]]
+name = "pwm1"
+id = "pwm1"
+value = 0
+"#,
+            )
+            .unwrap(),
+        );
+
+        let config = Config {
+            flats: vec![Flat {
+                name: "flat".into(),
+                value: 60,
+            }],
+            controls: vec![Control::new(
+                "control".into(),
+                Some("pwm1".into()),
+                Some("flat".into()),
+                true,
+                None,
+            )],
+            ..Default::default()
+        };
+        let mut app_graph = AppGraph::from_config(config, bridge.hardware());
+        let control_id = app_graph.root_nodes[0];
+
+        let mut update_loop =
+            UpdateLoop::spawn(bridge, app_graph.clone(), Duration::from_millis(10));
+
+        let values = next_values(&update_loop);
+        assert!(values.contains(&(control_id, Some(60.0))));
+
+        app_graph.remove_node(control_id);
+        update_loop.send(UpdateCommand::Graph(app_graph));
+
+        // values sent before the graph was received are skipped
+        let start = Instant::now();
+        loop {
+            let values = next_values(&update_loop);
+            if values.iter().all(|(id, _)| *id != control_id) {
+                break;
+            }
+            assert!(start.elapsed() < Duration::from_secs(5));
+        }
+
+        update_loop.shutdown();
+    }
+
+    #[test]
+    fn test_fan_read_once() {
+        let bridge = SnapshotBridge::from_snapshot(
+            HardwareSnapshot::from_str(
+                r#"
+[[Fan]]
+name = "fan1"
+id = "fan1"
+"#,
+            )
+            .unwrap(),
+        );
+
+        let config = Config {
+            fans: vec![Fan {
+                name: "fan".into(),
+                hardware_id: Some("fan1".into()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let app_graph = AppGraph::from_config(config, bridge.hardware());
+        let fan_id = *app_graph.nodes.keys().next().unwrap();
+
+        let update_loop = UpdateLoop::spawn(bridge, app_graph, Duration::from_secs(10));
+
+        // the fan has no value: one failure in the first cycle, not one for each read
+        let start = Instant::now();
+        let stats = loop {
+            if let Some(UpdateEvent::SensorStats(stats)) = update_loop.events().next() {
+                break stats;
+            }
+            assert!(start.elapsed() < Duration::from_secs(5));
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].0, fan_id);
+        assert_eq!(stats[0].1.failures, 1);
+    }
+}
@@ -11,7 +11,8 @@ public enum Command
     SetValue = 2,
     GetValue = 3,
     Shutdown = 4,
-    Update = 5
+    Update = 5,
+    GetValues = 6
 }
 
 public class Server
@@ -69,6 +70,17 @@ public class Server
                     var valueInBytes = BitConverter.GetBytes(value);
                     block_send(valueInBytes);
                     break;
+                case Command.GetValues:
+                    var count = block_read();
+                    var valuesInBytes = new byte[count * 4];
+                    for (var i = 0; i < count; i++)
+                    {
+                        index = block_read();
+                        value = hardwareManager.GetValue(index);
+                        BitConverter.GetBytes(value).CopyTo(valuesInBytes, i * 4);
+                    }
+                    block_send(valuesInBytes);
+                    break;
                 case Command.Shutdown:
                     return;
                 case Command.Update:
@@ -0,0 +1,171 @@
+//! Run a config without touching the hardware, to see what it would do.
+
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{HControl, HSensor, Hardware, HardwareBridge, HardwareValues, Mode, Value};
+
+/// Forward the reads to the inner bridge, but only log the writes.
+///
+/// Controls read back what would have been written, until they are set
+/// to auto, so the update loop behaves as if the writes happened.
+pub struct DryRunBridge<H: HardwareBridge> {
+    bridge: H,
+    /// Values that would have been written, by control id
+    intended_values: HashMap<String, Value>,
+    /// Modes that would have been set, by control id
+    intended_modes: HashMap<String, Mode>,
+}
+
+impl<H: HardwareBridge> DryRunBridge<H> {
+    pub fn from_bridge(bridge: H) -> Self {
+        Self {
+            bridge,
+            intended_values: HashMap::new(),
+            intended_modes: HashMap::new(),
+        }
+    }
+
+    /// Last value that would have been written to this control
+    fn intended_value(&self, control: &HControl) -> Option<Value> {
+        self.intended_values.get(&control.hardware_id).copied()
+    }
+
+    /// Last mode that would have been set to this control
+    fn intended_mode(&self, control: &HControl) -> Option<&Mode> {
+        self.intended_modes.get(&control.hardware_id)
+    }
+}
+
+impl<H: HardwareBridge> HardwareBridge for DryRunBridge<H> {
+    fn new() -> crate::Result<Self> {
+        Ok(Self::from_bridge(H::new()?))
+    }
+
+    fn hardware(&self) -> &Hardware {
+        self.bridge.hardware()
+    }
+
+    fn get_sensor_value(&mut self, sensor: &HSensor) -> crate::Result<Value> {
+        self.bridge.get_sensor_value(sensor)
+    }
+
+    fn get_control_value(&mut self, control: &HControl) -> crate::Result<Value> {
+        match self.intended_value(control) {
+            Some(value) => Ok(value),
+            None => self.bridge.get_control_value(control),
+        }
+    }
+
+    fn get_values(
+        &mut self,
+        sensors: &[Arc<HSensor>],
+        controls: &[Arc<HControl>],
+    ) -> HardwareValues {
+        let (intended, real): (Vec<_>, Vec<_>) = controls
+            .iter()
+            .cloned()
+            .partition(|control| self.intended_values.contains_key(&control.hardware_id));
+
+        let mut values = self.bridge.get_values(sensors, &real);
+        for control in intended {
+            if let Some(value) = self.intended_value(&control) {
+                values
+                    .controls
+                    .insert(control.hardware_id.clone(), Ok(value));
+            }
+        }
+        values
+    }
+
+    fn set_value(&mut self, control: &HControl, value: Value) -> crate::Result<()> {
+        info!("Dry run: would set {} to {}%.", control.name, value);
+        self.intended_values
+            .insert(control.hardware_id.clone(), value);
+        Ok(())
+    }
+
+    fn set_mode(&mut self, control: &HControl, mode: &Mode) -> crate::Result<()> {
+        info!("Dry run: would set mode {} to {}.", mode, control.name);
+        // the firmware keeps the control, so its value is read again
+        if *mode != Mode::Manual {
+            self.intended_values.remove(&control.hardware_id);
+        }
+        self.intended_modes
+            .insert(control.hardware_id.clone(), mode.clone());
+        Ok(())
+    }
+
+    fn get_mode(&mut self, control: &HControl) -> crate::Result<Option<Mode>> {
+        match self.intended_mode(control) {
+            Some(mode) => Ok(Some(mode.clone())),
+            None => self.bridge.get_mode(control),
+        }
+    }
+
+    fn update(&mut self) -> crate::Result<()> {
+        self.bridge.update()
+    }
+
+    fn rescan(&mut self) -> crate::Result<bool> {
+        self.bridge.rescan()
+    }
+
+    fn can_rescan(&self) -> bool {
+        self.bridge.can_rescan()
+    }
+
+    fn shutdown(&mut self) -> crate::Result<()> {
+        self.bridge.shutdown()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DryRunBridge;
+    use crate::{
+        snapshot::{HardwareSnapshot, SnapshotBridge},
+        HardwareBridge, Mode,
+    };
+
+    #[test]
+    fn test_dry_run() {
+        let snapshot = HardwareSnapshot::from_str(
+            r#"
+[[Control]]
+name = "pwm1"
+id = "pwm1"
+value = 10
+
+[[Temp]]
+name = "temp1"
+id = "temp1"
+value = 40
+"#,
+        )
+        .unwrap();
+        let mut bridge = DryRunBridge::from_bridge(SnapshotBridge::from_snapshot(snapshot));
+
+        let pwm1 = bridge.hardware().controls[0].clone();
+        let temp1 = bridge.hardware().temps[0].clone();
+
+        assert_eq!(bridge.get_sensor_value(&temp1).unwrap(), 40.0);
+        assert_eq!(bridge.get_control_value(&pwm1).unwrap(), 10.0);
+
+        bridge.set_mode(&pwm1, &Mode::Manual).unwrap();
+        bridge.set_value(&pwm1, 63.0).unwrap();
+        assert_eq!(bridge.intended_value(&pwm1), Some(63.0));
+        assert_eq!(bridge.get_control_value(&pwm1).unwrap(), 63.0);
+        assert_eq!(bridge.get_mode(&pwm1).unwrap(), Some(Mode::Manual));
+
+        let sensors = bridge.hardware().temps.clone();
+        let controls = bridge.hardware().controls.clone();
+        let mut values = bridge.get_values(&sensors, &controls);
+        assert_eq!(values.take_sensor(&temp1).unwrap().unwrap(), 40.0);
+        assert_eq!(values.take_control(&pwm1).unwrap().unwrap(), 63.0);
+
+        // nothing was written
+        bridge.set_mode(&pwm1, &Mode::Auto).unwrap();
+        assert_eq!(bridge.intended_value(&pwm1), None);
+        assert_eq!(bridge.get_control_value(&pwm1).unwrap(), 10.0);
+    }
+}
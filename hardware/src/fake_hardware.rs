@@ -1,82 +1,508 @@
+use std::{
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
 use rand::Rng;
+use serde::Deserialize;
+use thiserror::Error;
 
 use crate::{HControl, HSensor, Hardware, HardwareBridge, Mode, Value};
 
+// A simulated machine: each temp is heated by a constant source, and cooled
+// by the controls it is coupled to. Fans spin proportionally to their control.
+
+static DEFAULT_TOPOLOGY: &str = include_str!("../fake_topology.toml");
+static TOPOLOGY_ENV_VAR: &str = "FAN_CONTROL_FAKE_TOPOLOGY";
+
+// bigger steps would make the simulation unstable
+static MAX_STEP: f64 = 0.5;
+
+#[derive(Error, Debug)]
+pub enum FakeHardwareError {
+    #[error("Can't read topology file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Can't parse topology file: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("Invalid topology: {0}")]
+    InvalidTopology(String),
+}
+
+type Result<T> = std::result::Result<T, FakeHardwareError>;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Topology {
+    /// Simulated seconds per real second, used by [`HardwareBridge::update`].
+    #[serde(default = "default_time_scale")]
+    pub time_scale: f64,
+    #[serde(default, rename = "Temp")]
+    pub temps: Vec<TempModel>,
+    #[serde(default, rename = "Control")]
+    pub controls: Vec<ControlModel>,
+    #[serde(default, rename = "Fan")]
+    pub fans: Vec<FanModel>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TempModel {
+    pub id: String,
+    pub ambient: f64,
+    /// Heat produced, in W
+    pub heat: f64,
+    /// In J/°C
+    pub thermal_mass: f64,
+    /// In W/°C, when all fans are stopped
+    #[serde(default)]
+    pub passive_cooling: f64,
+    /// Amplitude of the random noise added to readings, in °C
+    #[serde(default)]
+    pub noise: f64,
+    pub initial: Option<f64>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Cooling {
+    pub temp: String,
+    /// In W/°C, when the control is at 100%
+    pub factor: f64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ControlModel {
+    pub id: String,
+    #[serde(default)]
+    pub cooling: Vec<Cooling>,
+    /// Temp used by the firmware curve. Default to the first cooled temp.
+    pub auto_input: Option<String>,
+    /// Firmware curve used in auto mode, as `[temp, percent]` points.
+    #[serde(default = "default_auto_curve")]
+    pub auto_curve: Vec<[f64; 2]>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FanModel {
+    pub id: String,
+    pub control: String,
+    pub max_rpm: f64,
+    /// Percent under which a spinning fan stops
+    #[serde(default)]
+    pub stall_duty: f64,
+    /// Percent needed to start a stopped fan
+    #[serde(default)]
+    pub start_duty: f64,
+}
+
+fn default_time_scale() -> f64 {
+    1.0
+}
+
+fn default_auto_curve() -> Vec<[f64; 2]> {
+    vec![[30.0, 20.0], [70.0, 100.0]]
+}
+
+impl Topology {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Self::from_str(&content)
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(content: &str) -> Result<Self> {
+        Ok(toml::from_str(content)?)
+    }
+}
+
+impl Default for Topology {
+    fn default() -> Self {
+        Self::from_str(DEFAULT_TOPOLOGY).expect("default topology should be valid")
+    }
+}
+
+struct SimTemp {
+    model: TempModel,
+    value: f64,
+}
+
+struct SimControl {
+    model: ControlModel,
+    // (temp index, factor)
+    cooling: Vec<(usize, f64)>,
+    auto_input: Option<usize>,
+    mode: Mode,
+    value: Value,
+}
+
+struct SimFan {
+    control: usize,
+    model: FanModel,
+    spinning: bool,
+}
+
 pub struct FakeHardwareBridge {
     hardware: Hardware,
+    time_scale: f64,
+    temps: Vec<SimTemp>,
+    controls: Vec<SimControl>,
+    fans: Vec<SimFan>,
+    last_update: Instant,
 }
 
-static TEMP_INTERNAL_INDEX: usize = 0;
-static FAN_INTERNAL_INDEX: usize = 1;
-static CONTROL_INTERNAL_INDEX: usize = 2;
+/// Piecewise linear interpolation, clamped at both ends.
+fn interpolate(curve: &[[f64; 2]], x: f64) -> f64 {
+    let Some(first) = curve.first() else {
+        return 100.0;
+    };
 
-impl HardwareBridge for FakeHardwareBridge {
-    fn new() -> crate::Result<Self> {
+    if x <= first[0] {
+        return first[1];
+    }
+
+    for window in curve.windows(2) {
+        let [xa, ya] = window[0];
+        let [xb, yb] = window[1];
+        if x <= xb {
+            return ya + (yb - ya) * (x - xa) / (xb - xa);
+        }
+    }
+
+    curve[curve.len() - 1][1]
+}
+
+impl FakeHardwareBridge {
+    pub fn from_topology(topology: Topology) -> crate::Result<Self> {
         let mut hardware = Hardware::default();
 
-        let temp1 = HSensor {
-            name: "temp1".into(),
-            hardware_id: "temp1".into(),
-            info: String::new(),
-            internal_index: TEMP_INTERNAL_INDEX,
+        let temp_index = |id: &str| {
+            topology
+                .temps
+                .iter()
+                .position(|t| t.id == id)
+                .ok_or_else(|| FakeHardwareError::InvalidTopology(format!("no temp {}", id)))
         };
-        hardware.temps.push(temp1.into());
 
-        let temp2 = HSensor {
-            name: "temp2".into(),
-            hardware_id: "temp2".into(),
-            info: String::new(),
-            internal_index: TEMP_INTERNAL_INDEX,
-        };
-        hardware.temps.push(temp2.into());
+        let mut temps = Vec::new();
+        for (index, model) in topology.temps.iter().enumerate() {
+            if model.thermal_mass <= 0.0 {
+                return Err(FakeHardwareError::InvalidTopology(format!(
+                    "thermal mass of {} should be positive",
+                    model.id
+                ))
+                .into());
+            }
 
-        let fan1 = HSensor {
-            name: "fan1".into(),
-            hardware_id: "fan1".into(),
-            info: String::new(),
-            internal_index: FAN_INTERNAL_INDEX,
-        };
-        hardware.fans.push(fan1.into());
+            hardware.temps.push(Arc::new(HSensor {
+                name: model.id.clone(),
+                hardware_id: model.id.clone(),
+                legacy_ids: Vec::new(),
+                info: String::new(),
+                internal_index: index,
+            }));
+            temps.push(SimTemp {
+                value: model.initial.unwrap_or(model.ambient),
+                model: model.clone(),
+            });
+        }
 
-        let control1 = HControl {
-            name: "control1".into(),
-            hardware_id: "control1".into(),
-            info: String::new(),
-            internal_index: CONTROL_INTERNAL_INDEX,
-        };
-        hardware.controls.push(control1.into());
+        let mut controls = Vec::new();
+        for (index, model) in topology.controls.iter().enumerate() {
+            let mut cooling = Vec::new();
+            for c in &model.cooling {
+                cooling.push((temp_index(&c.temp)?, c.factor));
+            }
+
+            let auto_input = match &model.auto_input {
+                Some(id) => Some(temp_index(id)?),
+                None => cooling.first().map(|(index, _)| *index),
+            };
+
+            let mut model = model.clone();
+            model
+                .auto_curve
+                .sort_by(|a, b| a[0].partial_cmp(&b[0]).unwrap_or(std::cmp::Ordering::Equal));
+
+            hardware.controls.push(Arc::new(HControl {
+                name: model.id.clone(),
+                hardware_id: model.id.clone(),
+                legacy_ids: Vec::new(),
+                info: String::new(),
+                internal_index: index,
+            }));
+            controls.push(SimControl {
+                model,
+                cooling,
+                auto_input,
+                mode: Mode::Auto,
+                value: 0.0,
+            });
+        }
+
+        let mut fans = Vec::new();
+        for model in &topology.fans {
+            let Some(control) = topology.controls.iter().position(|c| c.id == model.control) else {
+                return Err(FakeHardwareError::InvalidTopology(format!(
+                    "no control {}",
+                    model.control
+                ))
+                .into());
+            };
+
+            // fans are stored after temps
+            hardware.fans.push(Arc::new(HSensor {
+                name: model.id.clone(),
+                hardware_id: model.id.clone(),
+                legacy_ids: Vec::new(),
+                info: String::new(),
+                internal_index: temps.len() + fans.len(),
+            }));
+            fans.push(SimFan {
+                control,
+                model: model.clone(),
+                spinning: true,
+            });
+        }
 
-        let control2 = HControl {
-            name: "control2".into(),
-            hardware_id: "control2".into(),
-            info: String::new(),
-            internal_index: CONTROL_INTERNAL_INDEX,
+        Ok(Self {
+            hardware,
+            time_scale: topology.time_scale,
+            temps,
+            controls,
+            fans,
+            last_update: Instant::now(),
+        })
+    }
+
+    /// Percentage of the control, between 0 and 1, taking the firmware
+    /// curve into account in auto mode.
+    fn duty(&self, control: &SimControl) -> f64 {
+        match control.mode {
+            Mode::Manual => control.value.clamp(0.0, 100.0) / 100.0,
+            Mode::Auto | Mode::Specific(_) => match control.auto_input {
+                Some(index) => {
+                    interpolate(&control.model.auto_curve, self.temps[index].value)
+                        .clamp(0.0, 100.0)
+                        / 100.0
+                }
+                None => 1.0,
+            },
+        }
+    }
+
+    /// Advance the simulation by `seconds` simulated seconds.
+    pub fn step(&mut self, seconds: f64) {
+        let mut remaining = seconds;
+
+        while remaining > 0.0 {
+            let dt = remaining.min(MAX_STEP);
+            remaining -= dt;
+
+            let duties = self
+                .controls
+                .iter()
+                .map(|c| self.duty(c))
+                .collect::<Vec<_>>();
+
+            for (index, temp) in self.temps.iter_mut().enumerate() {
+                let mut cooling = temp.model.passive_cooling;
+                for (control, duty) in self.controls.iter().zip(&duties) {
+                    for (temp_index, factor) in &control.cooling {
+                        if *temp_index == index {
+                            cooling += factor * duty;
+                        }
+                    }
+                }
+
+                let power = temp.model.heat - cooling * (temp.value - temp.model.ambient);
+                temp.value += power / temp.model.thermal_mass * dt;
+            }
+
+            for fan in &mut self.fans {
+                let percent = duties[fan.control] * 100.0;
+                if fan.spinning && percent < fan.model.stall_duty {
+                    fan.spinning = false;
+                } else if !fan.spinning && percent >= fan.model.start_duty {
+                    fan.spinning = true;
+                }
+            }
+        }
+    }
+}
+
+impl HardwareBridge for FakeHardwareBridge {
+    fn new() -> crate::Result<Self> {
+        let topology = match std::env::var(TOPOLOGY_ENV_VAR) {
+            Ok(path) => {
+                info!("Loading fake topology from {}.", path);
+                Topology::from_file(path)?
+            }
+            Err(_) => Topology::default(),
         };
-        hardware.controls.push(control2.into());
 
-        Ok(Self { hardware })
+        Self::from_topology(topology)
     }
+
     fn hardware(&self) -> &Hardware {
         &self.hardware
     }
 
-    fn get_sensor_value(&mut self, _sensor: &HSensor) -> crate::Result<Value> {
-        let nb = rand::thread_rng().gen_range(30..80);
-        Ok(nb)
+    fn get_sensor_value(&mut self, sensor: &HSensor) -> crate::Result<Value> {
+        if let Some(temp) = self.temps.get(sensor.internal_index) {
+            let noise = if temp.model.noise > 0.0 {
+                rand::thread_rng().gen_range(-temp.model.noise..temp.model.noise)
+            } else {
+                0.0
+            };
+            return Ok(temp.value + noise);
+        }
+
+        let fan = &self.fans[sensor.internal_index - self.temps.len()];
+        if !fan.spinning {
+            return Ok(0.0);
+        }
+        let duty = self.duty(&self.controls[fan.control]);
+        Ok((fan.model.max_rpm * duty).round())
+    }
+
+    fn get_control_value(&mut self, control: &HControl) -> crate::Result<Value> {
+        let duty = self.duty(&self.controls[control.internal_index]);
+        Ok((duty * 100.0).round())
     }
 
-    fn get_control_value(&mut self, _control: &HControl) -> crate::Result<Value> {
-        let nb = rand::thread_rng().gen_range(30..80);
-        Ok(nb)
+    fn set_value(&mut self, control: &HControl, value: Value) -> crate::Result<()> {
+        debug!("set value {} to {}", value, control.name);
+        self.controls[control.internal_index].value = value;
+        Ok(())
     }
 
-    fn set_value(&mut self, _control: &HControl, value: Value) -> crate::Result<()> {
-        debug!("set value {}", value);
+    fn set_mode(&mut self, control: &HControl, mode: &Mode) -> crate::Result<()> {
+        debug!("set mode {} to {}", mode, control.name);
+        self.controls[control.internal_index].mode = mode.clone();
         Ok(())
     }
 
-    fn set_mode(&mut self, _control: &HControl, mode: &Mode) -> crate::Result<()> {
-        debug!("set mode {}", mode);
+    fn update(&mut self) -> crate::Result<()> {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_update);
+        self.last_update = now;
+
+        self.step(elapsed.min(Duration::from_secs(60)).as_secs_f64() * self.time_scale);
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{FakeHardwareBridge, Topology};
+    use crate::{HardwareBridge, Mode, Value};
+
+    fn assert_close(value: Value, expected: Value) {
+        assert!(
+            (value - expected).abs() < 0.01,
+            "{} is not {}",
+            value,
+            expected
+        );
+    }
+
+    static TOPOLOGY: &str = r#"
+[[Temp]]
+id = "cpu"
+ambient = 20.0
+heat = 50.0
+thermalMass = 100.0
+passiveCooling = 0.5
+
+[[Control]]
+id = "pwm"
+cooling = [{ temp = "cpu", factor = 2.0 }]
+autoCurve = [[25.0, 50.0], [65.0, 100.0]]
+
+[[Fan]]
+id = "fan"
+control = "pwm"
+maxRpm = 1500.0
+"#;
+
+    #[test]
+    fn test_equilibrium() {
+        let mut bridge =
+            FakeHardwareBridge::from_topology(Topology::from_str(TOPOLOGY).unwrap()).unwrap();
+
+        let cpu = bridge.hardware().temps[0].clone();
+        let fan = bridge.hardware().fans[0].clone();
+        let pwm = bridge.hardware().controls[0].clone();
+
+        bridge.set_mode(&pwm, &Mode::Manual).unwrap();
+        bridge.set_value(&pwm, 100.0).unwrap();
+        bridge.step(10000.0);
+
+        // 20 + 50 / (0.5 + 2.0)
+        assert_close(bridge.get_sensor_value(&cpu).unwrap(), 40.0);
+        assert_eq!(bridge.get_sensor_value(&fan).unwrap(), 1500.0);
+
+        bridge.set_value(&pwm, 40.0).unwrap();
+        bridge.step(10000.0);
+
+        // 20 + 50 / (0.5 + 0.8)
+        assert_close(bridge.get_sensor_value(&cpu).unwrap(), 58.46);
+        assert_eq!(bridge.get_sensor_value(&fan).unwrap(), 600.0);
+    }
+
+    #[test]
+    fn test_stall() {
+        let topology = format!("{}\nstallDuty = 20.0\nstartDuty = 40.0", TOPOLOGY);
+        let mut bridge =
+            FakeHardwareBridge::from_topology(Topology::from_str(&topology).unwrap()).unwrap();
+
+        let fan = bridge.hardware().fans[0].clone();
+        let pwm = bridge.hardware().controls[0].clone();
+
+        bridge.set_mode(&pwm, &Mode::Manual).unwrap();
+
+        bridge.set_value(&pwm, 20.0).unwrap();
+        bridge.step(1.0);
+        assert_eq!(bridge.get_sensor_value(&fan).unwrap(), 300.0);
+
+        bridge.set_value(&pwm, 19.0).unwrap();
+        bridge.step(1.0);
+        assert_eq!(bridge.get_sensor_value(&fan).unwrap(), 0.0);
+
+        bridge.set_value(&pwm, 30.0).unwrap();
+        bridge.step(1.0);
+        assert_eq!(bridge.get_sensor_value(&fan).unwrap(), 0.0);
+
+        bridge.set_value(&pwm, 40.0).unwrap();
+        bridge.step(1.0);
+        assert_eq!(bridge.get_sensor_value(&fan).unwrap(), 600.0);
+    }
+
+    #[test]
+    fn test_firmware_curve() {
+        let mut bridge =
+            FakeHardwareBridge::from_topology(Topology::from_str(TOPOLOGY).unwrap()).unwrap();
+
+        let cpu = bridge.hardware().temps[0].clone();
+        let pwm = bridge.hardware().controls[0].clone();
+
+        bridge.step(10000.0);
+
+        // the curve gives 75% at 45 °C, which is the equilibrium
+        assert_close(bridge.get_sensor_value(&cpu).unwrap(), 45.0);
+        assert_eq!(bridge.get_control_value(&pwm).unwrap(), 75.0);
+    }
+
+    #[test]
+    fn test_default_topology() {
+        let bridge = FakeHardwareBridge::new().unwrap();
+        let hardware = bridge.hardware();
+
+        assert_eq!(hardware.temps.len(), 2);
+        assert_eq!(hardware.fans.len(), 1);
+        assert_eq!(hardware.controls.len(), 2);
+    }
+}
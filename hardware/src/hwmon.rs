@@ -0,0 +1,946 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use thiserror::Error;
+
+use crate::{
+    merge_items,
+    thermal::{self, ThermalItem},
+    thinkpad::{self, ThinkpadItem},
+    HControl, HSensor, Hardware, HardwareBridge, HardwareValues, Mode, Value,
+};
+
+// Same hardware model as the libsensors bridge, but read directly from sysfs.
+// https://www.kernel.org/doc/Documentation/hwmon/sysfs-interface
+
+pub static DEFAULT_HWMON_ROOT: &str = "/sys/class/hwmon";
+
+static DEFAULT_PWM_ENABLE: u32 = 5;
+static MANUAL_MODE: u32 = 1;
+
+/// Where the hardware is enumerated. The default is the system.
+pub struct SysfsPaths {
+    pub hwmon: PathBuf,
+    /// Thermal zones and cooling devices are added when defined
+    pub thermal: Option<PathBuf>,
+    /// ThinkPad fan, added when defined and present
+    pub thinkpad_fan: Option<PathBuf>,
+}
+
+impl Default for SysfsPaths {
+    fn default() -> Self {
+        Self {
+            hwmon: DEFAULT_HWMON_ROOT.into(),
+            thermal: Some(thermal::DEFAULT_THERMAL_ROOT.into()),
+            thinkpad_fan: Some(thinkpad::DEFAULT_THINKPAD_FAN_PATH.into()),
+        }
+    }
+}
+
+pub struct HwmonBridge {
+    paths: SysfsPaths,
+    // removed sensors leave an empty slot, to keep indexes valid
+    sensors: Vec<Option<InternalSensor>>,
+    hardware: Hardware,
+}
+
+#[derive(Error, Debug)]
+pub enum HwmonError {
+    #[error("{0}: {1}")]
+    Io(String, io::Error),
+    #[error("Invalid data in {0}: {1}")]
+    InvalidData(String, String),
+    #[error("{0} was removed by a rescan")]
+    Removed(String),
+}
+
+type Result<T> = std::result::Result<T, HwmonError>;
+
+struct PwmFiles {
+    io: PathBuf,
+    enable: PathBuf,
+    default_enable_cached: u32,
+    // only restore the mode of pwm we changed
+    mode_changed: bool,
+}
+
+struct SensorFile {
+    io: PathBuf,
+    // temperatures are exposed in millidegree Celsius
+    divisor: Value,
+}
+
+enum InternalSensor {
+    Pwm(PwmFiles),
+    Sensor(SensorFile),
+    Thermal(ThermalItem),
+    Thinkpad(ThinkpadItem),
+}
+
+impl InternalSensor {
+    /// Take the paths of the same item, found by a rescan.
+    /// The state, like the mode to restore, is kept.
+    fn refresh(&mut self, found: InternalSensor) {
+        match (self, found) {
+            (InternalSensor::Pwm(pwm_files), InternalSensor::Pwm(mut found)) => {
+                std::mem::swap(&mut pwm_files.io, &mut found.io);
+                std::mem::swap(&mut pwm_files.enable, &mut found.enable);
+            }
+            (InternalSensor::Thermal(item), InternalSensor::Thermal(found)) => item.refresh(found),
+            // the procfs file doesn't move
+            (InternalSensor::Thinkpad(_), InternalSensor::Thinkpad(_)) => {}
+            (sensor, found) => *sensor = found,
+        }
+    }
+}
+
+impl Drop for PwmFiles {
+    fn drop(&mut self) {
+        if !self.mode_changed {
+            return;
+        }
+        if let Err(e) = write_value(&self.enable, self.default_enable_cached) {
+            error!("can't set auto to a pwm sensor when quitting: {}", e)
+        }
+    }
+}
+
+/// Files read during one batch of reads: a file holding several values,
+/// like the ThinkPad procfs file, is only read once.
+#[derive(Default)]
+pub(crate) struct Sweep {
+    contents: HashMap<PathBuf, std::result::Result<String, io::ErrorKind>>,
+}
+
+impl Sweep {
+    pub(crate) fn read(&mut self, path: &Path) -> io::Result<&str> {
+        self.contents
+            .entry(path.to_path_buf())
+            .or_insert_with(|| fs::read_to_string(path).map_err(|e| e.kind()))
+            .as_deref()
+            .map_err(|kind| io::Error::from(*kind))
+    }
+}
+
+fn read_value(path: &Path) -> Result<i64> {
+    sweep_value(&mut Sweep::default(), path)
+}
+
+fn sweep_value(sweep: &mut Sweep, path: &Path) -> Result<i64> {
+    let content = sweep
+        .read(path)
+        .map_err(|e| HwmonError::Io(format!("can't read {}", path.display()), e))?;
+
+    content
+        .trim()
+        .parse::<i64>()
+        .map_err(|e| HwmonError::InvalidData(path.display().to_string(), e.to_string()))
+}
+
+fn write_value(path: &Path, value: u32) -> Result<()> {
+    fs::write(path, value.to_string())
+        .map_err(|e| HwmonError::Io(format!("can't write {} to {}", value, path.display()), e))
+}
+
+pub(crate) fn read_string(path: &Path) -> Option<String> {
+    fs::read_to_string(path)
+        .ok()
+        .map(|s| s.trim().to_owned())
+        .filter(|s| !s.is_empty())
+}
+
+/// Some old drivers expose their attributes in the `device` directory.
+fn attributes_dir(hwmon_dir: &Path) -> PathBuf {
+    if !hwmon_dir.join("name").exists() && hwmon_dir.join("device/name").exists() {
+        hwmon_dir.join("device")
+    } else {
+        hwmon_dir.to_path_buf()
+    }
+}
+
+/// Parse the channel number of a file like `temp3_input` (prefix = "temp", suffix = "_input").
+fn channel_of(file_name: &str, prefix: &str, suffix: &str) -> Option<u32> {
+    let number = file_name.strip_prefix(prefix)?.strip_suffix(suffix)?;
+
+    if number.is_empty() || !number.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    number.parse().ok()
+}
+
+/// Resolve the real device behind a hwmon directory, if any.
+fn device_dir(attributes_dir: &Path) -> Option<PathBuf> {
+    match fs::canonicalize(attributes_dir.join("device")) {
+        Ok(device) => Some(device),
+        // old drivers: the attributes dir is already the device
+        Err(_) if attributes_dir.ends_with("device") => fs::canonicalize(attributes_dir).ok(),
+        Err(_) => None,
+    }
+}
+
+/// Path of the device relative to `/sys/devices`, like `platform/nct6775.656`
+/// or `pci0000:00/0000:03:00.0`. Unlike `hwmonN`, it doesn't change between boots.
+/// Chips without device are `virtual/{name}.{n}`, `n` being their position among
+/// the virtual chips with the same name, so identical chips get different paths.
+pub(crate) fn device_path(
+    attributes_dir: &Path,
+    name: &str,
+    virtual_names: &mut Vec<String>,
+) -> String {
+    let path = device_dir(attributes_dir).and_then(|device| {
+        let components = device
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+
+        match components.iter().rposition(|c| c == "devices") {
+            Some(index) if index + 1 < components.len() => Some(components[index + 1..].join("/")),
+            _ => None,
+        }
+    });
+
+    path.unwrap_or_else(|| {
+        let position = virtual_names.iter().filter(|n| *n == name).count();
+        virtual_names.push(name.to_owned());
+        format!("virtual/{}.{}", name, position)
+    })
+}
+
+/// Id derived from the device path, the driver name and the channel
+/// (`sub_feature`, like `temp1_input`).
+/// It doesn't depend on labels nor on the hwmon numbering.
+pub(crate) fn stable_id(device_path: &str, driver: &str, sub_feature: &str) -> String {
+    format!("{}/{}/{}", device_path, driver, sub_feature)
+}
+
+/// Build the chip name the same way libsensors does, so legacy ids stay
+/// compatible with configurations made with the libsensors bridge.
+/// Format: `{name}-{bus}-{address}`
+fn chip_name(attributes_dir: &Path, name: &str) -> String {
+    let Some(device) = device_dir(attributes_dir) else {
+        return format!("{}-virtual-0", name);
+    };
+
+    let subsystem = fs::canonicalize(device.join("subsystem"))
+        .ok()
+        .and_then(|p| p.file_name().map(|s| s.to_string_lossy().into_owned()));
+
+    let Some(device_name) = device.file_name().map(|s| s.to_string_lossy().into_owned()) else {
+        return format!("{}-virtual-0", name);
+    };
+
+    match subsystem.as_deref() {
+        // 0000:03:00.0
+        Some("pci") => {
+            let parse = || -> Option<u32> {
+                let (domain, rest) = device_name.split_once(':')?;
+                let (bus, rest) = rest.split_once(':')?;
+                let (slot, func) = rest.split_once('.')?;
+
+                let domain = u32::from_str_radix(domain, 16).ok()?;
+                let bus = u32::from_str_radix(bus, 16).ok()?;
+                let slot = u32::from_str_radix(slot, 16).ok()?;
+                let func = u32::from_str_radix(func, 16).ok()?;
+
+                Some((domain << 16) + (bus << 8) + (slot << 3) + func)
+            };
+            format!("{}-pci-{:04x}", name, parse().unwrap_or(0))
+        }
+        // 0-002d
+        Some("i2c") => match device_name.split_once('-') {
+            Some((bus, addr)) => {
+                let bus = bus.parse::<u32>().unwrap_or(0);
+                let addr = u32::from_str_radix(addr, 16).unwrap_or(0);
+                format!("{}-i2c-{}-{:02x}", name, bus, addr)
+            }
+            None => format!("{}-i2c-0-00", name),
+        },
+        // nct6775.656
+        Some("platform") | Some("of_platform") => {
+            let addr = device_name
+                .rsplit_once('.')
+                .and_then(|(_, addr)| addr.parse::<u32>().ok())
+                .unwrap_or(0);
+            format!("{}-isa-{:04x}", name, addr)
+        }
+        Some("acpi") => format!("{}-acpi-0", name),
+        _ => format!("{}-virtual-0", name),
+    }
+}
+
+fn generate_hardware(root: &Path, hardware: &mut Hardware) -> Result<Vec<InternalSensor>> {
+    let entries = fs::read_dir(root)
+        .map_err(|e| HwmonError::Io(format!("can't read {}", root.display()), e))?;
+
+    let mut hwmon_dirs = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            channel_of(&file_name, "hwmon", "").map(|index| (index, entry.path()))
+        })
+        .collect::<Vec<_>>();
+
+    hwmon_dirs.sort_by_key(|(index, _)| *index);
+
+    let mut sensors = Vec::new();
+    let mut virtual_names = Vec::new();
+
+    for (_, hwmon_dir) in hwmon_dirs {
+        let attributes_dir = attributes_dir(&hwmon_dir);
+
+        let Some(driver) = read_string(&attributes_dir.join("name")) else {
+            warn!("no name found for {}", hwmon_dir.display());
+            continue;
+        };
+
+        let chip_name = chip_name(&attributes_dir, &driver);
+        let device_path = device_path(&attributes_dir, &driver, &mut virtual_names);
+
+        let Ok(files) = fs::read_dir(&attributes_dir) else {
+            continue;
+        };
+
+        let mut fans = Vec::new();
+        let mut temps = Vec::new();
+        let mut pwms = Vec::new();
+
+        for file in files.filter_map(|file| file.ok()) {
+            let file_name = file.file_name().to_string_lossy().into_owned();
+
+            if let Some(channel) = channel_of(&file_name, "fan", "_input") {
+                fans.push(channel);
+            } else if let Some(channel) = channel_of(&file_name, "temp", "_input") {
+                temps.push(channel);
+            } else if let Some(channel) = channel_of(&file_name, "pwm", "") {
+                pwms.push(channel);
+            }
+        }
+
+        fans.sort();
+        temps.sort();
+        pwms.sort();
+
+        let h_info = |feature: &str, sub_feature: &str| {
+            let label = read_string(&attributes_dir.join(format!("{}_label", feature)))
+                .unwrap_or_else(|| feature.to_owned());
+
+            (
+                format!("{} {}", label, chip_name),
+                stable_id(&device_path, &driver, sub_feature),
+                vec![format!("{}-{}-{}", label, chip_name, sub_feature)],
+                format!(
+                    "chip path: {}\nchip name: {}\nlabel: {}\nfeature: {}",
+                    hwmon_dir.display(),
+                    chip_name,
+                    label,
+                    sub_feature
+                ),
+            )
+        };
+
+        for channel in fans {
+            let feature = format!("fan{}", channel);
+            let sub_feature = format!("{}_input", feature);
+            let (name, hardware_id, legacy_ids, info) = h_info(&feature, &sub_feature);
+
+            hardware.fans.push(Arc::new(HSensor {
+                name,
+                hardware_id,
+                legacy_ids,
+                info,
+                internal_index: sensors.len(),
+            }));
+            sensors.push(InternalSensor::Sensor(SensorFile {
+                io: attributes_dir.join(sub_feature),
+                divisor: 1.0,
+            }));
+        }
+
+        for channel in temps {
+            let feature = format!("temp{}", channel);
+            let sub_feature = format!("{}_input", feature);
+            let (name, hardware_id, legacy_ids, info) = h_info(&feature, &sub_feature);
+
+            hardware.temps.push(Arc::new(HSensor {
+                name,
+                hardware_id,
+                legacy_ids,
+                info,
+                internal_index: sensors.len(),
+            }));
+            sensors.push(InternalSensor::Sensor(SensorFile {
+                io: attributes_dir.join(sub_feature),
+                divisor: 1000.0,
+            }));
+        }
+
+        for channel in pwms {
+            let feature = format!("pwm{}", channel);
+            let enable = attributes_dir.join(format!("{}_enable", feature));
+
+            let enable_cached = match read_value(&enable) {
+                Ok(value) => {
+                    let value = value as u32;
+                    if value == MANUAL_MODE {
+                        DEFAULT_PWM_ENABLE
+                    } else {
+                        value
+                    }
+                }
+                Err(e) => {
+                    error!("can't read value of pwm {}", e);
+                    continue;
+                }
+            };
+
+            let (name, hardware_id, legacy_ids, info) = h_info(&feature, &feature);
+
+            hardware.controls.push(Arc::new(HControl {
+                name,
+                hardware_id,
+                legacy_ids,
+                info,
+                internal_index: sensors.len(),
+            }));
+            sensors.push(InternalSensor::Pwm(PwmFiles {
+                io: attributes_dir.join(&feature),
+                enable,
+                default_enable_cached: enable_cached,
+                mode_changed: false,
+            }));
+        }
+    }
+
+    Ok(sensors)
+}
+
+impl HwmonBridge {
+    /// Construct a bridge that walks `root` instead of `/sys/class/hwmon`,
+    /// without the thermal framework and the ThinkPad fan.
+    pub fn from_root(root: impl AsRef<Path>) -> crate::Result<Self> {
+        Self::from_paths(SysfsPaths {
+            hwmon: root.as_ref().to_path_buf(),
+            thermal: None,
+            thinkpad_fan: None,
+        })
+    }
+
+    pub fn from_paths(paths: SysfsPaths) -> crate::Result<Self> {
+        let mut bridge = Self {
+            paths,
+            sensors: Vec::new(),
+            hardware: Hardware::default(),
+        };
+
+        let mut hardware = Hardware::default();
+        bridge.sensors = bridge
+            .generate_hardware(&mut hardware)?
+            .into_iter()
+            .map(Some)
+            .collect();
+        bridge.hardware = hardware;
+
+        Ok(bridge)
+    }
+
+    /// A node can still hold the handle of an item removed by a rescan,
+    /// until it is bound again.
+    fn internal_sensor(&self, name: &str, internal_index: usize) -> Result<&InternalSensor> {
+        self.sensors
+            .get(internal_index)
+            .and_then(Option::as_ref)
+            .ok_or_else(|| HwmonError::Removed(name.to_owned()))
+    }
+
+    fn internal_sensor_mut(
+        &mut self,
+        name: &str,
+        internal_index: usize,
+    ) -> Result<&mut InternalSensor> {
+        self.sensors
+            .get_mut(internal_index)
+            .and_then(Option::as_mut)
+            .ok_or_else(|| HwmonError::Removed(name.to_owned()))
+    }
+
+    fn read_sensor(&self, sensor: &HSensor, sweep: &mut Sweep) -> crate::Result<Value> {
+        match self.internal_sensor(&sensor.name, sensor.internal_index)? {
+            InternalSensor::Sensor(sensor_file) => {
+                let value = sweep_value(sweep, &sensor_file.io)?;
+                Ok(value as Value / sensor_file.divisor)
+            }
+            InternalSensor::Thermal(ThermalItem::Zone(zone)) => Ok(zone.get_value(sweep)?),
+            InternalSensor::Thinkpad(ThinkpadItem::Speed(speed)) => Ok(speed.get_value(sweep)?),
+            _ => unreachable!(),
+        }
+    }
+
+    fn read_control(&self, control: &HControl, sweep: &mut Sweep) -> crate::Result<Value> {
+        match self.internal_sensor(&control.name, control.internal_index)? {
+            InternalSensor::Pwm(pwm_files) => {
+                let value = sweep_value(sweep, &pwm_files.io)?;
+                Ok((value as Value / 2.55).round())
+            }
+            InternalSensor::Thermal(ThermalItem::CoolingDevice(device)) => {
+                Ok(device.get_value(sweep)?)
+            }
+            InternalSensor::Thinkpad(ThinkpadItem::Level(level)) => Ok(level.get_value(sweep)?),
+            _ => unreachable!(),
+        }
+    }
+
+    fn generate_hardware(&self, hardware: &mut Hardware) -> Result<Vec<InternalSensor>> {
+        let mut sensors = generate_hardware(&self.paths.hwmon, hardware)?;
+
+        if let Some(thermal_root) = &self.paths.thermal {
+            thermal::generate_hardware(thermal_root, hardware, |item| {
+                sensors.push(InternalSensor::Thermal(item));
+                sensors.len() - 1
+            });
+        }
+
+        if let Some(thinkpad_fan) = &self.paths.thinkpad_fan {
+            thinkpad::generate_hardware(thinkpad_fan, hardware, |item| {
+                sensors.push(InternalSensor::Thinkpad(item));
+                sensors.len() - 1
+            });
+        }
+
+        Ok(sensors)
+    }
+}
+
+impl HardwareBridge for HwmonBridge {
+    fn new() -> crate::Result<Self> {
+        Self::from_paths(SysfsPaths::default())
+    }
+
+    fn hardware(&self) -> &Hardware {
+        &self.hardware
+    }
+
+    fn get_sensor_value(&mut self, sensor: &HSensor) -> crate::Result<Value> {
+        self.read_sensor(sensor, &mut Sweep::default())
+    }
+
+    fn get_control_value(&mut self, control: &HControl) -> crate::Result<Value> {
+        self.read_control(control, &mut Sweep::default())
+    }
+
+    fn get_values(
+        &mut self,
+        sensors: &[Arc<HSensor>],
+        controls: &[Arc<HControl>],
+    ) -> HardwareValues {
+        let mut sweep = Sweep::default();
+        let mut values = HardwareValues::default();
+
+        for sensor in sensors {
+            let value = self.read_sensor(sensor, &mut sweep);
+            values.sensors.insert(sensor.hardware_id.clone(), value);
+        }
+        for control in controls {
+            let value = self.read_control(control, &mut sweep);
+            values.controls.insert(control.hardware_id.clone(), value);
+        }
+
+        values
+    }
+
+    fn set_value(&mut self, control: &HControl, value: Value) -> crate::Result<()> {
+        match self.internal_sensor(&control.name, control.internal_index)? {
+            InternalSensor::Pwm(pwm_files) => {
+                let value = (value * 2.55).round() as u32;
+                write_value(&pwm_files.io, value)?;
+                Ok(())
+            }
+            InternalSensor::Thermal(ThermalItem::CoolingDevice(device)) => {
+                Ok(device.set_value(value)?)
+            }
+            InternalSensor::Thinkpad(ThinkpadItem::Level(level)) => Ok(level.set_value(value)?),
+            _ => unreachable!(),
+        }
+    }
+
+    fn set_mode(&mut self, control: &HControl, mode: &Mode) -> crate::Result<()> {
+        match self.internal_sensor_mut(&control.name, control.internal_index)? {
+            InternalSensor::Pwm(pwm_files) => {
+                let value = match mode {
+                    Mode::Auto => pwm_files.default_enable_cached,
+                    Mode::Manual => MANUAL_MODE,
+                    Mode::Specific(value) => *value as u32,
+                };
+
+                write_value(&pwm_files.enable, value)?;
+                pwm_files.mode_changed = *mode != Mode::Auto;
+                Ok(())
+            }
+            InternalSensor::Thermal(ThermalItem::CoolingDevice(device)) => {
+                Ok(device.set_mode(mode)?)
+            }
+            InternalSensor::Thinkpad(ThinkpadItem::Level(level)) => Ok(level.set_mode(mode)?),
+            _ => unreachable!(),
+        }
+    }
+
+    fn get_mode(&mut self, control: &HControl) -> crate::Result<Option<Mode>> {
+        match self.internal_sensor(&control.name, control.internal_index)? {
+            InternalSensor::Pwm(pwm_files) => {
+                let value = read_value(&pwm_files.enable)?;
+                let mode = match value as u32 {
+                    value if value == MANUAL_MODE => Mode::Manual,
+                    value if value == pwm_files.default_enable_cached => Mode::Auto,
+                    _ => Mode::Specific(value as i32),
+                };
+                Ok(Some(mode))
+            }
+            // the governor doesn't change the policy by itself
+            InternalSensor::Thermal(ThermalItem::CoolingDevice(_)) => Ok(None),
+            InternalSensor::Thinkpad(ThinkpadItem::Level(level)) => Ok(Some(level.get_mode()?)),
+            _ => unreachable!(),
+        }
+    }
+
+    fn update(&mut self) -> crate::Result<()> {
+        for sensor in self.sensors.iter().flatten() {
+            if let InternalSensor::Thinkpad(ThinkpadItem::Level(level)) = sensor {
+                // the other controls are still updated
+                if let Err(e) = level.keep_alive() {
+                    error!("can't keep the thinkpad fan level alive: {}", e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn can_rescan(&self) -> bool {
+        true
+    }
+
+    fn rescan(&mut self) -> crate::Result<bool> {
+        let mut found = Hardware::default();
+        let mut found_sensors = self
+            .generate_hardware(&mut found)?
+            .into_iter()
+            .map(Some)
+            .collect::<Vec<_>>();
+
+        let mut added = 0;
+        let sensors = &mut self.sensors;
+        let mut add = |index: usize, previous: Option<usize>| {
+            let found_sensor = found_sensors[index].take();
+            match previous {
+                Some(previous) => {
+                    // the hwmonN directory of a chip can change when its driver is reloaded
+                    if let (Some(sensor), Some(found_sensor)) =
+                        (&mut sensors[previous], found_sensor)
+                    {
+                        sensor.refresh(found_sensor);
+                    }
+                    previous
+                }
+                None => {
+                    added += 1;
+                    sensors.push(found_sensor);
+                    sensors.len() - 1
+                }
+            }
+        };
+
+        let mut removed = merge_items(&mut self.hardware.temps, found.temps, &mut add);
+        removed.extend(merge_items(&mut self.hardware.fans, found.fans, &mut add));
+        removed.extend(merge_items(
+            &mut self.hardware.controls,
+            found.controls,
+            &mut add,
+        ));
+
+        for index in &removed {
+            self.sensors[*index] = None;
+        }
+
+        Ok(added > 0 || !removed.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs,
+        os::unix::fs::symlink,
+        path::{Path, PathBuf},
+        sync::Arc,
+    };
+
+    use super::{HwmonBridge, SysfsPaths};
+    use crate::{HardwareBridge, Mode};
+
+    /// Create a fake sysfs tree with one nct6775 chip, in a unique temp directory.
+    fn fake_sysfs(test_name: &str) -> PathBuf {
+        let base = std::env::temp_dir().join(format!("fan-control-hwmon-{}", test_name));
+        let _ = fs::remove_dir_all(&base);
+
+        let device = base.join("devices/platform/nct6775.656");
+        let subsystem = base.join("bus/platform");
+        let hwmon = base.join("class/hwmon/hwmon2");
+
+        fs::create_dir_all(&device).unwrap();
+        fs::create_dir_all(&subsystem).unwrap();
+        fs::create_dir_all(&hwmon).unwrap();
+
+        symlink(&subsystem, device.join("subsystem")).unwrap();
+        symlink(&device, hwmon.join("device")).unwrap();
+
+        let write = |name: &str, content: &str| fs::write(hwmon.join(name), content).unwrap();
+
+        write("name", "nct6775\n");
+        write("temp1_input", "45500\n");
+        write("temp1_label", "SYSTIN\n");
+        write("temp2_input", "38000\n");
+        write("fan1_input", "1200\n");
+        write("pwm1", "128\n");
+        write("pwm1_enable", "5\n");
+        // no enable file: not controllable
+        write("pwm2", "128\n");
+
+        base
+    }
+
+    fn read(path: &Path) -> String {
+        fs::read_to_string(path).unwrap().trim().to_owned()
+    }
+
+    #[test]
+    fn test_enumeration() {
+        let base = fake_sysfs("enumeration");
+        let bridge = HwmonBridge::from_root(base.join("class/hwmon")).unwrap();
+        let hardware = bridge.hardware();
+
+        assert_eq!(hardware.temps.len(), 2);
+        assert_eq!(hardware.fans.len(), 1);
+        assert_eq!(hardware.controls.len(), 1);
+
+        assert_eq!(
+            hardware.temps[0].hardware_id,
+            "platform/nct6775.656/nct6775/temp1_input"
+        );
+        assert_eq!(
+            hardware.temps[0].legacy_ids,
+            vec!["SYSTIN-nct6775-isa-0290-temp1_input".to_owned()]
+        );
+        assert_eq!(hardware.temps[1].name, "temp2 nct6775-isa-0290");
+        assert_eq!(
+            hardware.controls[0].hardware_id,
+            "platform/nct6775.656/nct6775/pwm1"
+        );
+        assert_eq!(
+            hardware.controls[0].legacy_ids,
+            vec!["pwm1-nct6775-isa-0290-pwm1".to_owned()]
+        );
+    }
+
+    #[test]
+    fn test_values() {
+        let base = fake_sysfs("values");
+        let hwmon = base.join("class/hwmon/hwmon2");
+        let mut bridge = HwmonBridge::from_root(base.join("class/hwmon")).unwrap();
+
+        let temp = bridge.hardware().temps[0].clone();
+        let fan = bridge.hardware().fans[0].clone();
+        let control = bridge.hardware().controls[0].clone();
+
+        // millidegrees are not truncated
+        assert_eq!(bridge.get_sensor_value(&temp).unwrap(), 45.5);
+        fs::write(hwmon.join("temp1_input"), "-5250\n").unwrap();
+        assert_eq!(bridge.get_sensor_value(&temp).unwrap(), -5.25);
+        assert_eq!(bridge.get_sensor_value(&fan).unwrap(), 1200.0);
+        assert_eq!(bridge.get_control_value(&control).unwrap(), 50.0);
+
+        let mut values =
+            bridge.get_values(&[temp.clone(), fan.clone()], std::slice::from_ref(&control));
+        assert_eq!(values.take_sensor(&temp).unwrap().unwrap(), -5.25);
+        assert_eq!(values.take_sensor(&fan).unwrap().unwrap(), 1200.0);
+        assert_eq!(values.take_control(&control).unwrap().unwrap(), 50.0);
+
+        assert_eq!(bridge.get_mode(&control).unwrap(), Some(Mode::Auto));
+        bridge.set_mode(&control, &Mode::Manual).unwrap();
+        assert_eq!(read(&hwmon.join("pwm1_enable")), "1");
+        assert_eq!(bridge.get_mode(&control).unwrap(), Some(Mode::Manual));
+
+        bridge.set_value(&control, 100.0).unwrap();
+        assert_eq!(read(&hwmon.join("pwm1")), "255");
+
+        // the firmware took the control back
+        fs::write(hwmon.join("pwm1_enable"), "2\n").unwrap();
+        assert_eq!(bridge.get_mode(&control).unwrap(), Some(Mode::Specific(2)));
+
+        drop(bridge);
+        assert_eq!(read(&hwmon.join("pwm1_enable")), "5");
+    }
+
+    #[test]
+    fn test_rescan() {
+        let base = fake_sysfs("rescan");
+        let mut bridge = HwmonBridge::from_root(base.join("class/hwmon")).unwrap();
+
+        let temp = bridge.hardware().temps[0].clone();
+        let control = bridge.hardware().controls[0].clone();
+        bridge.set_mode(&control, &Mode::Manual).unwrap();
+
+        assert!(!bridge.rescan().unwrap());
+
+        // a new chip without device
+        let hwmon = base.join("class/hwmon/hwmon5");
+        fs::create_dir_all(&hwmon).unwrap();
+        fs::write(hwmon.join("name"), "nvme\n").unwrap();
+        fs::write(hwmon.join("temp1_input"), "33000\n").unwrap();
+
+        assert!(bridge.rescan().unwrap());
+
+        let hardware = bridge.hardware();
+        assert_eq!(hardware.temps.len(), 3);
+        assert!(Arc::ptr_eq(&hardware.temps[0], &temp));
+        assert!(Arc::ptr_eq(&hardware.controls[0], &control));
+        assert_eq!(
+            hardware.temps[2].hardware_id,
+            "virtual/nvme.0/nvme/temp1_input"
+        );
+
+        let nvme = hardware.temps[2].clone();
+        assert_eq!(bridge.get_sensor_value(&nvme).unwrap(), 33.0);
+        // the pwm was not reset by the rescan
+        assert_eq!(read(&base.join("class/hwmon/hwmon2/pwm1_enable")), "1");
+
+        fs::remove_dir_all(&hwmon).unwrap();
+        assert!(bridge.rescan().unwrap());
+        assert_eq!(bridge.hardware().temps.len(), 2);
+        assert_eq!(bridge.get_sensor_value(&temp).unwrap(), 45.5);
+        // a node still holding the removed handle gets an error
+        assert!(bridge.get_sensor_value(&nvme).is_err());
+
+        // the driver was reloaded, and the chip is now hwmon7
+        let hwmon = base.join("class/hwmon/hwmon7");
+        fs::rename(base.join("class/hwmon/hwmon2"), &hwmon).unwrap();
+        assert!(!bridge.rescan().unwrap());
+        assert!(Arc::ptr_eq(&bridge.hardware().temps[0], &temp));
+
+        fs::write(hwmon.join("temp1_input"), "47000\n").unwrap();
+        assert_eq!(bridge.get_sensor_value(&temp).unwrap(), 47.0);
+        bridge.set_value(&control, 100.0).unwrap();
+        assert_eq!(read(&hwmon.join("pwm1")), "255");
+
+        // the mode is still restored
+        drop(bridge);
+        assert_eq!(read(&hwmon.join("pwm1_enable")), "5");
+    }
+
+    #[test]
+    fn test_virtual_chips() {
+        let base = fake_sysfs("virtual-chips");
+
+        // two identical chips without device
+        for hwmon in ["hwmon3", "hwmon4"] {
+            let hwmon = base.join("class/hwmon").join(hwmon);
+            fs::create_dir_all(&hwmon).unwrap();
+            fs::write(hwmon.join("name"), "nvme\n").unwrap();
+            fs::write(hwmon.join("temp1_input"), "33000\n").unwrap();
+        }
+
+        let bridge = HwmonBridge::from_root(base.join("class/hwmon")).unwrap();
+        let ids = bridge
+            .hardware()
+            .temps
+            .iter()
+            .map(|temp| temp.hardware_id.as_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            ids,
+            vec![
+                "platform/nct6775.656/nct6775/temp1_input",
+                "platform/nct6775.656/nct6775/temp2_input",
+                "virtual/nvme.0/nvme/temp1_input",
+                "virtual/nvme.1/nvme/temp1_input",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_thermal() {
+        let base = fake_sysfs("thermal");
+        let thermal = base.join("class/thermal");
+        let device = thermal.join("cooling_device0");
+        fs::create_dir_all(thermal.join("thermal_zone0")).unwrap();
+        fs::create_dir_all(&device).unwrap();
+        fs::write(thermal.join("thermal_zone0/type"), "soc-thermal\n").unwrap();
+        fs::write(thermal.join("thermal_zone0/temp"), "51000\n").unwrap();
+        fs::write(device.join("type"), "gpio-fan\n").unwrap();
+        fs::write(device.join("max_state"), "2\n").unwrap();
+        fs::write(device.join("cur_state"), "0\n").unwrap();
+
+        let mut bridge = HwmonBridge::from_paths(SysfsPaths {
+            hwmon: base.join("class/hwmon"),
+            thermal: Some(thermal.clone()),
+            thinkpad_fan: None,
+        })
+        .unwrap();
+
+        let hardware = bridge.hardware();
+        assert_eq!(hardware.temps.len(), 3);
+        assert_eq!(hardware.controls.len(), 2);
+        let zone = hardware.temps[2].clone();
+        let cooling_device = hardware.controls[1].clone();
+        assert_eq!(zone.hardware_id, "thermal/soc-thermal/0");
+        assert_eq!(cooling_device.hardware_id, "cooling_device/gpio-fan/0");
+
+        assert_eq!(bridge.get_sensor_value(&zone).unwrap(), 51.0);
+        bridge.set_mode(&cooling_device, &Mode::Manual).unwrap();
+        bridge.set_value(&cooling_device, 50.0).unwrap();
+        assert_eq!(read(&device.join("cur_state")), "1");
+        assert_eq!(bridge.get_control_value(&cooling_device).unwrap(), 50.0);
+
+        assert!(!bridge.rescan().unwrap());
+
+        fs::remove_dir_all(&device).unwrap();
+        assert!(bridge.rescan().unwrap());
+        assert_eq!(bridge.hardware().controls.len(), 1);
+    }
+
+    #[test]
+    fn test_keep_alive_error() {
+        let base = fake_sysfs("keep-alive");
+        let proc = base.join("proc/acpi/ibm");
+        fs::create_dir_all(&proc).unwrap();
+        fs::write(
+            proc.join("fan"),
+            "status:\t\tenabled
+speed:\t\t2560
+level:\t\t3
+commands:\tlevel <level> (<level> is 0-7, auto, disengaged, full-speed)
+commands:\twatchdog <timeout> (<timeout> is 0 (off), 1-120 (seconds))
+",
+        )
+        .unwrap();
+
+        let mut bridge = HwmonBridge::from_paths(SysfsPaths {
+            hwmon: base.join("class/hwmon"),
+            thermal: None,
+            thinkpad_fan: Some(proc.join("fan")),
+        })
+        .unwrap();
+
+        let level = bridge.hardware().controls[1].clone();
+        assert_eq!(level.hardware_id, "thinkpad_acpi/fan/level");
+        bridge.set_mode(&level, &Mode::Manual).unwrap();
+
+        // the watchdog can't be written, but the other controls are updated
+        fs::remove_dir_all(&proc).unwrap();
+        bridge.update().unwrap();
+        let pwm1 = bridge.hardware().controls[0].clone();
+        bridge.set_value(&pwm1, 100.0).unwrap();
+    }
+}
@@ -1,28 +1,59 @@
 use derive_more::Display;
-use serde::Serialize;
-use std::{fmt::Debug, rc::Rc};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt::Debug, sync::Arc};
 use thiserror::Error;
 
 #[macro_use]
 extern crate log;
 
-#[cfg(target_os = "linux")]
+#[cfg(all(target_os = "linux", feature = "libsensors", not(feature = "hwmon")))]
 pub mod linux;
 
+#[cfg(target_os = "linux")]
+pub mod hwmon;
+
+#[cfg(target_os = "linux")]
+pub mod thermal;
+
+#[cfg(target_os = "linux")]
+pub mod thinkpad;
+
 #[cfg(target_os = "windows")]
 pub mod windows;
 
 #[cfg(feature = "fake_hardware")]
 pub mod fake_hardware;
 
+pub mod dry_run;
+
+pub mod record;
+
+pub mod snapshot;
+
 #[derive(Error, Debug)]
 pub enum HardwareError {
-    #[cfg(target_os = "linux")]
+    #[cfg(all(target_os = "linux", feature = "libsensors", not(feature = "hwmon")))]
     #[error(transparent)]
     Linux(#[from] linux::LinuxError),
+    #[cfg(target_os = "linux")]
+    #[error(transparent)]
+    Hwmon(#[from] hwmon::HwmonError),
+    #[cfg(target_os = "linux")]
+    #[error(transparent)]
+    Thermal(#[from] thermal::ThermalError),
+    #[cfg(target_os = "linux")]
+    #[error(transparent)]
+    Thinkpad(#[from] thinkpad::ThinkpadError),
     #[cfg(target_os = "windows")]
     #[error(transparent)]
     Windows(#[from] windows::WindowsError),
+    #[error(transparent)]
+    Record(#[from] record::RecordError),
+    #[error(transparent)]
+    Snapshot(#[from] snapshot::SnapshotError),
+    #[cfg(feature = "fake_hardware")]
+    #[error(transparent)]
+    FakeHardware(#[from] fake_hardware::FakeHardwareError),
 }
 
 type Result<T> = std::result::Result<T, HardwareError>;
@@ -31,17 +62,30 @@ pub trait HItem {
     fn name(&self) -> &String;
     fn id(&self) -> &String;
     fn info(&self) -> &String;
+    fn legacy_ids(&self) -> &[String];
 }
 
-#[derive(Serialize, Debug, Eq)]
+#[derive(Serialize, Deserialize, Debug, Eq)]
 pub struct HSensor {
     pub name: String,
     #[serde(rename = "id")]
     pub hardware_id: String,
 
-    #[serde(skip)]
+    /// Ids previously generated for this hardware, used to
+    /// find it from older configurations.
+    #[serde(
+        default,
+        rename = "legacyIds",
+        alias = "legacy_ids",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub legacy_ids: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub info: String,
 
+    /// Not serialized, bridges need to generate it again.
+    /// Deserialized items all have 0.
     #[serde(skip)]
     internal_index: usize,
 }
@@ -58,17 +102,33 @@ impl HItem for HSensor {
     fn info(&self) -> &String {
         &self.info
     }
+
+    fn legacy_ids(&self) -> &[String] {
+        &self.legacy_ids
+    }
 }
 
-#[derive(Serialize, Debug, Eq)]
+#[derive(Serialize, Deserialize, Debug, Eq)]
 pub struct HControl {
     pub name: String,
     #[serde(rename = "id")]
     pub hardware_id: String,
 
-    #[serde(skip)]
+    /// Ids previously generated for this hardware, used to
+    /// find it from older configurations.
+    #[serde(
+        default,
+        rename = "legacyIds",
+        alias = "legacy_ids",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub legacy_ids: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub info: String,
 
+    /// Not serialized, bridges need to generate it again.
+    /// Deserialized items all have 0.
     #[serde(skip)]
     internal_index: usize,
 }
@@ -85,6 +145,10 @@ impl HItem for HControl {
     fn info(&self) -> &String {
         &self.info
     }
+
+    fn legacy_ids(&self) -> &[String] {
+        &self.legacy_ids
+    }
 }
 
 impl PartialEq for HControl {
@@ -99,23 +163,119 @@ impl PartialEq for HSensor {
     }
 }
 
-#[derive(Serialize, Debug, Default)]
+/// Hardware which can be found again when rescanning.
+pub(crate) trait Rescannable: HItem + Sized {
+    fn internal_index(&self) -> usize;
+    fn with_internal_index(&self, internal_index: usize) -> Self;
+}
+
+impl Rescannable for HSensor {
+    fn internal_index(&self) -> usize {
+        self.internal_index
+    }
+
+    fn with_internal_index(&self, internal_index: usize) -> Self {
+        Self {
+            name: self.name.clone(),
+            hardware_id: self.hardware_id.clone(),
+            legacy_ids: self.legacy_ids.clone(),
+            info: self.info.clone(),
+            internal_index,
+        }
+    }
+}
+
+impl Rescannable for HControl {
+    fn internal_index(&self) -> usize {
+        self.internal_index
+    }
+
+    fn with_internal_index(&self, internal_index: usize) -> Self {
+        Self {
+            name: self.name.clone(),
+            hardware_id: self.hardware_id.clone(),
+            legacy_ids: self.legacy_ids.clone(),
+            info: self.info.clone(),
+            internal_index,
+        }
+    }
+}
+
+/// Replace `items` with the `found` ones, but keep the `Arc` of items
+/// with the same id, so nodes stay bound to them.
+/// `bind` receives the internal index of an item in the new enumeration,
+/// and its internal index in the bridge if it was already there. It returns
+/// its internal index in the bridge. Kept items can have new paths.
+/// Return the internal indexes of the items which disappeared.
+pub(crate) fn merge_items<T: Rescannable>(
+    items: &mut Vec<Arc<T>>,
+    found: Vec<Arc<T>>,
+    mut bind: impl FnMut(usize, Option<usize>) -> usize,
+) -> Vec<usize> {
+    let mut previous = std::mem::take(items);
+
+    for item in found {
+        match previous.iter().position(|p| p.id() == item.id()) {
+            Some(index) => {
+                let kept = previous.remove(index);
+                bind(item.internal_index(), Some(kept.internal_index()));
+                items.push(kept);
+            }
+            None => {
+                let internal_index = bind(item.internal_index(), None);
+                info!("New hardware found: {}.", item.id());
+                items.push(Arc::new(item.with_internal_index(internal_index)));
+            }
+        }
+    }
+
+    previous
+        .iter()
+        .map(|item| {
+            info!("Hardware removed: {}.", item.id());
+            item.internal_index()
+        })
+        .collect()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Hardware {
     #[serde(default, rename = "Control")]
-    pub controls: Vec<Rc<HControl>>,
+    pub controls: Vec<Arc<HControl>>,
     #[serde(default, rename = "Fan")]
-    pub fans: Vec<Rc<HSensor>>,
+    pub fans: Vec<Arc<HSensor>>,
     #[serde(default, rename = "Temp")]
-    pub temps: Vec<Rc<HSensor>>,
+    pub temps: Vec<Arc<HSensor>>,
 }
 
-pub type Value = i32;
+pub type Value = f64;
 
-#[derive(Debug, Clone, PartialEq, Eq, Display)]
+/// Values read by [`HardwareBridge::get_values`], by hardware id.
+/// Items which were not requested are missing.
+#[derive(Debug, Default)]
+pub struct HardwareValues {
+    pub sensors: HashMap<String, Result<Value>>,
+    pub controls: HashMap<String, Result<Value>>,
+}
+
+impl HardwareValues {
+    /// Remove the value of this sensor, so it is only used once.
+    pub fn take_sensor(&mut self, sensor: &HSensor) -> Option<Result<Value>> {
+        self.sensors.remove(&sensor.hardware_id)
+    }
+
+    /// Remove the value of this control, so it is only used once.
+    pub fn take_control(&mut self, control: &HControl) -> Option<Result<Value>> {
+        self.controls.remove(&control.hardware_id)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Display, Serialize, Deserialize)]
 pub enum Mode {
     Auto,
     Manual,
-    Specific(Value),
+    /// Value written to `pwm_enable`, or its equivalent
+    Specific(i32),
 }
 
 /// Try to construct a new hardware bridge
@@ -126,11 +286,25 @@ pub fn new() -> Result<impl HardwareBridge> {
     #[cfg(all(not(feature = "fake_hardware"), target_os = "windows"))]
     return windows::WindowsBridge::new();
 
-    #[cfg(all(not(feature = "fake_hardware"), target_os = "linux"))]
+    // sysfs is also used when libsensors is not linked
+    #[cfg(all(
+        not(feature = "fake_hardware"),
+        any(feature = "hwmon", not(feature = "libsensors")),
+        target_os = "linux"
+    ))]
+    return hwmon::HwmonBridge::new();
+
+    #[cfg(all(
+        not(feature = "fake_hardware"),
+        feature = "libsensors",
+        not(feature = "hwmon"),
+        target_os = "linux"
+    ))]
     return linux::LinuxBridge::new();
 }
 
-pub trait HardwareBridge {
+/// Bridges are `Send`, so the update loop can run in its own thread.
+pub trait HardwareBridge: Send {
     fn new() -> Result<Self>
     where
         Self: Sized;
@@ -140,9 +314,36 @@ pub trait HardwareBridge {
     fn get_sensor_value(&mut self, sensor: &HSensor) -> Result<Value>;
     fn get_control_value(&mut self, control: &HControl) -> Result<Value>;
 
+    /// Read several sensors and controls in one call, so bridges can
+    /// batch their I/O. The default reads them one by one.
+    fn get_values(
+        &mut self,
+        sensors: &[Arc<HSensor>],
+        controls: &[Arc<HControl>],
+    ) -> HardwareValues {
+        let mut values = HardwareValues::default();
+
+        for sensor in sensors {
+            let value = self.get_sensor_value(sensor);
+            values.sensors.insert(sensor.hardware_id.clone(), value);
+        }
+        for control in controls {
+            let value = self.get_control_value(control);
+            values.controls.insert(control.hardware_id.clone(), value);
+        }
+
+        values
+    }
+
     fn set_value(&mut self, control: &HControl, value: Value) -> Result<()>;
     fn set_mode(&mut self, control: &HControl, mode: &Mode) -> Result<()>;
 
+    /// Read back the mode of a control, to notice when the firmware takes it back.
+    /// None if the mode can't be read.
+    fn get_mode(&mut self, _control: &HControl) -> Result<Option<Mode>> {
+        Ok(None)
+    }
+
     /// Used on Windows, because we update all sensors in one function, so
     /// we don't want to update at each call, instead, we call this function
     /// one time in each update iteration.
@@ -150,6 +351,19 @@ pub trait HardwareBridge {
         Ok(())
     }
 
+    /// Enumerate the hardware again, to find devices that appeared or disappeared
+    /// since the start. Unchanged hardware keep the same `Arc`.
+    /// Return true if the hardware changed.
+    fn rescan(&mut self) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// False if `rescan` can't find new hardware with this bridge,
+    /// so the action is not offered to the user.
+    fn can_rescan(&self) -> bool {
+        false
+    }
+
     /// Used on Windows to shutdown the server properly.
     fn shutdown(&mut self) -> Result<()> {
         Ok(())
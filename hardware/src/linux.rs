@@ -1,9 +1,15 @@
-use std::{fmt::Debug, rc::Rc};
+use std::{collections::HashMap, fmt::Debug, path::Path, sync::Arc};
 
 use lm_sensors::{feature, value, ChipRef, FeatureRef, LMSensors, SubFeatureRef};
 use thiserror::Error;
 
-use crate::{HControl, HSensor, Hardware, HardwareBridge, HardwareError, Mode, Value};
+use crate::{
+    hwmon::{self, Sweep},
+    merge_items,
+    thermal::{self, ThermalItem},
+    thinkpad::{self, ThinkpadItem},
+    HControl, HSensor, Hardware, HardwareBridge, HardwareError, HardwareValues, Mode, Value,
+};
 use ouroboros::self_referencing;
 
 // https://www.kernel.org/doc/Documentation/hwmon/sysfs-interface
@@ -13,14 +19,23 @@ static DEFAULT_PWM_ENABLE: f64 = 5.0;
 static MANUAL_MODE: f64 = 1.0;
 
 pub struct LinuxBridge {
-    lm_sensor: LinuxBridgeSelfRef,
+    // None when libsensors can't be initialized again during a rescan
+    lm_sensor: Option<LinuxBridgeSelfRef>,
     hardware: Hardware,
 }
 
+// SAFETY: the references to libsensors never leave the bridge, which is only
+// moved between threads, never shared.
+unsafe impl Send for LinuxBridge {}
+
 #[derive(Error, Debug)]
 pub enum LinuxError {
     #[error("{0}: {1}")]
     LmSensors(String, lm_sensors::errors::Error),
+    #[error("libsensors is not initialized")]
+    NotInitialized,
+    #[error("{0} was removed by a rescan")]
+    Removed(String),
 }
 
 #[self_referencing]
@@ -32,11 +47,14 @@ struct LinuxBridgeSelfRef {
     // but this structure that store references should be dropped first
     #[borrows(lib)]
     #[not_covariant]
-    sensors: Vec<InternalSubFeatureRef<'this>>,
+    sensors: Vec<Option<InternalSubFeatureRef<'this>>>,
 }
 
 impl Drop for PwmRefs<'_> {
     fn drop(&mut self) {
+        if !self.restore_on_drop {
+            return;
+        }
         if let Err(e) = self.enable.set_raw_value(self.default_enable_cached) {
             error!("can't set auto to a pwm sensor when quitting: {}", e)
         }
@@ -47,6 +65,8 @@ struct PwmRefs<'a> {
     io: SubFeatureRef<'a>,
     enable: SubFeatureRef<'a>,
     default_enable_cached: f64,
+    // false when the pwm is enumerated again by a rescan
+    restore_on_drop: bool,
 }
 struct SensorRefs<'a> {
     io: SubFeatureRef<'a>,
@@ -55,6 +75,79 @@ struct SensorRefs<'a> {
 enum InternalSubFeatureRef<'a> {
     Pwm(PwmRefs<'a>),
     Sensor(SensorRefs<'a>),
+    Thermal(ThermalItem),
+    Thinkpad(ThinkpadItem),
+}
+
+/// State of an item which is kept by a rescan, while libsensors is initialized again.
+enum PreviousItem {
+    Pwm(f64),
+    Thermal(ThermalItem),
+    Thinkpad(ThinkpadItem),
+}
+
+fn init_lib() -> crate::Result<LMSensors> {
+    lm_sensors::Initializer::default()
+        .initialize()
+        .map_err(|e| {
+            HardwareError::Linux(LinuxError::LmSensors("failed to init libsensor".into(), e))
+        })
+}
+
+/// Enumerate the chips of libsensors, and the items it doesn't know.
+fn build_refs(lib: LMSensors, hardware: &mut Hardware) -> LinuxBridgeSelfRef {
+    LinuxBridgeSelfRefBuilder {
+        lib,
+        sensors_builder: |lib: &LMSensors| {
+            let mut sensors = generate_hardware(lib, hardware)
+                .into_iter()
+                .map(Some)
+                .collect::<Vec<_>>();
+            // libsensors only knows hwmon chips
+            thermal::generate_hardware(
+                Path::new(thermal::DEFAULT_THERMAL_ROOT),
+                hardware,
+                |item| {
+                    sensors.push(Some(InternalSubFeatureRef::Thermal(item)));
+                    sensors.len() - 1
+                },
+            );
+            thinkpad::generate_hardware(
+                Path::new(thinkpad::DEFAULT_THINKPAD_FAN_PATH),
+                hardware,
+                |item| {
+                    sensors.push(Some(InternalSubFeatureRef::Thinkpad(item)));
+                    sensors.len() - 1
+                },
+            );
+            sensors
+        },
+    }
+    .build()
+}
+
+/// A node can still hold the handle of an item removed by a rescan,
+/// until it is bound again.
+fn internal_ref<'a, 'b>(
+    sensors: &'b [Option<InternalSubFeatureRef<'a>>],
+    name: &str,
+    internal_index: usize,
+) -> crate::Result<&'b InternalSubFeatureRef<'a>> {
+    sensors
+        .get(internal_index)
+        .and_then(Option::as_ref)
+        .ok_or_else(|| HardwareError::Linux(LinuxError::Removed(name.to_owned())))
+}
+
+fn internal_ref_mut<'a, 'b>(
+    sensors: &'b mut [Option<InternalSubFeatureRef<'a>>],
+    name: &str,
+    internal_index: usize,
+) -> crate::Result<&'b mut InternalSubFeatureRef<'a>> {
+    sensors
+        .get_mut(internal_index)
+        .and_then(Option::as_mut)
+        .ok_or_else(|| HardwareError::Linux(LinuxError::Removed(name.to_owned())))
 }
 
 fn generate_hardware<'a>(
@@ -64,6 +157,7 @@ fn generate_hardware<'a>(
     struct HInfo {
         name: String,
         hardware_id: String,
+        legacy_id: String,
         info: String,
     }
 
@@ -75,12 +169,14 @@ fn generate_hardware<'a>(
         InvalidData(String),
     }
 
+    /// `chip_device` is the driver name and the device path of the chip.
     fn get_infos_from_refs(
         chip_ref: &ChipRef,
+        chip_device: Option<&(String, String)>,
         feature_ref: &FeatureRef,
         sub_feature_ref: &SubFeatureRef,
     ) -> std::result::Result<HInfo, GetInfoError> {
-        let Some(chip_path) = chip_ref.path() else {
+        let (Some(chip_path), Some((driver, device_path))) = (chip_ref.path(), chip_device) else {
             return Err(GetInfoError::InvalidData("chip path is none".to_owned()));
         };
 
@@ -100,7 +196,8 @@ fn generate_hardware<'a>(
 
         Ok(HInfo {
             name: format!("{} {}", label, chip_name),
-            hardware_id: format!("{}-{}-{}", label, chip_name, sub_feature_name),
+            hardware_id: hwmon::stable_id(device_path, driver, sub_feature_name),
+            legacy_id: format!("{}-{}-{}", label, chip_name, sub_feature_name),
             info: format!(
                 "chip path: {}\nchip name: {}\nbus: {}\nlabel: {}\nfeature: {}",
                 chip_path.display(),
@@ -113,8 +210,18 @@ fn generate_hardware<'a>(
     }
 
     let mut sensors = Vec::new();
+    let mut virtual_names = Vec::new();
 
     for chip_ref in lib.chip_iter(None) {
+        // once per chip, so identical virtual chips get different paths
+        let chip_device = chip_ref.path().map(|chip_path| {
+            let driver = hwmon::read_string(&chip_path.join("name"))
+                .or_else(|| chip_ref.name().ok())
+                .unwrap_or_default();
+            let device_path = hwmon::device_path(chip_path, &driver, &mut virtual_names);
+            (driver, device_path)
+        });
+
         for feature_ref in chip_ref.feature_iter() {
             let next_internal_index = sensors.len();
 
@@ -127,15 +234,21 @@ fn generate_hardware<'a>(
                             continue;
                         };
 
-                        match get_infos_from_refs(&chip_ref, &feature_ref, &sub_feature_ref) {
+                        match get_infos_from_refs(
+                            &chip_ref,
+                            chip_device.as_ref(),
+                            &feature_ref,
+                            &sub_feature_ref,
+                        ) {
                             Ok(h_info) => {
                                 let sensor = SensorRefs {
                                     io: sub_feature_ref,
                                 };
                                 sensors.push(InternalSubFeatureRef::Sensor(sensor));
-                                hardware.fans.push(Rc::new(HSensor {
+                                hardware.fans.push(Arc::new(HSensor {
                                     name: h_info.name,
                                     hardware_id: h_info.hardware_id,
+                                    legacy_ids: vec![h_info.legacy_id],
                                     info: h_info.info,
                                     internal_index: next_internal_index,
                                 }));
@@ -152,15 +265,21 @@ fn generate_hardware<'a>(
                             continue;
                         };
 
-                        match get_infos_from_refs(&chip_ref, &feature_ref, &sub_feature_ref) {
+                        match get_infos_from_refs(
+                            &chip_ref,
+                            chip_device.as_ref(),
+                            &feature_ref,
+                            &sub_feature_ref,
+                        ) {
                             Ok(h_info) => {
                                 let sensor = SensorRefs {
                                     io: sub_feature_ref,
                                 };
                                 sensors.push(InternalSubFeatureRef::Sensor(sensor));
-                                hardware.temps.push(Rc::new(HSensor {
+                                hardware.temps.push(Arc::new(HSensor {
                                     name: h_info.name,
                                     hardware_id: h_info.hardware_id,
+                                    legacy_ids: vec![h_info.legacy_id],
                                     info: h_info.info,
                                     internal_index: next_internal_index,
                                 }));
@@ -196,17 +315,24 @@ fn generate_hardware<'a>(
                             }
                         };
 
-                        match get_infos_from_refs(&chip_ref, &feature_ref, &sub_feature_ref_io) {
+                        match get_infos_from_refs(
+                            &chip_ref,
+                            chip_device.as_ref(),
+                            &feature_ref,
+                            &sub_feature_ref_io,
+                        ) {
                             Ok(h_info) => {
                                 let sensor = InternalSubFeatureRef::Pwm(PwmRefs {
                                     io: sub_feature_ref_io,
                                     enable: sub_feature_ref_enable,
                                     default_enable_cached: enable_cached,
+                                    restore_on_drop: true,
                                 });
                                 sensors.push(sensor);
-                                hardware.controls.push(Rc::new(HControl {
+                                hardware.controls.push(Arc::new(HControl {
                                     name: h_info.name,
                                     hardware_id: h_info.hardware_id,
+                                    legacy_ids: vec![h_info.legacy_id],
                                     info: h_info.info,
                                     internal_index: next_internal_index,
                                 }));
@@ -225,27 +351,67 @@ fn generate_hardware<'a>(
     sensors
 }
 
+fn read_sensor(
+    sensors: &[Option<InternalSubFeatureRef<'_>>],
+    sensor: &HSensor,
+    sweep: &mut Sweep,
+) -> crate::Result<Value> {
+    match internal_ref(sensors, &sensor.name, sensor.internal_index)? {
+        InternalSubFeatureRef::Sensor(sensor_refs) => match sensor_refs.io.raw_value() {
+            Ok(value) => Ok(value),
+            Err(e) => Err(HardwareError::Linux(LinuxError::LmSensors(
+                "sensor".to_owned(),
+                e,
+            ))),
+        },
+        InternalSubFeatureRef::Thermal(ThermalItem::Zone(zone)) => Ok(zone.get_value(sweep)?),
+        InternalSubFeatureRef::Thinkpad(ThinkpadItem::Speed(speed)) => Ok(speed.get_value(sweep)?),
+        _ => unreachable!(),
+    }
+}
+
+fn read_control(
+    sensors: &[Option<InternalSubFeatureRef<'_>>],
+    control: &HControl,
+    sweep: &mut Sweep,
+) -> crate::Result<Value> {
+    match internal_ref(sensors, &control.name, control.internal_index)? {
+        InternalSubFeatureRef::Pwm(pwm_refs) => match pwm_refs.io.raw_value() {
+            Ok(value) => Ok((value / 2.55).round()),
+            Err(e) => Err(HardwareError::Linux(LinuxError::LmSensors(
+                "pwm".to_owned(),
+                e,
+            ))),
+        },
+        InternalSubFeatureRef::Thermal(ThermalItem::CoolingDevice(device)) => {
+            Ok(device.get_value(sweep)?)
+        }
+        InternalSubFeatureRef::Thinkpad(ThinkpadItem::Level(level)) => Ok(level.get_value(sweep)?),
+        _ => unreachable!(),
+    }
+}
+
+impl LinuxBridge {
+    fn lm_sensor(&self) -> crate::Result<&LinuxBridgeSelfRef> {
+        self.lm_sensor
+            .as_ref()
+            .ok_or(HardwareError::Linux(LinuxError::NotInitialized))
+    }
+
+    fn lm_sensor_mut(&mut self) -> crate::Result<&mut LinuxBridgeSelfRef> {
+        self.lm_sensor
+            .as_mut()
+            .ok_or(HardwareError::Linux(LinuxError::NotInitialized))
+    }
+}
+
 impl HardwareBridge for LinuxBridge {
     fn new() -> crate::Result<Self> {
         let mut hardware = Hardware::default();
-
-        let lib = match lm_sensors::Initializer::default().initialize() {
-            Ok(lib) => lib,
-            Err(e) => {
-                return Err(HardwareError::Linux(LinuxError::LmSensors(
-                    "failed to init libsensor".into(),
-                    e,
-                )))
-            }
-        };
-        let bridge = LinuxBridgeSelfRefBuilder {
-            lib,
-            sensors_builder: |lib: &LMSensors| generate_hardware(lib, &mut hardware),
-        }
-        .build();
+        let lm_sensor = build_refs(init_lib()?, &mut hardware);
 
         Ok(Self {
-            lm_sensor: bridge,
+            lm_sensor: Some(lm_sensor),
             hardware,
         })
     }
@@ -254,45 +420,56 @@ impl HardwareBridge for LinuxBridge {
     }
 
     fn get_sensor_value(&mut self, sensor: &HSensor) -> crate::Result<Value> {
-        self.lm_sensor.with_sensors(|sensors| {
-            match sensors.get(sensor.internal_index).expect("no sensor found") {
-                InternalSubFeatureRef::Sensor(sensor_refs) => match sensor_refs.io.raw_value() {
-                    Ok(value) => Ok(value as i32),
-                    Err(e) => Err(HardwareError::Linux(LinuxError::LmSensors(
-                        "sensor".to_owned(),
-                        e,
-                    ))),
-                },
-                _ => unreachable!(),
-            }
-        })
+        self.lm_sensor()?
+            .with_sensors(|sensors| read_sensor(sensors, sensor, &mut Sweep::default()))
     }
+
     fn get_control_value(&mut self, control: &HControl) -> crate::Result<Value> {
-        self.lm_sensor.with_sensors(|sensors| {
-            match sensors
-                .get(control.internal_index)
-                .expect("no sensor found")
-            {
-                InternalSubFeatureRef::Pwm(pwm_refs) => match pwm_refs.io.raw_value() {
-                    Ok(value) => Ok((value / 2.55) as i32),
-                    Err(e) => Err(HardwareError::Linux(LinuxError::LmSensors(
-                        "pwm".to_owned(),
-                        e,
-                    ))),
-                },
-                _ => unreachable!(),
+        self.lm_sensor()?
+            .with_sensors(|sensors| read_control(sensors, control, &mut Sweep::default()))
+    }
+
+    fn get_values(
+        &mut self,
+        sensors: &[Arc<HSensor>],
+        controls: &[Arc<HControl>],
+    ) -> HardwareValues {
+        let Some(lm_sensor) = &self.lm_sensor else {
+            let not_initialized = || Err(HardwareError::Linux(LinuxError::NotInitialized));
+            return HardwareValues {
+                sensors: sensors
+                    .iter()
+                    .map(|sensor| (sensor.hardware_id.clone(), not_initialized()))
+                    .collect(),
+                controls: controls
+                    .iter()
+                    .map(|control| (control.hardware_id.clone(), not_initialized()))
+                    .collect(),
+            };
+        };
+
+        lm_sensor.with_sensors(|refs| {
+            let mut sweep = Sweep::default();
+            let mut values = HardwareValues::default();
+
+            for sensor in sensors {
+                let value = read_sensor(refs, sensor, &mut sweep);
+                values.sensors.insert(sensor.hardware_id.clone(), value);
+            }
+            for control in controls {
+                let value = read_control(refs, control, &mut sweep);
+                values.controls.insert(control.hardware_id.clone(), value);
             }
+
+            values
         })
     }
 
     fn set_value(&mut self, control: &HControl, value: Value) -> crate::Result<()> {
-        self.lm_sensor.with_sensors(|sensors| {
-            match sensors
-                .get(control.internal_index)
-                .expect("no sensor found")
-            {
+        self.lm_sensor()?.with_sensors(|sensors| {
+            match internal_ref(sensors, &control.name, control.internal_index)? {
                 InternalSubFeatureRef::Pwm(pwm_refs) => {
-                    let value = value as f64 * 2.55;
+                    let value = (value * 2.55).round();
                     if let Err(e) = pwm_refs.io.set_raw_value(value) {
                         let explication = format!("can't set value {} to a pwm", value);
                         let e = LinuxError::LmSensors(explication, e);
@@ -300,17 +477,20 @@ impl HardwareBridge for LinuxBridge {
                     }
                     Ok(())
                 }
+                InternalSubFeatureRef::Thermal(ThermalItem::CoolingDevice(device)) => {
+                    Ok(device.set_value(value)?)
+                }
+                InternalSubFeatureRef::Thinkpad(ThinkpadItem::Level(level)) => {
+                    Ok(level.set_value(value)?)
+                }
                 _ => unreachable!(),
             }
         })
     }
 
     fn set_mode(&mut self, control: &HControl, mode: &Mode) -> crate::Result<()> {
-        self.lm_sensor.with_sensors(|sensors| {
-            match sensors
-                .get(control.internal_index)
-                .expect("no sensor found")
-            {
+        self.lm_sensor_mut()?.with_sensors_mut(|sensors| {
+            match internal_ref_mut(sensors, &control.name, control.internal_index)? {
                 InternalSubFeatureRef::Pwm(pwm_refs) => {
                     let value = match mode {
                         Mode::Auto => pwm_refs.default_enable_cached,
@@ -325,8 +505,154 @@ impl HardwareBridge for LinuxBridge {
                     }
                     Ok(())
                 }
+                InternalSubFeatureRef::Thermal(ThermalItem::CoolingDevice(device)) => {
+                    Ok(device.set_mode(mode)?)
+                }
+                InternalSubFeatureRef::Thinkpad(ThinkpadItem::Level(level)) => {
+                    Ok(level.set_mode(mode)?)
+                }
+                _ => unreachable!(),
+            }
+        })
+    }
+
+    fn get_mode(&mut self, control: &HControl) -> crate::Result<Option<Mode>> {
+        self.lm_sensor()?.with_sensors(|sensors| {
+            match internal_ref(sensors, &control.name, control.internal_index)? {
+                InternalSubFeatureRef::Pwm(pwm_refs) => match pwm_refs.enable.raw_value() {
+                    Ok(value) if value == MANUAL_MODE => Ok(Some(Mode::Manual)),
+                    Ok(value) if value == pwm_refs.default_enable_cached => Ok(Some(Mode::Auto)),
+                    Ok(value) => Ok(Some(Mode::Specific(value as i32))),
+                    Err(e) => Err(HardwareError::Linux(LinuxError::LmSensors(
+                        "pwm enable".to_owned(),
+                        e,
+                    ))),
+                },
+                InternalSubFeatureRef::Thermal(ThermalItem::CoolingDevice(_)) => Ok(None),
+                InternalSubFeatureRef::Thinkpad(ThinkpadItem::Level(level)) => {
+                    Ok(Some(level.get_mode()?))
+                }
                 _ => unreachable!(),
             }
         })
     }
+
+    fn update(&mut self) -> crate::Result<()> {
+        self.lm_sensor()?.with_sensors(|sensors| {
+            for sensor in sensors.iter().flatten() {
+                if let InternalSubFeatureRef::Thinkpad(ThinkpadItem::Level(level)) = sensor {
+                    // the other controls are still updated
+                    if let Err(e) = level.keep_alive() {
+                        error!("can't keep the thinkpad fan level alive: {}", e);
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn can_rescan(&self) -> bool {
+        true
+    }
+
+    fn rescan(&mut self) -> crate::Result<bool> {
+        // libsensors only enumerates the chips when it is initialized,
+        // so the previous items are dropped, but their state is kept
+        let mut previous_items = HashMap::new();
+        if let Some(lm_sensor) = &mut self.lm_sensor {
+            lm_sensor.with_sensors_mut(|sensors| {
+                for (index, sensor) in sensors.iter_mut().enumerate() {
+                    let previous = match sensor.take() {
+                        Some(InternalSubFeatureRef::Pwm(mut pwm_refs)) => {
+                            pwm_refs.restore_on_drop = false;
+                            PreviousItem::Pwm(pwm_refs.default_enable_cached)
+                        }
+                        Some(InternalSubFeatureRef::Thermal(item)) => PreviousItem::Thermal(item),
+                        Some(InternalSubFeatureRef::Thinkpad(item)) => PreviousItem::Thinkpad(item),
+                        Some(InternalSubFeatureRef::Sensor(_)) | None => continue,
+                    };
+                    previous_items.insert(index, previous);
+                }
+            });
+        }
+        self.lm_sensor = None;
+
+        let mut found = Hardware::default();
+        let mut lm_sensor = build_refs(init_lib()?, &mut found);
+
+        // the kept items keep their internal index
+        let previous_len = self
+            .hardware
+            .temps
+            .iter()
+            .chain(&self.hardware.fans)
+            .map(|sensor| sensor.internal_index + 1)
+            .chain(
+                self.hardware
+                    .controls
+                    .iter()
+                    .map(|control| control.internal_index + 1),
+            )
+            .max()
+            .unwrap_or_default();
+
+        let hardware = &mut self.hardware;
+        let (added, removed) = lm_sensor.with_sensors_mut(|sensors| {
+            let mut found_sensors = std::mem::take(sensors);
+            sensors.resize_with(previous_len, || None);
+
+            let mut added = 0;
+            let mut add = |index: usize, previous: Option<usize>| {
+                let found_sensor = found_sensors[index].take();
+                let previous_item = previous.and_then(|previous| previous_items.remove(&previous));
+                let sensor = match (found_sensor, previous_item) {
+                    (
+                        Some(InternalSubFeatureRef::Pwm(mut pwm_refs)),
+                        Some(PreviousItem::Pwm(default_enable_cached)),
+                    ) => {
+                        pwm_refs.default_enable_cached = default_enable_cached;
+                        Some(InternalSubFeatureRef::Pwm(pwm_refs))
+                    }
+                    (
+                        Some(InternalSubFeatureRef::Thermal(found_item)),
+                        Some(PreviousItem::Thermal(mut item)),
+                    ) => {
+                        item.refresh(found_item);
+                        Some(InternalSubFeatureRef::Thermal(item))
+                    }
+                    // the procfs file doesn't move
+                    (
+                        Some(InternalSubFeatureRef::Thinkpad(_)),
+                        Some(PreviousItem::Thinkpad(item)),
+                    ) => Some(InternalSubFeatureRef::Thinkpad(item)),
+                    (found_sensor, _) => found_sensor,
+                };
+
+                match previous {
+                    Some(previous) => {
+                        sensors[previous] = sensor;
+                        previous
+                    }
+                    None => {
+                        added += 1;
+                        sensors.push(sensor);
+                        sensors.len() - 1
+                    }
+                }
+            };
+
+            let mut removed = merge_items(&mut hardware.temps, found.temps, &mut add);
+            removed.extend(merge_items(&mut hardware.fans, found.fans, &mut add));
+            removed.extend(merge_items(
+                &mut hardware.controls,
+                found.controls,
+                &mut add,
+            ));
+
+            (added, removed)
+        });
+        self.lm_sensor = Some(lm_sensor);
+
+        Ok(added > 0 || !removed.is_empty())
+    }
 }
@@ -0,0 +1,644 @@
+//! Record every interaction with a [`HardwareBridge`] in a file, and replay it later.
+//!
+//! The recording is a JSON lines file. The first line describes the hardware,
+//! then each line is an event. [`HardwareBridge::update`] delimits the cycles.
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write as _},
+    path::Path,
+    sync::Arc,
+    time::Instant,
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{HControl, HItem, HSensor, Hardware, HardwareBridge, HardwareValues, Mode, Value};
+
+static RECORD_ENV_VAR: &str = "FAN_CONTROL_RECORD";
+static REPLAY_ENV_VAR: &str = "FAN_CONTROL_REPLAY";
+
+#[derive(Error, Debug)]
+pub enum RecordError {
+    #[error("Can't access the recording: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Can't parse the recording: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("{0} is not defined")]
+    MissingPath(&'static str),
+    #[error("Invalid recording: {0}")]
+    InvalidRecording(String),
+    #[error("End of the recording")]
+    EndOfRecording,
+    #[error("No value was recorded for {0}")]
+    NoValue(String),
+    #[error("Recorded error: {0}")]
+    Recorded(String),
+}
+
+type Result<T> = std::result::Result<T, RecordError>;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Item {
+    name: String,
+    id: String,
+    #[serde(default)]
+    legacy_ids: Vec<String>,
+    #[serde(default)]
+    info: String,
+}
+
+impl Item {
+    fn from_hitem<H: HItem>(item: &H) -> Self {
+        Self {
+            name: item.name().clone(),
+            id: item.id().clone(),
+            legacy_ids: item.legacy_ids().to_vec(),
+            info: item.info().clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind")]
+pub enum Action {
+    Value { id: String, value: Value },
+    Mode { id: String, mode: Mode },
+}
+
+impl Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Action::Value { id, value } => write!(f, "set value {} to {}", value, id),
+            Action::Mode { id, mode } => write!(f, "set mode {} to {}", mode, id),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "event")]
+enum Event {
+    Hardware {
+        controls: Vec<Item>,
+        fans: Vec<Item>,
+        temps: Vec<Item>,
+    },
+    Update {
+        time: f64,
+    },
+    SensorValue {
+        time: f64,
+        id: String,
+        value: std::result::Result<Value, String>,
+    },
+    ControlValue {
+        time: f64,
+        id: String,
+        value: std::result::Result<Value, String>,
+    },
+    ControlMode {
+        time: f64,
+        id: String,
+        mode: std::result::Result<Option<Mode>, String>,
+    },
+    Action {
+        time: f64,
+        #[serde(flatten)]
+        action: Action,
+    },
+}
+
+/// Wrap a bridge, and write everything that goes through it in a file.
+pub struct RecordBridge<H: HardwareBridge> {
+    bridge: H,
+    writer: BufWriter<File>,
+    start: Instant,
+}
+
+impl<H: HardwareBridge> RecordBridge<H> {
+    pub fn from_bridge(bridge: H, path: impl AsRef<Path>) -> crate::Result<Self> {
+        let file = File::create(path).map_err(RecordError::from)?;
+
+        let mut record = Self {
+            bridge,
+            writer: BufWriter::new(file),
+            start: Instant::now(),
+        };
+
+        let hardware = record.bridge.hardware();
+        let event = Event::Hardware {
+            controls: hardware
+                .controls
+                .iter()
+                .map(|c| Item::from_hitem(&**c))
+                .collect(),
+            fans: hardware
+                .fans
+                .iter()
+                .map(|f| Item::from_hitem(&**f))
+                .collect(),
+            temps: hardware
+                .temps
+                .iter()
+                .map(|t| Item::from_hitem(&**t))
+                .collect(),
+        };
+        record.write_event(&event)?;
+
+        Ok(record)
+    }
+
+    pub fn inner(&self) -> &H {
+        &self.bridge
+    }
+
+    fn time(&self) -> f64 {
+        self.start.elapsed().as_secs_f64()
+    }
+
+    fn write_event(&mut self, event: &Event) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, event)?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+}
+
+impl<H: HardwareBridge> HardwareBridge for RecordBridge<H> {
+    fn new() -> crate::Result<Self> {
+        let path =
+            std::env::var(RECORD_ENV_VAR).map_err(|_| RecordError::MissingPath(RECORD_ENV_VAR))?;
+
+        Self::from_bridge(H::new()?, path)
+    }
+
+    fn hardware(&self) -> &Hardware {
+        self.bridge.hardware()
+    }
+
+    fn get_sensor_value(&mut self, sensor: &HSensor) -> crate::Result<Value> {
+        let res = self.bridge.get_sensor_value(sensor);
+
+        let event = Event::SensorValue {
+            time: self.time(),
+            id: sensor.hardware_id.clone(),
+            value: res.as_ref().map(|v| *v).map_err(|e| e.to_string()),
+        };
+        self.write_event(&event)?;
+
+        res
+    }
+
+    fn get_control_value(&mut self, control: &HControl) -> crate::Result<Value> {
+        let res = self.bridge.get_control_value(control);
+
+        let event = Event::ControlValue {
+            time: self.time(),
+            id: control.hardware_id.clone(),
+            value: res.as_ref().map(|v| *v).map_err(|e| e.to_string()),
+        };
+        self.write_event(&event)?;
+
+        res
+    }
+
+    /// Forwarded, so the batching of the inner bridge is kept.
+    /// Each value is recorded as if it was read alone.
+    fn get_values(
+        &mut self,
+        sensors: &[Arc<HSensor>],
+        controls: &[Arc<HControl>],
+    ) -> HardwareValues {
+        let values = self.bridge.get_values(sensors, controls);
+
+        let time = self.time();
+        let sensor_events = sensors.iter().filter_map(|sensor| {
+            let res = values.sensors.get(&sensor.hardware_id)?;
+            Some(Event::SensorValue {
+                time,
+                id: sensor.hardware_id.clone(),
+                value: res.as_ref().map(|v| *v).map_err(|e| e.to_string()),
+            })
+        });
+        let control_events = controls.iter().filter_map(|control| {
+            let res = values.controls.get(&control.hardware_id)?;
+            Some(Event::ControlValue {
+                time,
+                id: control.hardware_id.clone(),
+                value: res.as_ref().map(|v| *v).map_err(|e| e.to_string()),
+            })
+        });
+
+        for event in sensor_events.chain(control_events) {
+            if let Err(e) = self.write_event(&event) {
+                error!("can't record the values: {}", e);
+            }
+        }
+
+        values
+    }
+
+    fn set_value(&mut self, control: &HControl, value: Value) -> crate::Result<()> {
+        let event = Event::Action {
+            time: self.time(),
+            action: Action::Value {
+                id: control.hardware_id.clone(),
+                value,
+            },
+        };
+        self.write_event(&event)?;
+
+        self.bridge.set_value(control, value)
+    }
+
+    fn set_mode(&mut self, control: &HControl, mode: &Mode) -> crate::Result<()> {
+        let event = Event::Action {
+            time: self.time(),
+            action: Action::Mode {
+                id: control.hardware_id.clone(),
+                mode: mode.clone(),
+            },
+        };
+        self.write_event(&event)?;
+
+        self.bridge.set_mode(control, mode)
+    }
+
+    fn get_mode(&mut self, control: &HControl) -> crate::Result<Option<Mode>> {
+        let res = self.bridge.get_mode(control);
+
+        let event = Event::ControlMode {
+            time: self.time(),
+            id: control.hardware_id.clone(),
+            mode: res.as_ref().map(|m| m.clone()).map_err(|e| e.to_string()),
+        };
+        self.write_event(&event)?;
+
+        res
+    }
+
+    fn update(&mut self) -> crate::Result<()> {
+        self.writer.flush().map_err(RecordError::from)?;
+
+        let event = Event::Update { time: self.time() };
+        self.write_event(&event)?;
+
+        self.bridge.update()
+    }
+
+    fn rescan(&mut self) -> crate::Result<bool> {
+        self.bridge.rescan()
+    }
+
+    fn can_rescan(&self) -> bool {
+        self.bridge.can_rescan()
+    }
+
+    fn shutdown(&mut self) -> crate::Result<()> {
+        self.writer.flush().map_err(RecordError::from)?;
+        self.bridge.shutdown()
+    }
+}
+
+#[derive(Default, Debug)]
+struct Cycle {
+    sensors: HashMap<String, Vec<std::result::Result<Value, String>>>,
+    controls: HashMap<String, Vec<std::result::Result<Value, String>>>,
+    modes: HashMap<String, Vec<std::result::Result<Option<Mode>, String>>>,
+    writes: Vec<Action>,
+}
+
+/// Writes of a cycle which are not the same as in the recording.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    pub cycle: usize,
+    pub recorded: Vec<Action>,
+    pub replayed: Vec<Action>,
+}
+
+/// Serve the values of a recording, in the same order, and keep
+/// the writes to compare them with the recorded ones.
+///
+/// The first cycle contains what happened before the first update.
+pub struct ReplayBridge {
+    hardware: Hardware,
+    cycles: Vec<Cycle>,
+    current: usize,
+    // number of reads of each id in the current cycle
+    cursors: HashMap<String, usize>,
+    writes: Vec<Vec<Action>>,
+}
+
+impl ReplayBridge {
+    pub fn from_file(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let file = File::open(path).map_err(RecordError::from)?;
+        Self::from_reader(BufReader::new(file))
+    }
+
+    pub fn from_reader(reader: impl BufRead) -> crate::Result<Self> {
+        let mut events = Vec::new();
+        for line in reader.lines() {
+            let line = line.map_err(RecordError::from)?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let event: Event = serde_json::from_str(&line).map_err(RecordError::from)?;
+            events.push(event);
+        }
+
+        let mut events = events.into_iter();
+
+        let hardware = match events.next() {
+            Some(Event::Hardware {
+                controls,
+                fans,
+                temps,
+            }) => {
+                let mut hardware = Hardware::default();
+
+                for (index, item) in temps.into_iter().enumerate() {
+                    hardware.temps.push(Arc::new(HSensor {
+                        name: item.name,
+                        hardware_id: item.id,
+                        legacy_ids: item.legacy_ids,
+                        info: item.info,
+                        internal_index: index,
+                    }));
+                }
+                // fans are stored after temps, to keep the index unique
+                for item in fans {
+                    hardware.fans.push(Arc::new(HSensor {
+                        name: item.name,
+                        hardware_id: item.id,
+                        legacy_ids: item.legacy_ids,
+                        info: item.info,
+                        internal_index: hardware.temps.len() + hardware.fans.len(),
+                    }));
+                }
+                for (index, item) in controls.into_iter().enumerate() {
+                    hardware.controls.push(Arc::new(HControl {
+                        name: item.name,
+                        hardware_id: item.id,
+                        legacy_ids: item.legacy_ids,
+                        info: item.info,
+                        internal_index: index,
+                    }));
+                }
+                hardware
+            }
+            _ => {
+                return Err(RecordError::InvalidRecording(
+                    "the first event should describe the hardware".into(),
+                )
+                .into())
+            }
+        };
+
+        let mut cycles = vec![Cycle::default()];
+
+        for event in events {
+            let cycle = cycles.last_mut().unwrap();
+
+            match event {
+                Event::Hardware { .. } => {
+                    return Err(RecordError::InvalidRecording(
+                        "the hardware should be described only once".into(),
+                    )
+                    .into())
+                }
+                Event::Update { .. } => cycles.push(Cycle::default()),
+                Event::SensorValue { id, value, .. } => {
+                    cycle.sensors.entry(id).or_default().push(value)
+                }
+                Event::ControlValue { id, value, .. } => {
+                    cycle.controls.entry(id).or_default().push(value)
+                }
+                Event::ControlMode { id, mode, .. } => {
+                    cycle.modes.entry(id).or_default().push(mode)
+                }
+                Event::Action { action, .. } => cycle.writes.push(action),
+            }
+        }
+
+        Ok(Self {
+            hardware,
+            cycles,
+            current: 0,
+            cursors: HashMap::new(),
+            writes: vec![Vec::new()],
+        })
+    }
+
+    /// Number of cycles in the recording, including the first one.
+    pub fn cycles(&self) -> usize {
+        self.cycles.len()
+    }
+
+    /// Compare the writes of all cycles replayed so far with the recording.
+    pub fn diff(&self) -> Vec<Mismatch> {
+        self.writes
+            .iter()
+            .enumerate()
+            .filter_map(|(index, replayed)| {
+                let recorded = &self.cycles[index].writes;
+                if recorded != replayed {
+                    Some(Mismatch {
+                        cycle: index,
+                        recorded: recorded.clone(),
+                        replayed: replayed.clone(),
+                    })
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Serve the next recorded value for this id in the current cycle.
+    /// If the value was not read in this cycle, the last value
+    /// from a previous cycle is used.
+    /// `key` identifies the cursor of this kind of value.
+    fn read<T: Clone>(
+        &mut self,
+        id: &str,
+        key: String,
+        values: impl Fn(&Cycle) -> Option<&Vec<std::result::Result<T, String>>>,
+    ) -> Result<T> {
+        let value = match values(&self.cycles[self.current]) {
+            Some(values) => {
+                let cursor = self.cursors.entry(key).or_default();
+                let value = &values[(*cursor).min(values.len() - 1)];
+                *cursor += 1;
+                value
+            }
+            None => self.cycles[..self.current]
+                .iter()
+                .rev()
+                .find_map(|cycle| values(cycle).and_then(|values| values.last()))
+                .ok_or_else(|| RecordError::NoValue(id.to_owned()))?,
+        };
+
+        value.clone().map_err(RecordError::Recorded)
+    }
+}
+
+impl HardwareBridge for ReplayBridge {
+    fn new() -> crate::Result<Self> {
+        let path =
+            std::env::var(REPLAY_ENV_VAR).map_err(|_| RecordError::MissingPath(REPLAY_ENV_VAR))?;
+
+        Self::from_file(path)
+    }
+
+    fn hardware(&self) -> &Hardware {
+        &self.hardware
+    }
+
+    fn get_sensor_value(&mut self, sensor: &HSensor) -> crate::Result<Value> {
+        let id = &sensor.hardware_id;
+        Ok(self.read(id, id.clone(), |cycle| cycle.sensors.get(id))?)
+    }
+
+    fn get_control_value(&mut self, control: &HControl) -> crate::Result<Value> {
+        let id = &control.hardware_id;
+        Ok(self.read(id, id.clone(), |cycle| cycle.controls.get(id))?)
+    }
+
+    /// Recordings without modes return None.
+    fn get_mode(&mut self, control: &HControl) -> crate::Result<Option<Mode>> {
+        let id = &control.hardware_id;
+        match self.read(id, format!("mode:{}", id), |cycle| cycle.modes.get(id)) {
+            Err(RecordError::NoValue(_)) => Ok(None),
+            res => Ok(res?),
+        }
+    }
+
+    fn set_value(&mut self, control: &HControl, value: Value) -> crate::Result<()> {
+        self.writes[self.current].push(Action::Value {
+            id: control.hardware_id.clone(),
+            value,
+        });
+        Ok(())
+    }
+
+    fn set_mode(&mut self, control: &HControl, mode: &Mode) -> crate::Result<()> {
+        self.writes[self.current].push(Action::Mode {
+            id: control.hardware_id.clone(),
+            mode: mode.clone(),
+        });
+        Ok(())
+    }
+
+    fn update(&mut self) -> crate::Result<()> {
+        if self.current + 1 >= self.cycles.len() {
+            return Err(RecordError::EndOfRecording.into());
+        }
+
+        self.current += 1;
+        self.cursors.clear();
+        self.writes.push(Vec::new());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::{Action, Mismatch, RecordBridge, ReplayBridge};
+    use crate::{HardwareBridge, Mode};
+
+    static RECORDING: &str = r#"
+{"event":"Hardware","controls":[{"name":"control1","id":"control1"}],"fans":[{"name":"fan1","id":"fan1"}],"temps":[{"name":"temp1","id":"temp1"}]}
+{"event":"Action","time":0.0,"kind":"Mode","id":"control1","mode":"Manual"}
+{"event":"Update","time":0.1}
+{"event":"SensorValue","time":0.1,"id":"temp1","value":{"Ok":40}}
+{"event":"SensorValue","time":0.1,"id":"temp1","value":{"Ok":41}}
+{"event":"Action","time":0.1,"kind":"Value","id":"control1","value":50}
+{"event":"Update","time":1.1}
+{"event":"SensorValue","time":1.1,"id":"fan1","value":{"Err":"can't read"}}
+{"event":"Action","time":1.1,"kind":"Value","id":"control1","value":60}
+"#;
+
+    #[test]
+    fn test_replay() {
+        let mut bridge = ReplayBridge::from_reader(Cursor::new(RECORDING)).unwrap();
+
+        assert_eq!(bridge.cycles(), 3);
+
+        let temp1 = bridge.hardware().temps[0].clone();
+        let fan1 = bridge.hardware().fans[0].clone();
+        let control1 = bridge.hardware().controls[0].clone();
+
+        bridge.set_mode(&control1, &Mode::Manual).unwrap();
+
+        bridge.update().unwrap();
+        assert_eq!(bridge.get_sensor_value(&temp1).unwrap(), 40.0);
+        assert_eq!(bridge.get_sensor_value(&temp1).unwrap(), 41.0);
+        // the last value is repeated
+        assert_eq!(bridge.get_sensor_value(&temp1).unwrap(), 41.0);
+        assert!(bridge.get_sensor_value(&fan1).is_err());
+        bridge.set_value(&control1, 50.0).unwrap();
+
+        bridge.update().unwrap();
+        // not read in this cycle
+        assert_eq!(bridge.get_sensor_value(&temp1).unwrap(), 41.0);
+        assert!(bridge.get_sensor_value(&fan1).is_err());
+        bridge.set_value(&control1, 70.0).unwrap();
+
+        assert!(bridge.update().is_err());
+
+        assert_eq!(
+            bridge.diff(),
+            vec![Mismatch {
+                cycle: 2,
+                recorded: vec![Action::Value {
+                    id: "control1".into(),
+                    value: 60.0
+                }],
+                replayed: vec![Action::Value {
+                    id: "control1".into(),
+                    value: 70.0
+                }],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_record_then_replay() {
+        let path = std::env::temp_dir().join("fan-control-record-test.jsonl");
+
+        let recording = ReplayBridge::from_reader(Cursor::new(RECORDING)).unwrap();
+        let mut record = RecordBridge::from_bridge(recording, &path).unwrap();
+
+        let temp1 = record.hardware().temps[0].clone();
+        let fan1 = record.hardware().fans[0].clone();
+        let control1 = record.hardware().controls[0].clone();
+
+        record.update().unwrap();
+        let value = record.get_sensor_value(&temp1).unwrap();
+        record.set_value(&control1, value).unwrap();
+        // batch reads are recorded value by value
+        let mut values = record.get_values(&[temp1.clone(), fan1.clone()], &[]);
+        let value = values.take_sensor(&temp1).unwrap().unwrap();
+        record.set_value(&control1, value).unwrap();
+        record.shutdown().unwrap();
+
+        let mut replay = ReplayBridge::from_file(&path).unwrap();
+        assert_eq!(replay.cycles(), 2);
+
+        replay.update().unwrap();
+        let value = replay.get_sensor_value(&temp1).unwrap();
+        replay.set_value(&control1, value).unwrap();
+        let mut values = replay.get_values(&[temp1.clone(), fan1.clone()], &[]);
+        assert_eq!(values.take_sensor(&temp1).unwrap().unwrap(), 41.0);
+        assert!(values.take_sensor(&temp1).is_none());
+        replay.set_value(&control1, 41.0).unwrap();
+
+        assert!(replay.diff().is_empty());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,385 @@
+//! Hardware with its last known values, as written in `hardware.toml`.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{merge_items, HControl, HSensor, Hardware, HardwareBridge, Mode, Rescannable, Value};
+
+static SNAPSHOT_ENV_VAR: &str = "FAN_CONTROL_SNAPSHOT";
+
+#[derive(Error, Debug)]
+pub enum SnapshotError {
+    #[error("Can't read snapshot file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Can't parse snapshot file: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("{0} is not defined")]
+    MissingPath(&'static str),
+    #[error("No value in the snapshot for {0}")]
+    NoValue(String),
+}
+
+type Result<T> = std::result::Result<T, SnapshotError>;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ItemSnapshot<T> {
+    #[serde(flatten)]
+    pub item: Arc<T>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct HardwareSnapshot {
+    #[serde(default, rename = "Control")]
+    pub controls: Vec<ItemSnapshot<HControl>>,
+    #[serde(default, rename = "Fan")]
+    pub fans: Vec<ItemSnapshot<HSensor>>,
+    #[serde(default, rename = "Temp")]
+    pub temps: Vec<ItemSnapshot<HSensor>>,
+}
+
+impl HardwareSnapshot {
+    /// Read the current value of all hardware.
+    /// Values which can't be read are left empty.
+    pub fn from_bridge<H: HardwareBridge>(bridge: &mut H) -> Self {
+        let hardware = bridge.hardware();
+        let controls = hardware.controls.clone();
+        let fans = hardware.fans.clone();
+        let temps = hardware.temps.clone();
+
+        let mut read_sensors = |sensors: Vec<Arc<HSensor>>| {
+            sensors
+                .into_iter()
+                .map(|sensor| {
+                    let value = match bridge.get_sensor_value(&sensor) {
+                        Ok(value) => Some(value),
+                        Err(e) => {
+                            warn!("can't read {}: {}", sensor.hardware_id, e);
+                            None
+                        }
+                    };
+                    ItemSnapshot {
+                        item: sensor,
+                        value,
+                    }
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let fans = read_sensors(fans);
+        let temps = read_sensors(temps);
+
+        let controls = controls
+            .into_iter()
+            .map(|control| {
+                let value = match bridge.get_control_value(&control) {
+                    Ok(value) => Some(value),
+                    Err(e) => {
+                        warn!("can't read {}: {}", control.hardware_id, e);
+                        None
+                    }
+                };
+                ItemSnapshot {
+                    item: control,
+                    value,
+                }
+            })
+            .collect();
+
+        Self {
+            controls,
+            fans,
+            temps,
+        }
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Self::from_str(&content)
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(content: &str) -> Result<Self> {
+        let mut snapshot: Self = toml::from_str(content)?;
+        snapshot.generate_indexes();
+        Ok(snapshot)
+    }
+
+    /// Indexes are not serialized, so we generate them again.
+    /// Temps and fans share the indexes of sensors, like in [`SnapshotBridge`].
+    fn generate_indexes(&mut self) {
+        let sensors = self.temps.iter_mut().chain(self.fans.iter_mut());
+        for (index, snapshot) in sensors.enumerate() {
+            snapshot.item = Arc::new(snapshot.item.with_internal_index(index));
+        }
+        for (index, snapshot) in self.controls.iter_mut().enumerate() {
+            snapshot.item = Arc::new(snapshot.item.with_internal_index(index));
+        }
+    }
+}
+
+/// Serve the values of a snapshot, which never change,
+/// except the controls that are set.
+pub struct SnapshotBridge {
+    hardware: Hardware,
+    sensors: Vec<Option<Value>>,
+    controls: Vec<Option<Value>>,
+    /// Read again when rescanning
+    path: Option<PathBuf>,
+}
+
+impl SnapshotBridge {
+    pub fn from_snapshot(mut snapshot: HardwareSnapshot) -> Self {
+        let mut hardware = Hardware::default();
+        let mut sensors = Vec::new();
+        let mut controls = Vec::new();
+
+        // the items can come from another bridge
+        snapshot.generate_indexes();
+
+        for snapshot in snapshot.temps {
+            hardware.temps.push(snapshot.item);
+            sensors.push(snapshot.value);
+        }
+
+        for snapshot in snapshot.fans {
+            hardware.fans.push(snapshot.item);
+            sensors.push(snapshot.value);
+        }
+
+        for snapshot in snapshot.controls {
+            hardware.controls.push(snapshot.item);
+            controls.push(snapshot.value);
+        }
+
+        Self {
+            hardware,
+            sensors,
+            controls,
+            path: None,
+        }
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let mut bridge = Self::from_snapshot(HardwareSnapshot::from_file(&path)?);
+        bridge.path = Some(path.as_ref().to_path_buf());
+        Ok(bridge)
+    }
+}
+
+impl HardwareBridge for SnapshotBridge {
+    fn new() -> crate::Result<Self> {
+        let path = std::env::var(SNAPSHOT_ENV_VAR)
+            .map_err(|_| SnapshotError::MissingPath(SNAPSHOT_ENV_VAR))?;
+
+        Self::from_file(path)
+    }
+
+    fn hardware(&self) -> &Hardware {
+        &self.hardware
+    }
+
+    fn get_sensor_value(&mut self, sensor: &HSensor) -> crate::Result<Value> {
+        self.sensors[sensor.internal_index]
+            .ok_or_else(|| SnapshotError::NoValue(sensor.hardware_id.clone()).into())
+    }
+
+    fn get_control_value(&mut self, control: &HControl) -> crate::Result<Value> {
+        self.controls[control.internal_index]
+            .ok_or_else(|| SnapshotError::NoValue(control.hardware_id.clone()).into())
+    }
+
+    fn set_value(&mut self, control: &HControl, value: Value) -> crate::Result<()> {
+        debug!("set value {} to {}", value, control.name);
+        self.controls[control.internal_index] = Some(value);
+        Ok(())
+    }
+
+    fn set_mode(&mut self, control: &HControl, mode: &Mode) -> crate::Result<()> {
+        debug!("set mode {} to {}", mode, control.name);
+        Ok(())
+    }
+
+    fn can_rescan(&self) -> bool {
+        self.path.is_some()
+    }
+
+    /// Read the snapshot file again, values included.
+    fn rescan(&mut self) -> crate::Result<bool> {
+        let Some(path) = &self.path else {
+            return Ok(false);
+        };
+
+        let found = Self::from_snapshot(HardwareSnapshot::from_file(path)?);
+
+        let mut changed = false;
+
+        let sensors = &mut self.sensors;
+        let mut add_sensor = |_, previous| {
+            if let Some(previous) = previous {
+                return previous;
+            }
+            changed = true;
+            sensors.push(None);
+            sensors.len() - 1
+        };
+        let mut removed = merge_items(
+            &mut self.hardware.temps,
+            found.hardware.temps.clone(),
+            &mut add_sensor,
+        );
+        removed.extend(merge_items(
+            &mut self.hardware.fans,
+            found.hardware.fans.clone(),
+            &mut add_sensor,
+        ));
+        for index in removed {
+            changed = true;
+            self.sensors[index] = None;
+        }
+
+        let controls = &mut self.controls;
+        let mut add_control = |_, previous| {
+            if let Some(previous) = previous {
+                return previous;
+            }
+            changed = true;
+            controls.push(None);
+            controls.len() - 1
+        };
+        for index in merge_items(
+            &mut self.hardware.controls,
+            found.hardware.controls.clone(),
+            &mut add_control,
+        ) {
+            changed = true;
+            self.controls[index] = None;
+        }
+
+        // update the values of all hardware
+        for (items, found_items) in [
+            (&self.hardware.temps, &found.hardware.temps),
+            (&self.hardware.fans, &found.hardware.fans),
+        ] {
+            for (item, found_item) in items.iter().zip(found_items) {
+                self.sensors[item.internal_index] = found.sensors[found_item.internal_index];
+            }
+        }
+        for (item, found_item) in self.hardware.controls.iter().zip(&found.hardware.controls) {
+            self.controls[item.internal_index] = found.controls[found_item.internal_index];
+        }
+
+        Ok(changed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{HardwareSnapshot, SnapshotBridge};
+    use crate::HardwareBridge;
+
+    static SNAPSHOT: &str = r#"
+[[Control]]
+name = "pwm1"
+id = "platform/nct6775.656/nct6775/pwm1"
+value = 40
+
+[[Fan]]
+name = "fan1"
+id = "platform/nct6775.656/nct6775/fan1_input"
+value = 1200
+
+[[Temp]]
+name = "CPU"
+id = "platform/coretemp.0/coretemp/temp1_input"
+info = "coretemp"
+value = 52
+
+[[Temp]]
+name = "temp2"
+id = "platform/nct6775.656/nct6775/temp2_input"
+"#;
+
+    #[test]
+    fn test_bridge() {
+        let snapshot = HardwareSnapshot::from_str(SNAPSHOT).unwrap();
+        let mut bridge = SnapshotBridge::from_snapshot(snapshot);
+
+        let hardware = bridge.hardware();
+        let pwm1 = hardware.controls[0].clone();
+        let fan1 = hardware.fans[0].clone();
+        let cpu = hardware.temps[0].clone();
+        let temp2 = hardware.temps[1].clone();
+
+        assert_eq!(cpu.info, "coretemp");
+        assert_ne!(fan1, cpu);
+
+        assert_eq!(bridge.get_sensor_value(&fan1).unwrap(), 1200.0);
+        assert_eq!(bridge.get_sensor_value(&cpu).unwrap(), 52.0);
+        assert!(bridge.get_sensor_value(&temp2).is_err());
+
+        assert_eq!(bridge.get_control_value(&pwm1).unwrap(), 40.0);
+        bridge.set_value(&pwm1, 60.0).unwrap();
+        assert_eq!(bridge.get_control_value(&pwm1).unwrap(), 60.0);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let snapshot = HardwareSnapshot::from_str(SNAPSHOT).unwrap();
+        let mut bridge = SnapshotBridge::from_snapshot(snapshot);
+
+        let content = toml::to_string_pretty(&HardwareSnapshot::from_bridge(&mut bridge)).unwrap();
+        let snapshot = HardwareSnapshot::from_str(&content).unwrap();
+
+        assert_eq!(snapshot.controls.len(), 1);
+        assert_eq!(snapshot.fans.len(), 1);
+        assert_eq!(snapshot.temps.len(), 2);
+
+        assert_eq!(snapshot.temps[0].item.name, "CPU");
+        assert_eq!(snapshot.temps[0].item.info, "coretemp");
+        assert_eq!(snapshot.temps[0].value, Some(52.0));
+        assert_eq!(snapshot.temps[1].value, None);
+        assert_eq!(
+            snapshot.fans[0].item.hardware_id,
+            "platform/nct6775.656/nct6775/fan1_input"
+        );
+        assert_eq!(snapshot.controls[0].value, Some(40.0));
+    }
+
+    #[test]
+    fn test_legacy_ids() {
+        let snapshot = HardwareSnapshot::from_str(
+            r#"
+[[Temp]]
+name = "CPU"
+id = "platform/coretemp.0/coretemp/temp1_input"
+legacyIds = ["Package id 0-coretemp-isa-0000-temp1_input"]
+value = 52
+
+[[Temp]]
+name = "temp2"
+id = "platform/nct6775.656/nct6775/temp2_input"
+"#,
+        )
+        .unwrap();
+
+        // each item has its own index
+        assert_ne!(snapshot.temps[0].item, snapshot.temps[1].item);
+
+        let content = toml::to_string(&snapshot).unwrap();
+        assert_eq!(content.matches("legacyIds").count(), 1);
+
+        let snapshot = HardwareSnapshot::from_str(&content).unwrap();
+        assert_eq!(
+            snapshot.temps[0].item.legacy_ids,
+            vec!["Package id 0-coretemp-isa-0000-temp1_input".to_owned()]
+        );
+        assert!(snapshot.temps[1].item.legacy_ids.is_empty());
+    }
+}
@@ -0,0 +1,447 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use thiserror::Error;
+
+use crate::{
+    hwmon::{read_string, Sweep},
+    HControl, HSensor, Hardware, Mode, Value,
+};
+
+// Thermal zones and cooling devices of the kernel thermal framework, for fans
+// which are not exposed by a hwmon driver (laptops, ARM boards).
+// https://www.kernel.org/doc/html/latest/driver-api/thermal/sysfs-api.html
+
+pub static DEFAULT_THERMAL_ROOT: &str = "/sys/class/thermal";
+
+/// With this governor, the kernel doesn't change the state of the cooling devices.
+static USER_SPACE_POLICY: &str = "user_space";
+
+/// Types of the cooling devices which are fans. The others (cpufreq, powerclamp,
+/// backlight, ...) throttle the system, and are left to the kernel.
+static FAN_TYPES: [&str; 3] = ["Fan", "pwm-fan", "gpio-fan"];
+
+#[derive(Error, Debug)]
+pub enum ThermalError {
+    #[error("{0}: {1}")]
+    Io(String, io::Error),
+    #[error("Invalid data in {0}: {1}")]
+    InvalidData(String, String),
+    #[error("Mode {0} is not supported by cooling devices")]
+    UnsupportedMode(Mode),
+}
+
+type Result<T> = std::result::Result<T, ThermalError>;
+
+pub(crate) struct ThermalZone {
+    temp: PathBuf,
+}
+
+pub(crate) struct CoolingDevice {
+    cur_state: PathBuf,
+    max_state: u64,
+    /// `policy` files of the zones bound to this device
+    zone_policies: Vec<PathBuf>,
+    /// Policies to restore, when the governors were stopped
+    policies_cached: Vec<(PathBuf, String)>,
+}
+
+pub(crate) enum ThermalItem {
+    Zone(ThermalZone),
+    CoolingDevice(CoolingDevice),
+}
+
+impl Drop for CoolingDevice {
+    fn drop(&mut self) {
+        if let Err(e) = self.restore_policies() {
+            error!(
+                "can't give a cooling device back to the kernel when quitting: {}",
+                e
+            )
+        }
+    }
+}
+
+fn read_content<'a>(sweep: &'a mut Sweep, path: &Path) -> Result<&'a str> {
+    sweep
+        .read(path)
+        .map_err(|e| ThermalError::Io(format!("can't read {}", path.display()), e))
+}
+
+fn read_value(sweep: &mut Sweep, path: &Path) -> Result<u64> {
+    let content = read_content(sweep, path)?;
+
+    content
+        .trim()
+        .parse::<u64>()
+        .map_err(|e| ThermalError::InvalidData(path.display().to_string(), e.to_string()))
+}
+
+fn write_string(path: &Path, value: &str) -> Result<()> {
+    fs::write(path, value)
+        .map_err(|e| ThermalError::Io(format!("can't write {} to {}", value, path.display()), e))
+}
+
+impl ThermalZone {
+    pub(crate) fn get_value(&self, sweep: &mut Sweep) -> Result<Value> {
+        let content = read_content(sweep, &self.temp)?;
+
+        // millidegree Celsius, can be negative
+        let value = content.trim().parse::<i64>().map_err(|e| {
+            ThermalError::InvalidData(self.temp.display().to_string(), e.to_string())
+        })?;
+
+        Ok(value as Value / 1000.0)
+    }
+}
+
+impl CoolingDevice {
+    pub(crate) fn get_value(&self, sweep: &mut Sweep) -> Result<Value> {
+        let state = read_value(sweep, &self.cur_state)?;
+        Ok((state as Value * 100.0 / self.max_state as Value).round())
+    }
+
+    /// 0-100% is mapped onto 0-`max_state`
+    pub(crate) fn set_value(&self, value: Value) -> Result<()> {
+        let state = (value.clamp(0.0, 100.0) * self.max_state as Value / 100.0).round() as u64;
+        write_string(&self.cur_state, &state.to_string())
+    }
+
+    /// Manual stops the governors of the bound zones, Auto gives the device back to them.
+    pub(crate) fn set_mode(&mut self, mode: &Mode) -> Result<()> {
+        match mode {
+            Mode::Auto => self.restore_policies(),
+            Mode::Manual => {
+                if !self.policies_cached.is_empty() {
+                    return Ok(());
+                }
+
+                for policy in &self.zone_policies {
+                    let Some(previous) = read_string(policy) else {
+                        continue;
+                    };
+                    if previous == USER_SPACE_POLICY {
+                        continue;
+                    }
+                    write_string(policy, USER_SPACE_POLICY)?;
+                    self.policies_cached.push((policy.clone(), previous));
+                }
+                Ok(())
+            }
+            Mode::Specific(_) => Err(ThermalError::UnsupportedMode(mode.clone())),
+        }
+    }
+
+    fn restore_policies(&mut self) -> Result<()> {
+        for (policy, previous) in std::mem::take(&mut self.policies_cached) {
+            write_string(&policy, &previous)?;
+        }
+        Ok(())
+    }
+}
+
+impl ThermalItem {
+    /// Take the paths of the same item, found by a rescan.
+    /// The policies to restore are kept.
+    pub(crate) fn refresh(&mut self, found: ThermalItem) {
+        match (self, found) {
+            (ThermalItem::CoolingDevice(device), ThermalItem::CoolingDevice(mut found)) => {
+                std::mem::swap(&mut device.cur_state, &mut found.cur_state);
+                std::mem::swap(&mut device.zone_policies, &mut found.zone_policies);
+                device.max_state = found.max_state;
+            }
+            (item, found) => *item = found,
+        }
+    }
+}
+
+/// Entries like `thermal_zone3`, sorted by number.
+fn numbered_dirs(root: &Path, prefix: &str) -> Vec<(u32, PathBuf)> {
+    let Ok(entries) = fs::read_dir(root) else {
+        return Vec::new();
+    };
+
+    let mut dirs = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            let number = file_name.strip_prefix(prefix)?.parse::<u32>().ok()?;
+            Some((number, entry.path()))
+        })
+        .collect::<Vec<_>>();
+
+    dirs.sort_by_key(|(number, _)| *number);
+    dirs
+}
+
+/// The numbering of zones and devices can change between boots, so ids
+/// use their type, and their position among the items of the same type.
+/// Example: `thermal/acpitz/0`
+fn stable_id(kind: &str, item_type: &str, types_seen: &mut Vec<String>) -> String {
+    let position = types_seen.iter().filter(|t| *t == item_type).count();
+    types_seen.push(item_type.to_owned());
+    format!("{}/{}/{}", kind, item_type, position)
+}
+
+/// Add the thermal zones as temps, and the cooling devices as controls.
+/// `push` stores an item and returns its internal index.
+/// A missing root is not an error: the thermal framework can be disabled.
+pub(crate) fn generate_hardware(
+    root: &Path,
+    hardware: &mut Hardware,
+    mut push: impl FnMut(ThermalItem) -> usize,
+) {
+    let zones = numbered_dirs(root, "thermal_zone");
+    let cooling_devices = numbered_dirs(root, "cooling_device");
+
+    // the kernel registers a hwmon device for the zones of a type (acpitz,
+    // x86_pkg_temp, ...), under the first zone of this type: these temps
+    // are already found by the hwmon backend
+    let hwmon_types = zones
+        .iter()
+        .filter(|(_, zone_dir)| !numbered_dirs(zone_dir, "hwmon").is_empty())
+        .filter_map(|(_, zone_dir)| read_string(&zone_dir.join("type")))
+        .collect::<Vec<_>>();
+
+    let mut types_seen = Vec::new();
+
+    for (_, zone_dir) in &zones {
+        let Some(zone_type) = read_string(&zone_dir.join("type")) else {
+            warn!("no type found for {}", zone_dir.display());
+            continue;
+        };
+        if hwmon_types.contains(&zone_type) {
+            debug!("{} is exposed by hwmon", zone_dir.display());
+            continue;
+        }
+        let dir_name = zone_dir.file_name().unwrap_or_default().to_string_lossy();
+
+        let hardware_id = stable_id("thermal", &zone_type, &mut types_seen);
+        let info = format!("path: {}\ntype: {}", zone_dir.display(), zone_type);
+
+        let internal_index = push(ThermalItem::Zone(ThermalZone {
+            temp: zone_dir.join("temp"),
+        }));
+
+        hardware.temps.push(Arc::new(HSensor {
+            name: format!("{} {}", zone_type, dir_name),
+            hardware_id,
+            legacy_ids: Vec::new(),
+            info,
+            internal_index,
+        }));
+    }
+
+    let mut types_seen = Vec::new();
+
+    for (_, device_dir) in &cooling_devices {
+        let Some(device_type) = read_string(&device_dir.join("type")) else {
+            warn!("no type found for {}", device_dir.display());
+            continue;
+        };
+        if !FAN_TYPES.contains(&device_type.as_str()) {
+            debug!("{} is not a fan: {}", device_dir.display(), device_type);
+            continue;
+        }
+        let max_state = match read_value(&mut Sweep::default(), &device_dir.join("max_state")) {
+            Ok(0) => {
+                debug!("{} has only one state", device_dir.display());
+                continue;
+            }
+            Ok(max_state) => max_state,
+            Err(e) => {
+                error!("can't read max state of cooling device: {}", e);
+                continue;
+            }
+        };
+        let dir_name = device_dir.file_name().unwrap_or_default().to_string_lossy();
+
+        // zones have a `cdevN` link for each bound cooling device
+        let device_path = fs::canonicalize(device_dir).ok();
+        let bound_zones = zones
+            .iter()
+            .map(|(_, zone_dir)| {
+                let cdevs = numbered_dirs(zone_dir, "cdev")
+                    .iter()
+                    .map(|(_, cdev)| fs::canonicalize(cdev).ok())
+                    .collect::<Vec<_>>();
+                (zone_dir, cdevs)
+            })
+            .filter(|(_, cdevs)| cdevs.contains(&device_path))
+            .collect::<Vec<_>>();
+
+        // stopping the governor of such a zone would also stop
+        // the throttling done by the other devices
+        if let Some((zone_dir, _)) = bound_zones
+            .iter()
+            .find(|(_, cdevs)| cdevs.iter().any(|cdev| cdev != &device_path))
+        {
+            info!(
+                "{} is not exposed: {} also drives other cooling devices",
+                device_dir.display(),
+                zone_dir.display()
+            );
+            continue;
+        }
+
+        let zone_policies = bound_zones
+            .iter()
+            .map(|(zone_dir, _)| zone_dir.join("policy"))
+            .collect::<Vec<_>>();
+
+        let hardware_id = stable_id("cooling_device", &device_type, &mut types_seen);
+        let info = format!(
+            "path: {}\ntype: {}\nmax state: {}",
+            device_dir.display(),
+            device_type,
+            max_state
+        );
+
+        let internal_index = push(ThermalItem::CoolingDevice(CoolingDevice {
+            cur_state: device_dir.join("cur_state"),
+            max_state,
+            zone_policies,
+            policies_cached: Vec::new(),
+        }));
+
+        hardware.controls.push(Arc::new(HControl {
+            name: format!("{} {}", device_type, dir_name),
+            hardware_id,
+            legacy_ids: Vec::new(),
+            info,
+            internal_index,
+        }));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs,
+        os::unix::fs::symlink,
+        path::{Path, PathBuf},
+    };
+
+    use super::{generate_hardware, ThermalItem};
+    use crate::{hwmon::Sweep, Hardware, Mode};
+
+    /// Create a fake `/sys/class/thermal`, in a unique temp directory.
+    fn fake_thermal(test_name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("fan-control-thermal-{}", test_name));
+        let _ = fs::remove_dir_all(&root);
+
+        let write = |path: &str, content: &str| {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        };
+
+        write("thermal_zone0/type", "acpitz\n");
+        write("thermal_zone0/temp", "47000\n");
+        write("thermal_zone0/policy", "step_wise\n");
+        write("thermal_zone1/type", "acpitz\n");
+        write("thermal_zone1/temp", "-2500\n");
+        write("thermal_zone1/policy", "step_wise\n");
+
+        // exposed by hwmon, under the first zone of this type
+        write("thermal_zone2/type", "x86_pkg_temp\n");
+        write("thermal_zone2/temp", "52000\n");
+        write("thermal_zone2/hwmon3/name", "x86_pkg_temp\n");
+        write("thermal_zone3/type", "x86_pkg_temp\n");
+        write("thermal_zone3/temp", "53000\n");
+        write("thermal_zone4/type", "cpu-thermal\n");
+        write("thermal_zone4/temp", "60000\n");
+        write("thermal_zone4/policy", "step_wise\n");
+
+        write("cooling_device0/type", "Fan\n");
+        write("cooling_device0/max_state", "0\n");
+        write("cooling_device0/cur_state", "0\n");
+        write("cooling_device1/type", "pwm-fan\n");
+        write("cooling_device1/max_state", "4\n");
+        write("cooling_device1/cur_state", "1\n");
+        write("cooling_device2/type", "Processor\n");
+        write("cooling_device2/max_state", "3\n");
+        write("cooling_device2/cur_state", "0\n");
+        write("cooling_device3/type", "Fan\n");
+        write("cooling_device3/max_state", "2\n");
+        write("cooling_device3/cur_state", "0\n");
+
+        let bind = |device: &str, cdev: &str| {
+            symlink(root.join(device), root.join(cdev)).unwrap();
+        };
+        bind("cooling_device1", "thermal_zone0/cdev0");
+        bind("cooling_device2", "thermal_zone4/cdev0");
+        bind("cooling_device3", "thermal_zone4/cdev1");
+
+        root
+    }
+
+    fn read(path: &Path) -> String {
+        fs::read_to_string(path).unwrap().trim().to_owned()
+    }
+
+    #[test]
+    fn test_thermal() {
+        let root = fake_thermal("thermal");
+
+        let mut hardware = Hardware::default();
+        let mut items = Vec::new();
+        generate_hardware(&root, &mut hardware, |item| {
+            items.push(item);
+            items.len() - 1
+        });
+
+        // x86_pkg_temp zones are already exposed by hwmon
+        assert_eq!(hardware.temps.len(), 3);
+        assert_eq!(hardware.temps[0].hardware_id, "thermal/acpitz/0");
+        assert_eq!(hardware.temps[1].hardware_id, "thermal/acpitz/1");
+        assert_eq!(hardware.temps[2].hardware_id, "thermal/cpu-thermal/0");
+        // a device with only one state can't be controlled, Processor is not a fan,
+        // and the second Fan shares its zone with Processor
+        assert_eq!(hardware.controls.len(), 1);
+        assert_eq!(hardware.controls[0].hardware_id, "cooling_device/pwm-fan/0");
+
+        let ThermalItem::Zone(zone) = &items[hardware.temps[1].internal_index] else {
+            panic!("not a zone");
+        };
+        assert_eq!(zone.get_value(&mut Sweep::default()).unwrap(), -2.5);
+
+        let ThermalItem::CoolingDevice(device) = &mut items[hardware.controls[0].internal_index]
+        else {
+            panic!("not a cooling device");
+        };
+        assert_eq!(device.get_value(&mut Sweep::default()).unwrap(), 25.0);
+
+        device.set_mode(&Mode::Manual).unwrap();
+        assert_eq!(read(&root.join("thermal_zone0/policy")), "user_space");
+        // not bound to this device
+        assert_eq!(read(&root.join("thermal_zone1/policy")), "step_wise");
+        assert_eq!(read(&root.join("thermal_zone4/policy")), "step_wise");
+
+        device.set_value(60.0).unwrap();
+        assert_eq!(read(&root.join("cooling_device1/cur_state")), "2");
+        device.set_value(100.0).unwrap();
+        assert_eq!(read(&root.join("cooling_device1/cur_state")), "4");
+
+        device.set_mode(&Mode::Auto).unwrap();
+        assert_eq!(read(&root.join("thermal_zone0/policy")), "step_wise");
+
+        assert!(device.set_mode(&Mode::Specific(2)).is_err());
+
+        // the governor is restored when quitting
+        device.set_mode(&Mode::Manual).unwrap();
+        drop(items);
+        assert_eq!(read(&root.join("thermal_zone0/policy")), "step_wise");
+    }
+
+    #[test]
+    fn test_missing_root() {
+        let mut hardware = Hardware::default();
+        generate_hardware(Path::new("/this/path/does/not/exist"), &mut hardware, |_| 0);
+        assert!(hardware.temps.is_empty());
+        assert!(hardware.controls.is_empty());
+    }
+}
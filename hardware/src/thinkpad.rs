@@ -0,0 +1,342 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use thiserror::Error;
+
+use crate::{hwmon::Sweep, HControl, HSensor, Hardware, Mode, Value};
+
+// ThinkPads only allow fan control through the thinkpad_acpi procfs file,
+// when the module is loaded with `fan_control=1`.
+// https://www.kernel.org/doc/html/latest/admin-guide/laptops/thinkpad-acpi.html#fan-control-and-monitoring-fan-speed-fan-enable-disable
+
+pub static DEFAULT_THINKPAD_FAN_PATH: &str = "/proc/acpi/ibm/fan";
+
+static MAX_LEVEL: u32 = 7;
+
+/// End of the id of the pwm exposed by the hwmon chip of thinkpad_acpi
+static HWMON_PWM: &str = "/thinkpad/pwm1";
+
+/// The firmware takes the fan back if no command is received during this delay.
+/// The watchdog is re-armed at each update of the bridge, so it must stay
+/// above the update delay.
+static WATCHDOG_TIMEOUT: u32 = 30;
+
+#[derive(Error, Debug)]
+pub enum ThinkpadError {
+    #[error("{0}: {1}")]
+    Io(String, io::Error),
+    #[error("Invalid data in {0}: {1}")]
+    InvalidData(String, String),
+    #[error("The fan is controlled by the firmware")]
+    AutoLevel,
+    #[error("Mode {0} is not supported by the ThinkPad fan")]
+    UnsupportedMode(Mode),
+}
+
+type Result<T> = std::result::Result<T, ThinkpadError>;
+
+pub(crate) struct FanSpeed {
+    path: PathBuf,
+}
+
+pub(crate) struct FanLevel {
+    path: PathBuf,
+    // only give back the fan we took
+    mode_changed: bool,
+}
+
+pub(crate) enum ThinkpadItem {
+    Speed(FanSpeed),
+    Level(FanLevel),
+}
+
+impl Drop for FanLevel {
+    fn drop(&mut self) {
+        if !self.mode_changed {
+            return;
+        }
+        if let Err(e) = self.set_mode(&Mode::Auto) {
+            error!("can't set auto to the ThinkPad fan when quitting: {}", e)
+        }
+    }
+}
+
+/// Value of a line like `speed:  2560`.
+/// The speed and the level are in the same file, which the sweep reads once.
+fn read_field(sweep: &mut Sweep, path: &Path, field: &str) -> Result<String> {
+    let content = sweep
+        .read(path)
+        .map_err(|e| ThinkpadError::Io(format!("can't read {}", path.display()), e))?;
+
+    content
+        .lines()
+        .find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            (key.trim() == field).then(|| value.trim().to_owned())
+        })
+        .ok_or_else(|| ThinkpadError::InvalidData(path.display().to_string(), field.to_owned()))
+}
+
+/// One command per write, like `level 3`
+fn write_command(path: &Path, command: &str) -> Result<()> {
+    fs::write(path, command)
+        .map_err(|e| ThinkpadError::Io(format!("can't write {} to {}", command, path.display()), e))
+}
+
+impl FanSpeed {
+    pub(crate) fn get_value(&self, sweep: &mut Sweep) -> Result<Value> {
+        let speed = read_field(sweep, &self.path, "speed")?;
+
+        speed
+            .parse::<u32>()
+            .map(Value::from)
+            .map_err(|e| ThinkpadError::InvalidData(self.path.display().to_string(), e.to_string()))
+    }
+}
+
+impl FanLevel {
+    pub(crate) fn get_value(&self, sweep: &mut Sweep) -> Result<Value> {
+        let level = read_field(sweep, &self.path, "level")?;
+
+        match level.as_str() {
+            "auto" => Err(ThinkpadError::AutoLevel),
+            "full-speed" | "disengaged" => Ok(100.0),
+            level => {
+                let level = level.parse::<u32>().map_err(|e| {
+                    ThinkpadError::InvalidData(self.path.display().to_string(), e.to_string())
+                })?;
+                Ok((Value::from(level) * 100.0 / Value::from(MAX_LEVEL)).round())
+            }
+        }
+    }
+
+    pub(crate) fn get_mode(&self) -> Result<Mode> {
+        match read_field(&mut Sweep::default(), &self.path, "level")?.as_str() {
+            "auto" => Ok(Mode::Auto),
+            _ => Ok(Mode::Manual),
+        }
+    }
+
+    /// 0-100% is mapped onto the levels 0-7. `full-speed` is never used,
+    /// because the firmware doesn't regulate the fan anymore.
+    pub(crate) fn set_value(&self, value: Value) -> Result<()> {
+        let level = (value.clamp(0.0, 100.0) * Value::from(MAX_LEVEL) / 100.0).round() as u32;
+        write_command(&self.path, &format!("level {}", level))
+    }
+
+    /// Re-arm the watchdog. `set_value` is not called when the value
+    /// doesn't change, so it can't be relied on.
+    pub(crate) fn keep_alive(&self) -> Result<()> {
+        if !self.mode_changed {
+            return Ok(());
+        }
+        write_command(&self.path, &format!("watchdog {}", WATCHDOG_TIMEOUT))
+    }
+
+    pub(crate) fn set_mode(&mut self, mode: &Mode) -> Result<()> {
+        match mode {
+            Mode::Auto => {
+                write_command(&self.path, "level auto")?;
+                write_command(&self.path, "watchdog 0")?;
+                self.mode_changed = false;
+            }
+            Mode::Manual => {
+                write_command(&self.path, &format!("watchdog {}", WATCHDOG_TIMEOUT))?;
+                self.mode_changed = true;
+            }
+            Mode::Specific(_) => return Err(ThinkpadError::UnsupportedMode(mode.clone())),
+        }
+        Ok(())
+    }
+}
+
+/// Add the fan speed, and the fan level as a control when fan control is enabled.
+/// `push` stores an item and returns its internal index.
+/// A missing file is not an error: this is not a ThinkPad.
+/// Must be called after the hwmon chips were added: thinkpad_acpi also registers
+/// a `thinkpad` chip with the same fan, which is kept instead.
+pub(crate) fn generate_hardware(
+    path: &Path,
+    hardware: &mut Hardware,
+    mut push: impl FnMut(ThinkpadItem) -> usize,
+) {
+    let Ok(content) = fs::read_to_string(path) else {
+        return;
+    };
+
+    if hardware
+        .controls
+        .iter()
+        .any(|control| control.hardware_id.ends_with(HWMON_PWM))
+    {
+        debug!("the ThinkPad fan is already exposed by hwmon");
+        return;
+    }
+
+    let info = format!("path: {}", path.display());
+
+    let internal_index = push(ThinkpadItem::Speed(FanSpeed {
+        path: path.to_path_buf(),
+    }));
+    hardware.fans.push(Arc::new(HSensor {
+        name: "ThinkPad fan".to_owned(),
+        hardware_id: "thinkpad_acpi/fan/speed".to_owned(),
+        legacy_ids: Vec::new(),
+        info: info.clone(),
+        internal_index,
+    }));
+
+    // commands are only listed with `fan_control=1`
+    let controllable = content.lines().any(|line| {
+        line.split_once(':').is_some_and(|(key, value)| {
+            key.trim() == "commands" && value.trim().starts_with("level")
+        })
+    });
+    if !controllable {
+        info!(
+            "{} is read only, thinkpad_acpi must be loaded with fan_control=1",
+            path.display()
+        );
+        return;
+    }
+
+    let internal_index = push(ThinkpadItem::Level(FanLevel {
+        path: path.to_path_buf(),
+        mode_changed: false,
+    }));
+    hardware.controls.push(Arc::new(HControl {
+        name: "ThinkPad fan".to_owned(),
+        hardware_id: "thinkpad_acpi/fan/level".to_owned(),
+        legacy_ids: Vec::new(),
+        info,
+        internal_index,
+    }));
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+        sync::Arc,
+    };
+
+    use super::{generate_hardware, ThinkpadItem};
+    use crate::{hwmon::Sweep, HControl, Hardware, Mode};
+
+    static FAN: &str = "status:\t\tenabled
+speed:\t\t2560
+level:\t\t3
+commands:\tlevel <level> (<level> is 0-7, auto, disengaged, full-speed)
+commands:\tenable, disable
+commands:\twatchdog <timeout> (<timeout> is 0 (off), 1-120 (seconds))
+";
+
+    /// Create a fake `/proc/acpi/ibm/fan`. Unlike the real one, it is replaced by
+    /// the last command written.
+    fn fake_fan(test_name: &str, content: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("fan-control-thinkpad-{}", test_name));
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn generate(path: &Path) -> (Hardware, Vec<ThinkpadItem>) {
+        let mut hardware = Hardware::default();
+        let mut items = Vec::new();
+        generate_hardware(path, &mut hardware, |item| {
+            items.push(item);
+            items.len() - 1
+        });
+        (hardware, items)
+    }
+
+    #[test]
+    fn test_thinkpad() {
+        let path = fake_fan("thinkpad", FAN);
+        let (hardware, mut items) = generate(&path);
+
+        assert_eq!(hardware.fans.len(), 1);
+        assert_eq!(hardware.controls.len(), 1);
+        assert_eq!(hardware.controls[0].hardware_id, "thinkpad_acpi/fan/level");
+
+        let ThinkpadItem::Speed(speed) = &items[hardware.fans[0].internal_index] else {
+            panic!("not the speed");
+        };
+        assert_eq!(speed.get_value(&mut Sweep::default()).unwrap(), 2560.0);
+
+        let ThinkpadItem::Level(level) = &mut items[hardware.controls[0].internal_index] else {
+            panic!("not the level");
+        };
+        assert_eq!(level.get_value(&mut Sweep::default()).unwrap(), 43.0);
+
+        // the file is read once by a sweep
+        let mut sweep = Sweep::default();
+        assert_eq!(level.get_value(&mut sweep).unwrap(), 43.0);
+        fs::write(&path, FAN.replace("level:\t\t3", "level:\t\t7")).unwrap();
+        assert_eq!(level.get_value(&mut sweep).unwrap(), 43.0);
+        assert_eq!(level.get_value(&mut Sweep::default()).unwrap(), 100.0);
+        fs::write(&path, FAN).unwrap();
+
+        level.keep_alive().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), FAN);
+
+        level.set_mode(&Mode::Manual).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "watchdog 30");
+
+        fs::write(&path, FAN).unwrap();
+        level.keep_alive().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "watchdog 30");
+
+        level.set_value(100.0).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "level 7");
+        level.set_value(50.0).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "level 4");
+
+        assert!(level.set_mode(&Mode::Specific(1)).is_err());
+
+        fs::write(&path, FAN.replace("level:\t\t3", "level:\t\tauto")).unwrap();
+        assert!(level.get_value(&mut Sweep::default()).is_err());
+
+        // the firmware takes the fan back when quitting
+        drop(items);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "watchdog 0");
+    }
+
+    #[test]
+    fn test_read_only() {
+        let content = FAN
+            .lines()
+            .filter(|line| !line.starts_with("commands"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let path = fake_fan("read-only", &content);
+        let (hardware, _) = generate(&path);
+
+        assert_eq!(hardware.fans.len(), 1);
+        assert!(hardware.controls.is_empty());
+
+        let (hardware, _) = generate(Path::new("/this/path/does/not/exist"));
+        assert!(hardware.fans.is_empty());
+    }
+
+    #[test]
+    fn test_hwmon_duplicate() {
+        let path = fake_fan("hwmon-duplicate", FAN);
+
+        let mut hardware = Hardware::default();
+        hardware.controls.push(Arc::new(HControl {
+            name: "fan1 thinkpad-isa-0000".into(),
+            hardware_id: "platform/thinkpad_hwmon/thinkpad/pwm1".into(),
+            legacy_ids: Vec::new(),
+            info: String::new(),
+            internal_index: 0,
+        }));
+        generate_hardware(&path, &mut hardware, |_| 0);
+
+        assert!(hardware.fans.is_empty());
+        assert_eq!(hardware.controls.len(), 1);
+    }
+}
@@ -2,7 +2,7 @@ use std::{
     io::{self, BufRead, BufReader, Read, Write},
     net::TcpStream,
     process::{self},
-    rc::Rc,
+    sync::Arc,
     thread,
     time::Duration,
 };
@@ -10,7 +10,7 @@ use std::{
 use serde::Deserialize;
 use thiserror::Error;
 
-use crate::{HControl, HSensor, Hardware, HardwareBridge, Mode, Value};
+use crate::{HControl, HSensor, Hardware, HardwareBridge, HardwareValues, Mode, Value};
 
 use self::packet::{command::Command, i32::I32, Packet};
 
@@ -162,21 +162,24 @@ fn read_hardware(stream: &TcpStream) -> Result<Hardware> {
 
     for base_hardware in base_hardware_list {
         match base_hardware.hardware_type {
-            HardwareType::Control => hardware.controls.push(Rc::new(HControl {
+            HardwareType::Control => hardware.controls.push(Arc::new(HControl {
                 name: base_hardware.name,
                 hardware_id: base_hardware.id,
+                legacy_ids: Vec::new(),
                 info: String::new(),
                 internal_index: base_hardware.index,
             })),
-            HardwareType::Fan => hardware.fans.push(Rc::new(HSensor {
+            HardwareType::Fan => hardware.fans.push(Arc::new(HSensor {
                 name: base_hardware.name,
                 hardware_id: base_hardware.id,
+                legacy_ids: Vec::new(),
                 info: String::new(),
                 internal_index: base_hardware.index,
             })),
-            HardwareType::Temp => hardware.temps.push(Rc::new(HSensor {
+            HardwareType::Temp => hardware.temps.push(Arc::new(HSensor {
                 name: base_hardware.name,
                 hardware_id: base_hardware.id,
+                legacy_ids: Vec::new(),
                 info: String::new(),
                 internal_index: base_hardware.index,
             })),
@@ -203,6 +206,7 @@ mod packet {
             GetValue = 3,
             Shutdown = 4,
             Update = 5,
+            GetValues = 6,
         }
 
         impl From<Command> for Packet {
@@ -266,6 +270,25 @@ impl WindowsBridge {
         Ok(buf.into())
     }
 
+    /// Send the command, the number of indexes and the indexes in one write,
+    /// then read one value per index.
+    fn read_values(&mut self, indexes: &[usize]) -> Result<Vec<Value>> {
+        let mut request = Vec::with_capacity((indexes.len() + 2) * 4);
+        request.extend(Packet::from(Command::GetValues).0);
+        request.extend(Packet::from(I32::from(indexes.len())).0);
+        for index in indexes {
+            request.extend(Packet::from(I32::from(*index)).0);
+        }
+        if let Err(e) = self.stream.write_all(&request) {
+            return Err(WindowsError::Io("can't send packet".into(), e));
+        }
+
+        indexes
+            .iter()
+            .map(|_| self.read::<I32>().map(|value| value.0.into()))
+            .collect()
+    }
+
     fn close_and_wait_server(&mut self) -> Result<()> {
         self.send(Command::Shutdown)?;
 
@@ -319,7 +342,7 @@ impl HardwareBridge for WindowsBridge {
         self.send(I32::from(sensor.internal_index))?;
 
         let value = self.read::<I32>()?;
-        Ok(value.0)
+        Ok(value.0.into())
     }
 
     fn get_control_value(&mut self, control: &HControl) -> crate::Result<Value> {
@@ -327,13 +350,63 @@ impl HardwareBridge for WindowsBridge {
         self.send(I32::from(control.internal_index))?;
 
         let value = self.read::<I32>()?;
-        Ok(value.0)
+        Ok(value.0.into())
+    }
+
+    /// One round trip to the server for all the values.
+    fn get_values(
+        &mut self,
+        sensors: &[Arc<HSensor>],
+        controls: &[Arc<HControl>],
+    ) -> HardwareValues {
+        let indexes = sensors
+            .iter()
+            .map(|sensor| sensor.internal_index)
+            .chain(controls.iter().map(|control| control.internal_index))
+            .collect::<Vec<_>>();
+
+        let mut values = HardwareValues::default();
+
+        match self.read_values(&indexes) {
+            Ok(read) => {
+                let mut read = read.into_iter();
+                for (sensor, value) in sensors.iter().zip(&mut read) {
+                    values.sensors.insert(sensor.hardware_id.clone(), Ok(value));
+                }
+                for (control, value) in controls.iter().zip(&mut read) {
+                    values
+                        .controls
+                        .insert(control.hardware_id.clone(), Ok(value));
+                }
+            }
+            Err(e) => {
+                // the error can't be cloned
+                let error = || {
+                    crate::HardwareError::from(WindowsError::Io(
+                        "can't read values".into(),
+                        io::Error::other(e.to_string()),
+                    ))
+                };
+                for sensor in sensors {
+                    values
+                        .sensors
+                        .insert(sensor.hardware_id.clone(), Err(error()));
+                }
+                for control in controls {
+                    values
+                        .controls
+                        .insert(control.hardware_id.clone(), Err(error()));
+                }
+            }
+        }
+
+        values
     }
 
     fn set_value(&mut self, control: &HControl, value: Value) -> crate::Result<()> {
         self.send(Command::SetValue)?;
         self.send(I32::from(control.internal_index))?;
-        self.send(I32(value))?;
+        self.send(I32(value.round() as i32))?;
         Ok(())
     }
 
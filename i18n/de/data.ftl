@@ -2,15 +2,25 @@ none = Keine
 average = Durchschnitt
 max = Max
 min = Min
+moving_average = Gleitender Durchschnitt
+exponential = Exponentiell
+median = Median
+sum = Summe
+weighted = Gewichtet
 
 default_control = Steuerung
 default_fan = Lüfter
 default_temp = Temperatur
+default_command_temp = Befehlstemperatur
+default_file_temp = Dateitemperatur
 default_custom_temp = Benutzerdefinierte Temperatur
+default_filter = Filter
 default_graph = Diagramm
 default_flat = Flach
 default_linear = Linear
 default_target = Ziel
+default_pid = PID
+default_mix = Mischung
 
 system_theme = System
 dark_theme = Dunkel
@@ -7,6 +7,14 @@ theme = Thema
 update_delay = Update-Verzögerung
 update_delay_value = { $value } ms
 temp_selection = Temperaturauswahl
+behavior_selection = Verhaltensauswahl
+command = Befehl
+regex = Regex
+json_pointer = JSON-Pointer
+path = Pfad
+sensor_stats = { $failures } fehlgeschlagene Lesevorgänge, { $rejected } verworfene Werte
+takeovers = { $count } Mal von der Firmware übernommen
+would_set = Würde auf { $value } % setzen
 min_temp = Mindesttemperatur
 min_speed = Mindestgeschwindigkeit
 max_temp = Maximaltemperatur
@@ -15,13 +23,22 @@ idle_temp = Leerlauftemperatur
 idle_speed = Leerlaufdrehzahl
 load_temp = Lasttemperatur
 load_speed = Lastgeschwindigkeit
+setpoint = Sollwert
+kp = Proportionalverstärkung
+ki = Integralverstärkung
+kd = Differentialverstärkung
+period = Zeitraum
+timeout = Zeitlimit
 launch_graph_window = Koordinaten hinzufügen
 
 # Add item description
 add_item = Ein Element hinzufügen
 add_fan = Überwachung eines Lüftersensors
 add_temp = Überwachung eines Temperatursensors
+add_command_temp = Liest eine Temperatur, die von einem Befehl ausgegeben wird (nvidia-smi, smartctl, ...)
+add_file_temp = Liest eine Temperatur aus einer Datei (/sys/class/thermal/..., ...)
 add_custom_temp = Definiere die Logik zwischen Werten (Max, Durchschnitt, ...)
+add_filter = Glätte eine Temperatur über die Zeit (gleitender Durchschnitt, Median, ...)
 add_control = Weist einer bestimmten Hardwarekomponente ein bestimmtes Verhalten zu
 add_flat = Gibt einen festen Wert zurück
 add_linear = Nimm 5 Variablen:
@@ -37,6 +54,8 @@ add_target = Nimm 5 Variablen:
     - einen Sensorwert
     Wenn der Sensor > Auslösetemperatur ist, wird die Auslösegeschwindigkeit
     so lange gesetzt, bis dieser Sensor < Idealtemperatur ist.
+add_pid = Hält einen Sensor auf einem Sollwert, indem die Geschwindigkeit laufend angepasst wird (PID-Regler)
+add_mix = Kombiniere die Geschwindigkeiten mehrerer Verhalten (Max, Summe, ...)
 add_graph = Diagramm
 
 # Config
@@ -44,6 +63,8 @@ config_name = Konfiguration Name
 save_config = Konfiguration speichern/umbenennen
 delete_config = Konfiguration löschen
 create_config = Konfiguration erstellen
+rescan_hardware = Hardware erneut suchen
+hardware_changed = Die Hardware hat sich geändert
 
 # Error
 already_used_error = Dieser Name wird bereits verwendet
@@ -2,15 +2,25 @@ none = None
 average = Average
 max = Max
 min = Min
+moving_average = Moving average
+exponential = Exponential
+median = Median
+sum = Sum
+weighted = Weighted
 
 default_control = Control
 default_fan = Fan
 default_temp = Temp
+default_command_temp = Command temp
+default_file_temp = File temp
 default_custom_temp = Custom temp
+default_filter = Filter
 default_graph = Graph
 default_flat = Flat
 default_linear = Linear
 default_target = Target
+default_pid = PID
+default_mix = Mix
 
 system_theme = System
 dark_theme = Dark
@@ -7,6 +7,14 @@ theme = Theme
 update_delay = Update delay
 update_delay_value = { $value } ms
 temp_selection = Temp selection
+behavior_selection = Behavior selection
+command = Command
+regex = Regex
+json_pointer = JSON pointer
+path = Path
+sensor_stats = { $failures } failed reads, { $rejected } rejected values
+takeovers = Taken back by the firmware { $count } times
+would_set = Would set { $value } %
 min_temp = min temp
 min_speed = min speed
 max_temp = max temp
@@ -15,13 +23,22 @@ idle_temp = idle temp
 idle_speed = idle speed
 load_temp = load temp
 load_speed = load speed
+setpoint = setpoint
+kp = proportional gain
+ki = integral gain
+kd = derivative gain
+period = period
+timeout = timeout
 launch_graph_window = Add coordinates
 
 # Add item description
 add_item = Add an item
 add_fan = Monitor a fan sensor
 add_temp = Monitor a temp sensor
+add_command_temp = Read a temperature printed by a command (nvidia-smi, smartctl, ...)
+add_file_temp = Read a temperature from a file (/sys/class/thermal/..., ...)
 add_custom_temp = Define logic between values (Max, Averrage, ...)
+add_filter = Smooth a temperature over time (moving average, median, ...)
 add_control = Assigns a certain behavior to a certain hardware component
 add_flat = Returns a fixed value
 add_linear = Take 5 variables:
@@ -37,6 +54,8 @@ add_target = Take 5 variables:
     - a sensor value
     If the sensor > trigger temperature, trigger speed is set
     until this sensor is < ideal temperature
+add_pid = Hold a sensor at a setpoint, by adjusting the speed continuously (PID controller)
+add_mix = Combine the speeds of several behaviors (Max, Sum, ...)
 add_graph = Graph
 
 # Config
@@ -44,6 +63,8 @@ config_name = Configuration name
 save_config = Save/rename this configuration
 delete_config = Delete configuration
 create_config = Create configuration
+rescan_hardware = Rescan hardware
+hardware_changed = The hardware changed
 
 # Error
 already_used_error = This name is already being use
@@ -2,15 +2,25 @@ none = Aucun
 average = Moyenne
 max = Max
 min = Min
+moving_average = Moyenne glissante
+exponential = Exponentielle
+median = Médiane
+sum = Somme
+weighted = Pondérée
 
 default_control = Control
 default_fan = Ventilateur
 default_temp = Temp
+default_command_temp = Temp commande
+default_file_temp = Temp fichier
 default_custom_temp = Temp custom
+default_filter = Filtre
 default_graph = Graph
 default_flat = Plat
 default_linear = Lineaire
 default_target = Cible
+default_pid = PID
+default_mix = Mélange
 
 system_theme = Système
 dark_theme = Sombre
@@ -7,6 +7,14 @@ theme = Thème
 update_delay = Délai de mise à jour
 update_delay_value = { $value } ms
 temp_selection = Temp selection
+behavior_selection = Sélection du comportement
+command = Commande
+regex = Regex
+json_pointer = Pointeur JSON
+path = Chemin
+sensor_stats = { $failures } lectures échouées, { $rejected } valeurs rejetées
+takeovers = Reprise par le firmware { $count } fois
+would_set = Serait réglé à { $value } %
 min_temp = min temp
 min_speed = min speed
 max_temp = max temp
@@ -15,13 +23,22 @@ idle_temp = idle temp
 idle_speed = idle speed
 load_temp = load temp
 load_speed = load speed
+setpoint = consigne
+kp = gain proportionnel
+ki = gain intégral
+kd = gain dérivé
+period = période
+timeout = délai maximal
 launch_graph_window = Ajout de coordonnées
 
 # Add item description
 add_item = Ajouter un item
 add_fan = Monitore un ventilateur
 add_temp = Monitore un capteur de temperature
+add_command_temp = Lit une temperature affichée par une commande (nvidia-smi, smartctl, ...)
+add_file_temp = Lit une temperature depuis un fichier (/sys/class/thermal/..., ...)
 add_custom_temp = Defini une logique entre des valeurs (Max, Moyenne, ...)
+add_filter = Lisse une température dans le temps (moyenne glissante, médiane, ...)
 add_control = Applique un certain comportement a un ventilateur
 add_flat = Retourne une valeur fixe
 add_linear = Prend 5 variables :
@@ -37,6 +54,8 @@ add_target = Prend 5 variables :
     - une valeur de capteur
     Si le capteur > température de déclenchement, la vitesse de déclenchement est définie
     jusqu'à ce que ce capteur < température idéale
+add_pid = Maintient un capteur à une consigne, en ajustant la vitesse en continu (régulateur PID)
+add_mix = Combine les vitesses de plusieurs comportements (Max, Somme, ...)
 add_graph = Graphe
 
 # Config
@@ -44,6 +63,8 @@ config_name = Nom de la configuration
 save_config = Enregistrer/renommer cette configuration
 delete_config = Supprimer la configuration
 create_config = Créer une configuration
+rescan_hardware = Rechercher à nouveau le matériel
+hardware_changed = Le matériel a changé
 
 # Error
 already_used_error = Ce nom est déjà utilisé
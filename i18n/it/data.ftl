@@ -2,15 +2,25 @@ none = Nessuno
 average = Media
 max = Max
 min = Min
+moving_average = Media mobile
+exponential = Esponenziale
+median = Mediana
+sum = Somma
+weighted = Ponderata
 
 default_control = Controllo
 default_fan = Ventola
 default_temp = Temp
+default_command_temp = Temp. comando
+default_file_temp = Temp. file
 default_custom_temp = Temp. personalizzata
+default_filter = Filtro
 default_graph = Grafico
 default_flat = Piatto
 default_linear = Lineare
 default_target = Target
+default_pid = PID
+default_mix = Mix
 
 system_theme = Sistema
 dark_theme = Scuro
@@ -7,6 +7,14 @@ theme = Tema
 update_delay = Ritardo di aggiornamento
 update_delay_value = { $value } ms
 temp_selection = Selezione della temperatura
+behavior_selection = Selezione del comportamento
+command = Comando
+regex = Regex
+json_pointer = Puntatore JSON
+path = Percorso
+sensor_stats = { $failures } letture fallite, { $rejected } valori scartati
+takeovers = Ripreso dal firmware { $count } volte
+would_set = Verrebbe impostato a { $value } %
 min_temp = temp min
 min_speed = velocità min
 max_temp = temp max
@@ -15,13 +23,22 @@ idle_temp = idle temp
 idle_speed = idle speed
 load_temp = load temp
 load_speed = load speed
+setpoint = valore target
+kp = guadagno proporzionale
+ki = guadagno integrale
+kd = guadagno derivativo
+period = periodo
+timeout = timeout
 launch_graph_window = Aggiungi coordinate
 
 # Add item description
 add_item = Aggiungi un elemento
 add_fan = Monitorare un sensore della ventola
 add_temp = Monitorare un sensore di temperatura
+add_command_temp = Legge una temperatura stampata da un comando (nvidia-smi, smartctl, ...)
+add_file_temp = Legge una temperatura da un file (/sys/class/thermal/..., ...)
 add_custom_temp = Definire la logica tra i valori (Max, Media, ...)
+add_filter = Smussare una temperatura nel tempo (media mobile, mediana, ...)
 add_control = Assegna un determinato comportamento a un determinato componente hardware
 add_flat = Restituisce un valore fisso
 add_linear = Prendi 5 variabili:
@@ -37,6 +54,8 @@ add_target = Prendi 5 variabili:
     - un valore del sensore
     Se il sensore > temperatura di attivazione, viene impostata la velocità di attivazione
      fino a quando questo sensore < temperatura ideale
+add_pid = Mantiene un sensore a un valore target, regolando la velocità di continuo (controllore PID)
+add_mix = Combinare le velocità di più comportamenti (Max, Somma, ...)
 add_graph = Grafico
 
 # Config
@@ -44,6 +63,8 @@ config_name = Nome della configurazione
 save_config = Salva/rinomina questa configurazione
 delete_config = Elimina configurazione
 create_config = Crea configurazione
+rescan_hardware = Cerca di nuovo l'hardware
+hardware_changed = L'hardware è cambiato
 
 # Error
 already_used_error =  Questo nome è già in uso
@@ -2,15 +2,25 @@ none = 空
 average = 平均值
 max = 最大值
 min = 最小值
+moving_average = 移动平均
+exponential = 指数
+median = 中位数
+sum = 总和
+weighted = 加权
 
 default_control = 控制
 default_fan = 风扇
 default_temp = 温度
+default_command_temp = 命令温度
+default_file_temp = 文件温度
 default_custom_temp = 自定义温度
+default_filter = 滤波器
 default_graph = 图表
 default_flat = 水平
 default_linear = 线性
 default_target = 目标
+default_pid = PID
+default_mix = 混合
 
 system_theme = 系统
 dark_theme = 暗色
@@ -7,6 +7,14 @@ theme = 主题
 update_delay = 更新延迟
 update_delay_value = { $value } ms
 temp_selection = 温度选择
+behavior_selection = 行为选择
+command = 命令
+regex = 正则表达式
+json_pointer = JSON 指针
+path = 路径
+sensor_stats = { $failures } 次读取失败，{ $rejected } 个数值被拒绝
+takeovers = 被固件接管 { $count } 次
+would_set = 将设置为 { $value } %
 min_temp = 最低温度
 min_speed = 最小速度
 max_temp = 最高温度
@@ -15,13 +23,22 @@ idle_temp = 怠速温度
 idle_speed = 怠速速度
 load_temp = 负载温度
 load_speed = 负载速度
+setpoint = 设定值
+kp = 比例增益
+ki = 积分增益
+kd = 微分增益
+period = 周期
+timeout = 超时
 launch_graph_window = 添加坐标
 
 # Add item description
 add_item = 添加项目
 add_fan = 监控风扇传感器
 add_temp = 监控温度传感器
+add_command_temp = 读取命令输出的温度（nvidia-smi、smartctl、 ...）
+add_file_temp = 从文件读取温度（/sys/class/thermal/...、 ...）
 add_custom_temp = 定义值之间的逻辑（最大值、平均值、 ...）
+add_filter = 随时间平滑温度（移动平均、中位数、 ...）
 add_control = 将特定行为分配给特定硬件组件
 add_flat = 返回一个固定值
 add_linear = 取决于5个变量:
@@ -37,6 +54,8 @@ add_target = 取决于5个变量:
     - 一个传感器值
     如果传感器 > 触发温度，会设置触发速度
     直到这个传感器 < 理想温度
+add_pid = 通过持续调整速度，使传感器保持在设定值 (PID 控制器)
+add_mix = 组合多个行为的速度（最大值、总和、 ...）
 add_graph = 图表
 
 # Config
@@ -44,6 +63,8 @@ config_name = 配置名称
 save_config = 保存/重命名此配置
 delete_config = 删除配置
 create_config = 创建配置
+rescan_hardware = 重新扫描硬件
+hardware_changed = 硬件已更改
 
 # Error
 already_used_error = 此名称已被使用
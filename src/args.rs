@@ -59,4 +59,45 @@ pub struct Args {
         help = "Write hardware file in the config dir"
     )]
     pub serialize_hardware: bool,
+
+    #[arg(
+        long = "snapshot",
+        value_hint = ValueHint::FilePath,
+        value_names = ["PATH"],
+        help = "Use the hardware and the values of a hardware file instead of the real hardware"
+    )]
+    pub snapshot: Option<PathBuf>,
+
+    #[arg(
+        long = "calibrate",
+        num_args = 2,
+        value_names = ["CONTROL", "FAN"],
+        help = "Measure the speed of a fan at each duty of a control, and save the result in the config"
+    )]
+    pub calibrate: Option<Vec<String>>,
+
+    #[arg(
+        long = "record",
+        value_hint = ValueHint::FilePath,
+        value_names = ["PATH"],
+        help = "Record all interactions with the hardware in a file"
+    )]
+    pub record: Option<PathBuf>,
+
+    #[arg(
+        long = "replay",
+        value_hint = ValueHint::FilePath,
+        value_names = ["PATH"],
+        conflicts_with = "record",
+        help = "Replay a recording with the current config, and show the writes that differ"
+    )]
+    pub replay: Option<PathBuf>,
+
+    #[arg(
+        long = "dry-run",
+        default_value_t = false,
+        conflicts_with_all = ["calibrate", "replay"],
+        help = "Read the hardware, but only show the values that would be written"
+    )]
+    pub dry_run: bool,
 }
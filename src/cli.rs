@@ -2,14 +2,23 @@
 // no blocking read timeout for now
 
 use std::{
+    collections::HashMap,
     sync::mpsc::{self, RecvTimeoutError, Sender},
     thread::{self},
     time::Duration,
 };
 
 use crossterm::event::{self, Event, KeyCode, KeyEvent};
-use data::{settings::Settings, AppState};
-use hardware::HardwareBridge;
+use data::{
+    app_graph::Nodes,
+    calibration::{self, CalibrationSettings},
+    config::Config,
+    id::Id,
+    node::NodeType,
+    settings::Settings,
+    AppState,
+};
+use hardware::{record::ReplayBridge, HardwareBridge, Value};
 
 pub fn run_cli<H: HardwareBridge>(mut app_state: AppState<H>) {
     let current_config = match &app_state.dir_manager.settings().current_config {
@@ -25,9 +34,16 @@ pub fn run_cli<H: HardwareBridge>(mut app_state: AppState<H>) {
         }
     };
 
+    let can_rescan = app_state.bridge.can_rescan();
     let (tx, rx) = mpsc::channel::<UserAction>();
-    start_listening(tx);
-    display_info(app_state.dir_manager.settings(), current_config);
+    start_listening(tx, can_rescan);
+    display_info(app_state.dir_manager.settings(), current_config, can_rescan);
+    if app_state.dry_run {
+        println!("Dry run: nothing is written to the hardware");
+        println!();
+    }
+
+    let mut intended_values = HashMap::new();
 
     loop {
         if let Err(e) = app_state.bridge.update() {
@@ -43,6 +59,14 @@ pub fn run_cli<H: HardwareBridge>(mut app_state: AppState<H>) {
             error!("{}", e);
         }
 
+        app_state
+            .update
+            .verify_controls(&mut app_state.app_graph.nodes, &mut app_state.bridge);
+
+        if app_state.dry_run {
+            print_intended_values(&app_state.app_graph.nodes, &mut intended_values);
+        }
+
         let settings_update_delay =
             Duration::from_millis(app_state.dir_manager.settings().update_delay);
         let final_delay = std::cmp::max(settings_update_delay, Duration::from_millis(50));
@@ -53,6 +77,16 @@ pub fn run_cli<H: HardwareBridge>(mut app_state: AppState<H>) {
                     println!("quit requested");
                     break;
                 }
+                UserAction::Rescan => match app_state.rescan_hardware() {
+                    Ok(changes) => {
+                        println!(
+                            "Hardware rescanned: {} node(s) bound, {} node(s) unbound",
+                            changes.bound.len(),
+                            changes.unbound.len()
+                        );
+                    }
+                    Err(e) => error!("can't rescan hardware: {}", e),
+                },
             },
 
             Err(RecvTimeoutError::Disconnected) => {
@@ -71,12 +105,127 @@ pub fn run_cli<H: HardwareBridge>(mut app_state: AppState<H>) {
     }
 }
 
+/// Run all cycles of a recording, then compare the writes with the recorded ones.
+pub fn run_replay(mut app_state: AppState<ReplayBridge>) {
+    // the first cycle is what happened before the first update
+    for _ in 1..app_state.bridge.cycles() {
+        if let Err(e) = app_state.bridge.update() {
+            error!("{}", e);
+            break;
+        }
+
+        if let Err(e) = app_state.update.optimized(
+            &mut app_state.app_graph.nodes,
+            &app_state.app_graph.root_nodes,
+            &mut app_state.bridge,
+        ) {
+            error!("{}", e);
+        }
+    }
+
+    let mismatches = app_state.bridge.diff();
+
+    if mismatches.is_empty() {
+        println!("All writes match the recording");
+        return;
+    }
+
+    for mismatch in &mismatches {
+        println!("cycle {}:", mismatch.cycle);
+        println!("  recorded:");
+        for action in &mismatch.recorded {
+            println!("    {}", action);
+        }
+        println!("  replayed:");
+        for action in &mismatch.replayed {
+            println!("    {}", action);
+        }
+    }
+    println!("{} cycle(s) differ from the recording", mismatches.len());
+}
+
+/// Calibrate a control, with the fan it drives, then save the result in the current config.
+pub fn run_calibration<H: HardwareBridge>(
+    mut app_state: AppState<H>,
+    control_name: &str,
+    fan_name: &str,
+) {
+    let Some(current_config) = app_state.dir_manager.settings().current_config.clone() else {
+        println!("There is no active configuration defined");
+        return;
+    };
+
+    let fan_h = app_state
+        .app_graph
+        .nodes
+        .values()
+        .find_map(|node| match &node.node_type {
+            NodeType::Fan(fan) if fan.name == fan_name => fan.fan_h.clone(),
+            _ => None,
+        });
+
+    let Some(fan_h) = fan_h else {
+        println!("Fan {} was not found, or has no hardware", fan_name);
+        return;
+    };
+
+    let control =
+        app_state
+            .app_graph
+            .nodes
+            .values_mut()
+            .find_map(|node| match &mut node.node_type {
+                NodeType::Control(control) if control.name == control_name => Some(control),
+                _ => None,
+            });
+
+    let Some(control) = control else {
+        println!("Control {} was not found", control_name);
+        return;
+    };
+
+    println!("Calibrating {}, this can take a few minutes", control_name);
+
+    let res = calibration::calibrate(
+        control,
+        &fan_h,
+        &mut app_state.bridge,
+        &CalibrationSettings::default(),
+        |bridge, delay| {
+            thread::sleep(delay);
+            if let Err(e) = bridge.update() {
+                error!("{}", e);
+            }
+        },
+    );
+
+    match res {
+        Ok(calibration) => {
+            println!("Stall: {}%", calibration.stall);
+            println!("Spin up: {}%", calibration.spin_up);
+            println!("Max speed: {} rpm", calibration.max_rpm());
+            control.set_calibration(calibration);
+
+            let config = Config::from_app_graph(&app_state.app_graph);
+            match app_state.dir_manager.save_config(&current_config, &config) {
+                Ok(_) => println!("Calibration saved in {}", current_config),
+                Err(e) => error!("can't save config: {}", e),
+            }
+        }
+        Err(e) => error!("calibration of {} failed: {}", control_name, e),
+    }
+
+    if let Err(e) = app_state.bridge.shutdown() {
+        error!("shutdown hardware: {}", e);
+    }
+}
+
 enum UserAction {
     Quit,
+    Rescan,
 }
 
-#[allow(clippy::single_match)]
-fn start_listening(tx: Sender<UserAction>) {
+fn start_listening(tx: Sender<UserAction>, can_rescan: bool) {
     let _handle = thread::spawn(move || loop {
         match event::read() {
             Ok(event) => match event {
@@ -89,6 +238,15 @@ fn start_listening(tx: Sender<UserAction>) {
                         break;
                     }
                 }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('r'),
+                    ..
+                }) if can_rescan => {
+                    if let Err(e) = tx.send(UserAction::Rescan) {
+                        error!("can't send user action to app: {e}");
+                        break;
+                    }
+                }
                 _ => {}
             },
             Err(e) => {
@@ -98,12 +256,30 @@ fn start_listening(tx: Sender<UserAction>) {
     });
 }
 
-fn display_info(settings: &Settings, current_config: &String) {
+/// Print the values that would be written, when they change.
+fn print_intended_values(nodes: &Nodes, printed: &mut HashMap<Id, Value>) {
+    for node in nodes.values() {
+        let NodeType::Control(control) = &node.node_type else {
+            continue;
+        };
+        let Some(output) = control.output_set else {
+            continue;
+        };
+        if printed.insert(node.id, output) != Some(output) {
+            println!("would set {} to {}%", control.name, output.round());
+        }
+    }
+}
+
+fn display_info(settings: &Settings, current_config: &String, can_rescan: bool) {
     println!();
     println!("Update delay: {} ms", settings.update_delay);
     println!("Active configuration: {}", current_config);
     println!();
     println!("Available options:");
     println!("quit: q");
+    if can_rescan {
+        println!("rescan hardware: r");
+    }
     println!();
 }
@@ -5,9 +5,11 @@ use std::time::Duration;
 use crate::args::Args;
 use crate::integrated_test::init_test_logging;
 use data::app_graph::AppGraph;
+use data::calibration::{calibrate, CalibrationSettings};
 use data::dir_manager::DirManager;
+use data::node::NodeType;
 use data::{update::Update, AppState};
-use hardware::HardwareBridge;
+use hardware::{HardwareBridge, Mode};
 
 #[test]
 fn test_config() {
@@ -27,17 +29,18 @@ fn test_config() {
 
     let app_graph = AppGraph::from_config(config, bridge.hardware());
 
-    let app_state = AppState {
+    let mut app_state = AppState {
         dir_manager,
         app_graph,
         update: Update::new(),
         bridge,
+        dry_run: false,
     };
 
-    run(app_state)
+    run(&mut app_state)
 }
 
-fn run<H: HardwareBridge>(mut app_state: AppState<H>) {
+fn run<H: HardwareBridge>(app_state: &mut AppState<H>) {
     for _ in 0..20 {
         if let Err(e) = app_state.bridge.update() {
             error!("{}", e);
@@ -56,3 +59,218 @@ fn run<H: HardwareBridge>(mut app_state: AppState<H>) {
         thread::sleep(Duration::from_millis(50));
     }
 }
+
+#[test]
+fn test_convergence() {
+    init_test_logging();
+
+    let args = Args {
+        config_dir_path: Some(PathBuf::from("./configs-examples")),
+        config_name: Some("fake".into()),
+        ..Default::default()
+    };
+
+    let dir_manager = DirManager::new(&args.config_dir_path, &args.config_name);
+
+    let topology =
+        hardware::fake_hardware::Topology::from_file("./hardware/fake_topology.toml").unwrap();
+    let mut bridge =
+        hardware::fake_hardware::FakeHardwareBridge::from_topology(topology.clone()).unwrap();
+
+    let config = dir_manager.get_config().unwrap();
+
+    let mut app_graph = AppGraph::from_config(config, bridge.hardware());
+    let mut update = Update::new();
+
+    let temps = bridge.hardware().temps.clone();
+
+    let read_temps = |bridge: &mut hardware::fake_hardware::FakeHardwareBridge| {
+        temps
+            .iter()
+            .map(|temp| bridge.get_sensor_value(temp).unwrap())
+            .collect::<Vec<_>>()
+    };
+
+    // one simulated hour, updated every second
+    for _ in 0..3600 {
+        bridge.step(1.0);
+        update
+            .optimized(&mut app_graph.nodes, &app_graph.root_nodes, &mut bridge)
+            .unwrap();
+    }
+
+    let settled = read_temps(&mut bridge);
+
+    for _ in 0..600 {
+        bridge.step(1.0);
+        update
+            .optimized(&mut app_graph.nodes, &app_graph.root_nodes, &mut bridge)
+            .unwrap();
+    }
+
+    let last = read_temps(&mut bridge);
+
+    let target = equilibrium(&topology);
+
+    for (((model, settled), last), target) in
+        topology.temps.iter().zip(settled).zip(last).zip(target)
+    {
+        debug!("{}: {} -> {}, target {}", model.id, settled, last, target);
+        assert!(
+            (settled - last).abs() <= 1.0,
+            "{} did not converge",
+            model.id
+        );
+        assert!(
+            (last - target).abs() <= 0.1,
+            "{} ended at {} instead of {}",
+            model.id,
+            last,
+            target
+        );
+
+        let uncooled = model.ambient + model.heat / model.passive_cooling;
+        assert!(last > model.ambient);
+        assert!(last < uncooled);
+    }
+}
+
+/// Temps where the heat of the fake topology equals its cooling, with
+/// control1 following linear1 of the fake config, and control2 inactive,
+/// so following its firmware curve.
+fn equilibrium(topology: &hardware::fake_hardware::Topology) -> Vec<f64> {
+    let duties = |temps: &[f64]| {
+        let average = (temps[0] + temps[1]) / 2.0;
+        let linear1 = 10.0 + (average - 10.0) * (100.0 - 10.0) / (70.0 - 10.0);
+        let auto_curve = 20.0 + (temps[1] - 30.0) * (100.0 - 20.0) / (70.0 - 30.0);
+        [
+            linear1.clamp(10.0, 100.0) / 100.0,
+            auto_curve.clamp(20.0, 100.0) / 100.0,
+        ]
+    };
+
+    let mut temps = topology
+        .temps
+        .iter()
+        .map(|model| model.ambient)
+        .collect::<Vec<_>>();
+
+    for _ in 0..1000 {
+        let duties = duties(&temps);
+        for (index, model) in topology.temps.iter().enumerate() {
+            let mut cooling = model.passive_cooling;
+            for (control, duty) in topology.controls.iter().zip(duties) {
+                for c in &control.cooling {
+                    if c.temp == model.id {
+                        cooling += c.factor * duty;
+                    }
+                }
+            }
+            // damped, so the iteration doesn't oscillate
+            temps[index] = (temps[index] + model.ambient + model.heat / cooling) / 2.0;
+        }
+    }
+
+    temps
+}
+
+#[test]
+fn test_replay() {
+    init_test_logging();
+
+    let args = Args {
+        config_dir_path: Some(PathBuf::from("./configs-examples")),
+        config_name: Some("fake".into()),
+        ..Default::default()
+    };
+
+    let path = std::env::temp_dir().join("fan-control-replay-test.jsonl");
+
+    let dir_manager = DirManager::new(&args.config_dir_path, &args.config_name);
+    let bridge = hardware::fake_hardware::FakeHardwareBridge::new().unwrap();
+    let bridge = hardware::record::RecordBridge::from_bridge(bridge, &path).unwrap();
+    let config = dir_manager.get_config().unwrap();
+    let app_graph = AppGraph::from_config(config, bridge.hardware());
+
+    let mut app_state = AppState {
+        dir_manager,
+        app_graph,
+        update: Update::new(),
+        bridge,
+        dry_run: false,
+    };
+    run(&mut app_state);
+    app_state.bridge.shutdown().unwrap();
+
+    let dir_manager = DirManager::new(&args.config_dir_path, &args.config_name);
+    let mut bridge = hardware::record::ReplayBridge::from_file(&path).unwrap();
+    let config = dir_manager.get_config().unwrap();
+    let mut app_graph = AppGraph::from_config(config, bridge.hardware());
+    let mut update = Update::new();
+
+    for _ in 1..bridge.cycles() {
+        bridge.update().unwrap();
+        update
+            .optimized(&mut app_graph.nodes, &app_graph.root_nodes, &mut bridge)
+            .unwrap();
+    }
+
+    assert!(bridge.diff().is_empty());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_calibration() {
+    init_test_logging();
+
+    let args = Args {
+        config_dir_path: Some(PathBuf::from("./configs-examples")),
+        config_name: Some("fake".into()),
+        ..Default::default()
+    };
+
+    let dir_manager = DirManager::new(&args.config_dir_path, &args.config_name);
+
+    let topology =
+        hardware::fake_hardware::Topology::from_file("./hardware/fake_topology.toml").unwrap();
+    let mut bridge = hardware::fake_hardware::FakeHardwareBridge::from_topology(topology).unwrap();
+
+    let config = dir_manager.get_config().unwrap();
+    let mut app_graph = AppGraph::from_config(config, bridge.hardware());
+
+    let fan_h = bridge.hardware().fans[0].clone();
+    let control_h = bridge.hardware().controls[0].clone();
+
+    let control = app_graph
+        .nodes
+        .values_mut()
+        .find_map(|node| match &mut node.node_type {
+            NodeType::Control(control) if control.name == "control1" => Some(control),
+            _ => None,
+        })
+        .unwrap();
+
+    let calibration = calibrate(
+        control,
+        &fan_h,
+        &mut bridge,
+        &CalibrationSettings::default(),
+        |bridge, delay| bridge.step(delay.as_secs_f64()),
+    )
+    .unwrap();
+
+    assert_eq!(calibration.stall, 15);
+    assert_eq!(calibration.spin_up, 30);
+    assert_eq!(calibration.max_rpm(), 2000.0);
+    assert_eq!(calibration.curve.len(), 21);
+
+    // the firmware controls the fan again
+    assert_eq!(control.mode_set, Some(Mode::Auto));
+    bridge.step(1.0);
+    assert!(bridge.get_control_value(&control_h).unwrap() >= 20.0);
+
+    control.set_calibration(calibration);
+    assert_eq!(control.output.min_start, 30);
+    assert_eq!(control.output.min_duty, 15);
+}
@@ -7,7 +7,7 @@ use std::{env, fs};
 use args::Args;
 use clap::Parser;
 use data::{app_graph::AppGraph, dir_manager::DirManager, update::Update, AppState};
-use hardware::{self, HardwareBridge};
+use hardware::{self, dry_run::DryRunBridge, snapshot::HardwareSnapshot, HardwareBridge};
 use log::LevelFilter;
 use thiserror::Error;
 
@@ -92,27 +92,89 @@ fn try_run() -> Result<()> {
 
     let dir_manager = DirManager::new(&args.config_dir_path, &args.config_name);
 
-    let bridge = hardware::new()?;
-    let hardware = bridge.hardware();
+    if let Some(path) = &args.replay {
+        let bridge = hardware::record::ReplayBridge::from_file(path)?;
+        cli::run_replay(app_state(dir_manager, bridge, false));
+        return Ok(());
+    }
 
-    debug!("sensors found: {:?}", hardware);
+    match &args.snapshot {
+        Some(path) => {
+            let bridge = hardware::snapshot::SnapshotBridge::from_file(path)?;
+            start(&args, dir_manager, bridge)
+        }
+        None => start(&args, dir_manager, hardware::new()?),
+    }
+}
 
-    if args.serialize_hardware {
-        dir_manager.serialize_hardware(hardware);
-        return Ok(());
+fn start<H: HardwareBridge + 'static>(
+    args: &Args,
+    dir_manager: DirManager,
+    bridge: H,
+) -> Result<()> {
+    // recorded after the dry run, to compare the writes it would do
+    match args.dry_run {
+        true => record(args, dir_manager, DryRunBridge::from_bridge(bridge)),
+        false => record(args, dir_manager, bridge),
     }
+}
+
+fn record<H: HardwareBridge + 'static>(
+    args: &Args,
+    dir_manager: DirManager,
+    bridge: H,
+) -> Result<()> {
+    match &args.record {
+        Some(path) => {
+            let bridge = hardware::record::RecordBridge::from_bridge(bridge, path)?;
+            run(args, dir_manager, bridge)
+        }
+        None => run(args, dir_manager, bridge),
+    }
+}
+
+fn app_state<H: HardwareBridge>(dir_manager: DirManager, bridge: H, dry_run: bool) -> AppState<H> {
+    let hardware = bridge.hardware();
 
     let app_graph = match dir_manager.get_config() {
         Some(config) => AppGraph::from_config(config, hardware),
         None => AppGraph::default(hardware),
     };
 
-    let app_state = AppState {
+    if !app_graph.rebound.is_empty() {
+        warn!(
+            "{} node(s) were bound to their hardware with a legacy id. Save the configuration to use the new ids.",
+            app_graph.rebound.len()
+        );
+    }
+
+    AppState {
         dir_manager,
         bridge,
         app_graph,
         update: Update::new(),
-    };
+        dry_run,
+    }
+}
+
+fn run<H: HardwareBridge + 'static>(
+    args: &Args,
+    dir_manager: DirManager,
+    mut bridge: H,
+) -> Result<()> {
+    debug!("sensors found: {:?}", bridge.hardware());
+
+    if args.serialize_hardware {
+        dir_manager.serialize_hardware(&HardwareSnapshot::from_bridge(&mut bridge));
+        return Ok(());
+    }
+
+    let app_state = app_state(dir_manager, bridge, args.dry_run);
+
+    if let Some([control, fan]) = args.calibrate.as_deref() {
+        cli::run_calibration(app_state, control, fan);
+        return Ok(());
+    }
 
     #[cfg(not(feature = "ui"))]
     cli::run_cli(app_state);
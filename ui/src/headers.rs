@@ -140,9 +140,19 @@ fn config_choice_line<'a>(optional_name: Option<String>) -> Element<'a, AppMsg>
         .into()
 }
 
-pub fn header_end<'a>() -> Vec<Element<'a, AppMsg>> {
+pub fn header_end<'a>(can_rescan: bool) -> Vec<Element<'a, AppMsg>> {
     let mut elems = vec![];
 
+    if can_rescan {
+        let rescan_button = icon_button("refresh/40")
+            .tooltip(fl!("rescan_hardware"))
+            .on_press(AppMsg::RescanHardware)
+            .height(ICON_LENGHT)
+            .width(ICON_LENGHT)
+            .into();
+        elems.push(rescan_button);
+    }
+
     let settings_button = icon_button("settings/40")
         .on_press(AppMsg::Toggle(ToogleMsg::Settings))
         .height(ICON_LENGHT)
@@ -43,11 +43,16 @@ pub fn icon_path_for_node_type(node_type: &NodeTypeLight) -> &'static str {
         NodeTypeLight::Control => "speed/24",
         NodeTypeLight::Fan => "toys_fan/24",
         NodeTypeLight::Temp => "thermometer/24",
+        NodeTypeLight::CommandTemp => "terminal/24",
+        NodeTypeLight::FileTemp => "description/24",
         NodeTypeLight::CustomTemp => "thermostat/24",
+        NodeTypeLight::Filter => "filter_alt/24",
         NodeTypeLight::Graph => "psychology/24",
         NodeTypeLight::Flat => "horizontal_rule/24",
         NodeTypeLight::Linear => "linear/24",
         NodeTypeLight::Target => "my_location/24",
+        NodeTypeLight::Pid => "tune/24",
+        NodeTypeLight::Mix => "merge/24",
     }
 }
 
@@ -27,10 +27,44 @@ impl MyFrom<&str> for Option<u8> {
     }
 }
 
+impl MyFrom<i32> for u64 {
+    fn from(value: i32) -> Self {
+        value as u64
+    }
+}
+
+impl MyFrom<&str> for Option<u64> {
+    fn from(value: &str) -> Self {
+        match value.parse::<u64>() {
+            Ok(value) => Some(value),
+            Err(_) => None,
+        }
+    }
+}
+
+impl MyFrom<i32> for f64 {
+    fn from(value: i32) -> Self {
+        value.into()
+    }
+}
+
+impl MyFrom<&str> for Option<f64> {
+    fn from(value: &str) -> Self {
+        match value.parse::<f64>() {
+            Ok(value) if value.is_finite() => Some(value),
+            _ => None,
+        }
+    }
+}
+
 #[derive(PartialEq, Eq)]
 pub enum InputLineUnit {
     Celcius,
     Porcentage,
+    Second,
+    Millisecond,
+    /// No unit, like the gains of a PID
+    None,
 }
 
 pub fn input_line<'a, V, F>(
@@ -92,6 +126,9 @@ where
     let unit_text = match unit {
         InputLineUnit::Celcius => " °C",
         InputLineUnit::Porcentage => " %",
+        InputLineUnit::Second => " s",
+        InputLineUnit::Millisecond => " ms",
+        InputLineUnit::None => "",
     };
 
     let icon_lenght = Length::Fixed(30.0);
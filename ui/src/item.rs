@@ -1,4 +1,4 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
 use cosmic::{
     iced_core::{Alignment, Length, Padding},
@@ -13,11 +13,20 @@ use cosmic::{
 use data::{
     app_graph::Nodes,
     config::{
-        control::Control,
+        command_temp::CommandTemp,
+        control::{is_control_used, Control},
         custom_temp::{CustomTemp, CustomTempKind},
+        fan::Fan,
+        file_temp::FileTemp,
+        filter::{Filter, FilterKind, MAX_PERIOD},
         flat::Flat,
         linear::Linear,
+        mix::{Mix, MixKind},
+        pid::Pid,
+        sensor_policy::SensorStats,
         target::Target,
+        temp::Temp,
+        TEMP_RANGE,
     },
     node::{Input, Node, NodeTypeLight, ValueKind},
 };
@@ -28,10 +37,11 @@ use crate::{
     icon::{icon_button, icon_path_for_node_type, my_icon},
     input_line::{input_line, InputLineUnit},
     message::{
-        AppMsg, ControlMsg, CustomTempMsg, FlatMsg, LinearMsg, ModifNodeMsg, TargetMsg, ToogleMsg,
+        AppMsg, CommandTempMsg, ControlMsg, CustomTempMsg, FileTempMsg, FilterMsg, FlatMsg,
+        LinearMsg, MixMsg, ModifNodeMsg, PidMsg, TargetMsg, ToogleMsg,
     },
     my_widgets::{self, drop_down::DropDown, offset::Offset},
-    node_cache::{LinearC, NodeC, NodesC, TargetC},
+    node_cache::{CommandTempC, FileTempC, FilterC, LinearC, NodeC, NodesC, PidC, TargetC},
     pick_list_utils::{self, MyOption},
 };
 
@@ -39,6 +49,7 @@ pub fn items_view<'a>(
     nodes: &'a Nodes,
     nodes_c: &'a NodesC,
     hardware: &'a Hardware,
+    dry_run: bool,
 ) -> Element<'a, AppMsg> {
     let mut controls = Vec::new();
     let mut behaviors = Vec::new();
@@ -48,17 +59,21 @@ pub fn items_view<'a>(
 
     for node in nodes.values() {
         let node_c = nodes_c.get(&node.id);
-        let content = item_view(node, node_c, nodes, hardware);
+        let content = item_view(node, node_c, nodes, hardware, dry_run);
 
         match node.node_type.to_light() {
             NodeTypeLight::Control => controls.push(content),
             NodeTypeLight::Fan => fans.push(content),
-            NodeTypeLight::Temp => temps.push(content),
+            NodeTypeLight::Temp | NodeTypeLight::CommandTemp | NodeTypeLight::FileTemp => {
+                temps.push(content)
+            }
             NodeTypeLight::Graph
             | NodeTypeLight::Flat
             | NodeTypeLight::Linear
-            | NodeTypeLight::Target => behaviors.push(content),
-            NodeTypeLight::CustomTemp => custom_temps.push(content),
+            | NodeTypeLight::Target
+            | NodeTypeLight::Pid
+            | NodeTypeLight::Mix => behaviors.push(content),
+            NodeTypeLight::CustomTemp | NodeTypeLight::Filter => custom_temps.push(content),
         }
     }
 
@@ -106,6 +121,7 @@ fn item_view<'a>(
     node_c: &'a NodeC,
     nodes: &'a Nodes,
     hardware: &'a Hardware,
+    dry_run: bool,
 ) -> Element<'a, AppMsg> {
     let item_icon = my_icon(icon_path_for_node_type(&node.node_type.to_light()));
 
@@ -149,10 +165,23 @@ fn item_view<'a>(
         .align_items(Alignment::Center);
 
     let node_specific_content = match &node.node_type {
-        data::node::NodeType::Control(control) => control_view(node, control, nodes, hardware),
-        data::node::NodeType::Fan(_fan) => fan_view(node, hardware),
-        data::node::NodeType::Temp(_temp) => temp_view(node, hardware),
+        data::node::NodeType::Control(control) => {
+            control_view(node, control, nodes, hardware, dry_run)
+        }
+        data::node::NodeType::Fan(fan) => fan_view(node, fan, hardware),
+        data::node::NodeType::Temp(temp) => temp_view(node, temp, hardware),
+        data::node::NodeType::CommandTemp(command_temp) => command_temp_view(
+            node,
+            command_temp,
+            node_c.node_type_c.unwrap_command_temp_ref(),
+        ),
+        data::node::NodeType::FileTemp(file_temp) => {
+            file_temp_view(node, file_temp, node_c.node_type_c.unwrap_file_temp_ref())
+        }
         data::node::NodeType::CustomTemp(custom_temp) => custom_temp_view(node, custom_temp, nodes),
+        data::node::NodeType::Filter(filter) => {
+            filter_view(node, filter, node_c.node_type_c.unwrap_filter_ref(), nodes)
+        }
         data::node::NodeType::Graph(graph) => {
             graph_view(node, graph, node_c.node_type_c.unwrap_graph_ref(), nodes)
         }
@@ -163,6 +192,10 @@ fn item_view<'a>(
         data::node::NodeType::Target(target) => {
             target_view(node, target, node_c.node_type_c.unwrap_target_ref(), nodes)
         }
+        data::node::NodeType::Pid(pid) => {
+            pid_view(node, pid, node_c.node_type_c.unwrap_pid_ref(), nodes)
+        }
+        data::node::NodeType::Mix(mix) => mix_view(node, mix, nodes),
     };
 
     let content = Column::new()
@@ -180,12 +213,12 @@ fn item_view<'a>(
 
 fn pick_hardware<'a, H: HItem>(
     node: &'a Node,
-    hardwares: &'a [Rc<H>],
-    one_ref: bool,
+    hardwares: &'a [Arc<H>],
+    is_used: impl Fn(&H) -> bool,
 ) -> Element<'a, AppMsg> {
     let hardware_id = node.hardware_id().clone();
     let (selected_hardware_info, input_hardware) =
-        pick_list_utils::hardware::availlable_hardware(&hardware_id, hardwares, one_ref);
+        pick_list_utils::hardware::availlable_hardware(&hardware_id, hardwares, is_used);
 
     PickList::new(input_hardware, Some(selected_hardware_info), |selected| {
         let message_content = match selected {
@@ -204,6 +237,7 @@ fn control_view<'a>(
     control: &'a Control,
     nodes: &'a Nodes,
     hardware: &'a Hardware,
+    dry_run: bool,
 ) -> Element<'a, AppMsg> {
     let input_options =
         pick_list_utils::input::optional_availlable_inputs(nodes, node, control.input.is_some());
@@ -215,11 +249,19 @@ fn control_view<'a>(
     .width(Length::Fill)
     .into();
 
-    let content = vec![
-        pick_hardware(node, &hardware.controls, true),
+    // in a dry run, the value is the one which would be written
+    let value_text = match node.value {
+        Some(value) if dry_run => fl!("would_set", value = value.round()),
+        _ => node.value_text(&ValueKind::Porcentage),
+    };
+
+    let mut content = vec![
+        pick_hardware(node, &hardware.controls, |h| {
+            is_control_used(nodes, &h.hardware_id)
+        }),
         pick_input,
         Row::new()
-            .push(Text::new(node.value_text(&ValueKind::Porcentage)))
+            .push(Text::new(value_text))
             .push(Space::new(Length::Fill, Length::Fixed(0.0)))
             .push(Toggler::new(None, control.active, |is_active| {
                 ModifNodeMsg::Control(ControlMsg::Active(is_active)).to_app(node.id)
@@ -229,23 +271,109 @@ fn control_view<'a>(
             .into(),
     ];
 
+    // only shown when the firmware took the control back
+    if control.takeovers > 0 {
+        content.push(Text::new(fl!("takeovers", count = control.takeovers)).into());
+    }
+
     Column::with_children(content).into()
 }
 
-fn fan_view<'a>(node: &'a Node, hardware: &'a Hardware) -> Element<'a, AppMsg> {
-    let content = vec![
-        pick_hardware(node, &hardware.fans, false),
+/// Only shown when the sensor had problems
+fn sensor_stats_view<'a>(stats: &SensorStats) -> Option<Element<'a, AppMsg>> {
+    if !stats.is_flaky() {
+        return None;
+    }
+
+    let text = fl!(
+        "sensor_stats",
+        failures = stats.failures,
+        rejected = stats.rejected
+    );
+    Some(Text::new(text).into())
+}
+
+fn fan_view<'a>(node: &'a Node, fan: &'a Fan, hardware: &'a Hardware) -> Element<'a, AppMsg> {
+    let mut content = vec![
+        pick_hardware(node, &hardware.fans, |_| false),
         Text::new(node.value_text(&ValueKind::RPM)).into(),
     ];
+    content.extend(sensor_stats_view(&fan.health.stats));
 
     Column::with_children(content).into()
 }
 
-fn temp_view<'a>(node: &'a Node, hardware: &'a Hardware) -> Element<'a, AppMsg> {
-    let content = vec![
-        pick_hardware(node, &hardware.temps, false),
+fn temp_view<'a>(node: &'a Node, temp: &'a Temp, hardware: &'a Hardware) -> Element<'a, AppMsg> {
+    let mut content = vec![
+        pick_hardware(node, &hardware.temps, |_| false),
+        Text::new(node.value_text(&ValueKind::Celsius)).into(),
+    ];
+    content.extend(sensor_stats_view(&temp.health.stats));
+
+    Column::with_children(content).into()
+}
+
+fn command_temp_view<'a>(
+    node: &'a Node,
+    command_temp: &'a CommandTemp,
+    command_temp_c: &'a CommandTempC,
+) -> Element<'a, AppMsg> {
+    let command = TextInput::new(fl!("command"), &command_temp_c.command)
+        .on_input(|s| ModifNodeMsg::CommandTemp(CommandTempMsg::Command(s)).to_app(node.id))
+        .on_submit(ModifNodeMsg::CommandTemp(CommandTempMsg::Submit).to_app(node.id))
+        .width(Length::Fill);
+
+    let mut regex = TextInput::new(fl!("regex"), &command_temp_c.regex)
+        .on_input(|s| ModifNodeMsg::CommandTemp(CommandTempMsg::Regex(s)).to_app(node.id))
+        .on_submit(ModifNodeMsg::CommandTemp(CommandTempMsg::Submit).to_app(node.id))
+        .width(Length::Fill);
+
+    let mut json_pointer = TextInput::new(fl!("json_pointer"), &command_temp_c.json_pointer)
+        .on_input(|s| ModifNodeMsg::CommandTemp(CommandTempMsg::JsonPointer(s)).to_app(node.id))
+        .on_submit(ModifNodeMsg::CommandTemp(CommandTempMsg::Submit).to_app(node.id))
+        .width(Length::Fill);
+
+    // the parser is built when the node is submitted
+    if command_temp.parser.is_none() {
+        regex = regex.error("this value is invalid");
+        json_pointer = json_pointer.error("this value is invalid");
+    }
+
+    let mut content = vec![
+        command.into(),
+        regex.into(),
+        json_pointer.into(),
+        input_line(
+            fl!("timeout"),
+            &command_temp.timeout_ms,
+            &command_temp_c.timeout_ms,
+            InputLineUnit::Millisecond,
+            1..u64::MAX,
+            |val, cached_val| ModifNodeMsg::CommandTemp(CommandTempMsg::TimeoutMs(val, cached_val)),
+        )
+        .map(|m| m.to_app(node.id)),
+        Text::new(node.value_text(&ValueKind::Celsius)).into(),
+    ];
+    content.extend(sensor_stats_view(&command_temp.health.stats));
+
+    Column::with_children(content).into()
+}
+
+fn file_temp_view<'a>(
+    node: &'a Node,
+    file_temp: &'a FileTemp,
+    file_temp_c: &'a FileTempC,
+) -> Element<'a, AppMsg> {
+    let path = TextInput::new(fl!("path"), &file_temp_c.path)
+        .on_input(|s| ModifNodeMsg::FileTemp(FileTempMsg::Path(s)).to_app(node.id))
+        .on_submit(ModifNodeMsg::FileTemp(FileTempMsg::Submit).to_app(node.id))
+        .width(Length::Fill);
+
+    let mut content = vec![
+        path.into(),
         Text::new(node.value_text(&ValueKind::Celsius)).into(),
     ];
+    content.extend(sensor_stats_view(&file_temp.health.stats));
 
     Column::with_children(content).into()
 }
@@ -303,6 +431,51 @@ fn custom_temp_view<'a>(
     Column::with_children(content).into()
 }
 
+fn filter_view<'a>(
+    node: &'a Node,
+    filter: &'a Filter,
+    filter_c: &'a FilterC,
+    nodes: &'a Nodes,
+) -> Element<'a, AppMsg> {
+    let kind_options = FilterKind::VALUES
+        .iter()
+        .filter(|k| &filter.kind != *k)
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let pick_kind = PickList::new(kind_options, Some(filter.kind.clone()), |k| {
+        ModifNodeMsg::Filter(FilterMsg::Kind(k)).to_app(node.id)
+    })
+    .width(Length::Fill)
+    .into();
+
+    let input_options =
+        pick_list_utils::input::optional_availlable_inputs(nodes, node, filter.input.is_some());
+    let current_input: MyOption<Input> = filter.input.clone().into();
+    let pick_input = PickList::new(input_options, Some(current_input), |input| {
+        ModifNodeMsg::ReplaceInput(input.into()).to_app(node.id)
+    })
+    .width(Length::Fill)
+    .into();
+
+    let content = vec![
+        pick_kind,
+        pick_input,
+        input_line(
+            fl!("period"),
+            &filter.period,
+            &filter_c.period,
+            InputLineUnit::Second,
+            0.0..MAX_PERIOD,
+            |val, cached_val| ModifNodeMsg::Filter(FilterMsg::Period(val, cached_val)),
+        )
+        .map(|m| m.to_app(node.id)),
+        Text::new(node.value_text(&ValueKind::Celsius)).into(),
+    ];
+
+    Column::with_children(content).into()
+}
+
 fn flat_view<'a>(node: &'a Node, flat: &'a Flat) -> Element<'a, AppMsg> {
     let mut sub_button = icon_button("remove/24");
     if flat.value > 0 {
@@ -361,7 +534,7 @@ fn linear_view<'a>(
             &linear.min_temp,
             &linear_c.min_temp,
             InputLineUnit::Celcius,
-            0..linear.max_temp,
+            *TEMP_RANGE.start()..linear.max_temp,
             |val, cached_val| ModifNodeMsg::Linear(LinearMsg::MinTemp(val, cached_val)),
         )
         .map(|m| m.to_app(node.id)),
@@ -379,7 +552,7 @@ fn linear_view<'a>(
             &linear.max_temp,
             &linear_c.max_temp,
             InputLineUnit::Celcius,
-            linear.min_temp..255,
+            linear.min_temp..*TEMP_RANGE.end(),
             |val, cached_val| ModifNodeMsg::Linear(LinearMsg::MaxTemp(val, cached_val)),
         )
         .map(|m| m.to_app(node.id)),
@@ -420,7 +593,7 @@ fn target_view<'a>(
             &target.idle_temp,
             &target_c.idle_temp,
             InputLineUnit::Celcius,
-            0..255,
+            *TEMP_RANGE.start()..*TEMP_RANGE.end(),
             |val, cached_val| ModifNodeMsg::Target(TargetMsg::IdleTemp(val, cached_val)),
         )
         .map(|m| m.to_app(node.id)),
@@ -438,7 +611,7 @@ fn target_view<'a>(
             &target.load_temp,
             &target_c.load_temp,
             InputLineUnit::Celcius,
-            0..255,
+            *TEMP_RANGE.start()..*TEMP_RANGE.end(),
             |val, cached_val| ModifNodeMsg::Target(TargetMsg::LoadTemp(val, cached_val)),
         )
         .map(|m| m.to_app(node.id)),
@@ -455,3 +628,139 @@ fn target_view<'a>(
 
     Column::with_children(content).into()
 }
+
+fn mix_view<'a>(node: &'a Node, mix: &'a Mix, nodes: &'a Nodes) -> Element<'a, AppMsg> {
+    let kind_options = MixKind::VALUES
+        .iter()
+        .filter(|k| &mix.kind != *k)
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let pick_kind = PickList::new(kind_options, Some(mix.kind.clone()), |k| {
+        ModifNodeMsg::Mix(MixMsg::Kind(k)).to_app(node.id)
+    })
+    .width(Length::Fill)
+    .into();
+
+    let inputs = node.inputs.iter().map(|input| {
+        let mut row = Row::new().push(Text::new(input.name.clone()).width(Length::Fixed(100.0)));
+
+        // weights are edited in the config file
+        if mix.kind == MixKind::Weighted {
+            let weight = mix
+                .inputs
+                .iter()
+                .position(|name| name == &input.name)
+                .map_or(1.0, |index| mix.weight(index));
+            row = row.push(Text::new(format!("× {}", weight)));
+        }
+
+        row.push(Space::new(Length::Fill, Length::Fixed(0.0)))
+            .push(
+                icon_button("close/20")
+                    .on_press(ModifNodeMsg::RemoveInput(input.clone()).to_app(node.id)),
+            )
+            .align_items(Alignment::Center)
+            .into()
+    });
+
+    let input_options: Vec<Input> =
+        pick_list_utils::input::availlable_inputs(nodes, node).collect();
+
+    let current_input = Input {
+        id: Default::default(),
+        name: fl!("behavior_selection"),
+    };
+
+    let pick_input = PickList::new(input_options, Some(current_input), |input| {
+        ModifNodeMsg::AddInput(input).to_app(node.id)
+    })
+    .width(Length::Fill)
+    .into();
+
+    let content = vec![
+        pick_kind,
+        pick_input,
+        Column::with_children(inputs).into(),
+        Text::new(node.value_text(&ValueKind::Porcentage)).into(),
+    ];
+
+    Column::with_children(content).into()
+}
+
+fn pid_view<'a>(
+    node: &'a Node,
+    pid: &'a Pid,
+    pid_c: &'a PidC,
+    nodes: &'a Nodes,
+) -> Element<'a, AppMsg> {
+    let input_options =
+        pick_list_utils::input::optional_availlable_inputs(nodes, node, pid.input.is_some());
+    let current_input: MyOption<Input> = pid.input.clone().into();
+    let pick_input = PickList::new(input_options, Some(current_input), |input| {
+        ModifNodeMsg::ReplaceInput(input.into()).to_app(node.id)
+    })
+    .width(Length::Fill)
+    .into();
+
+    let content = vec![
+        pick_input,
+        Text::new(node.value_text(&ValueKind::Porcentage)).into(),
+        input_line(
+            fl!("setpoint"),
+            &pid.setpoint,
+            &pid_c.setpoint,
+            InputLineUnit::Celcius,
+            *TEMP_RANGE.start()..*TEMP_RANGE.end(),
+            |val, cached_val| ModifNodeMsg::Pid(PidMsg::Setpoint(val, cached_val)),
+        )
+        .map(|m| m.to_app(node.id)),
+        input_line(
+            fl!("kp"),
+            &pid.kp,
+            &pid_c.kp,
+            InputLineUnit::None,
+            0.0..1000.0,
+            |val, cached_val| ModifNodeMsg::Pid(PidMsg::Kp(val, cached_val)),
+        )
+        .map(|m| m.to_app(node.id)),
+        input_line(
+            fl!("ki"),
+            &pid.ki,
+            &pid_c.ki,
+            InputLineUnit::None,
+            0.0..1000.0,
+            |val, cached_val| ModifNodeMsg::Pid(PidMsg::Ki(val, cached_val)),
+        )
+        .map(|m| m.to_app(node.id)),
+        input_line(
+            fl!("kd"),
+            &pid.kd,
+            &pid_c.kd,
+            InputLineUnit::None,
+            0.0..1000.0,
+            |val, cached_val| ModifNodeMsg::Pid(PidMsg::Kd(val, cached_val)),
+        )
+        .map(|m| m.to_app(node.id)),
+        input_line(
+            fl!("min_speed"),
+            &pid.min_speed,
+            &pid_c.min_speed,
+            InputLineUnit::Porcentage,
+            0..101,
+            |val, cached_val| ModifNodeMsg::Pid(PidMsg::MinSpeed(val, cached_val)),
+        )
+        .map(|m| m.to_app(node.id)),
+        input_line(
+            fl!("max_speed"),
+            &pid.max_speed,
+            &pid_c.max_speed,
+            InputLineUnit::Porcentage,
+            0..101,
+            |val, cached_val| ModifNodeMsg::Pid(PidMsg::MaxSpeed(val, cached_val)),
+        )
+        .map(|m| m.to_app(node.id)),
+    ];
+
+    Column::with_children(content).into()
+}
@@ -3,13 +3,15 @@ use std::time::Duration;
 use data::{
     app_graph::AppGraph,
     config::Config,
-    node::{validate_name, IsValid, NodeType},
+    dir_manager::DirManager,
+    node::{validate_name, NodeType},
     settings::AppTheme,
+    update_loop::{UpdateCommand, UpdateEvent, UpdateLoop},
     utils::RemoveElem,
     AppState,
 };
 use graph::GraphWindow;
-use hardware::{HardwareBridge, Mode};
+use hardware::{Hardware, HardwareBridge};
 use item::items_view;
 use message::{ConfigMsg, ModifNodeMsg, SettingsMsg, ToogleMsg};
 use node_cache::{NodeC, NodesC};
@@ -30,7 +32,10 @@ use cosmic::{
     ApplicationExt, Element,
 };
 
-use crate::message::{AppMsg, ControlMsg, CustomTempMsg, FlatMsg, LinearMsg, TargetMsg};
+use crate::message::{
+    AppMsg, CommandTempMsg, ControlMsg, CustomTempMsg, FileTempMsg, FilterMsg, FlatMsg, LinearMsg,
+    MixMsg, PidMsg, TargetMsg,
+};
 
 use crate::add_node::add_node_button_view;
 
@@ -52,7 +57,7 @@ mod node_cache;
 mod pick_list_utils;
 mod settings_drawer;
 
-impl<H: HardwareBridge> CosmicFlags for Flags<H> {
+impl CosmicFlags for Flags {
     type SubCommand = String;
 
     type Args = Vec<String>;
@@ -61,34 +66,66 @@ impl<H: HardwareBridge> CosmicFlags for Flags<H> {
 pub fn run_ui<H: HardwareBridge + 'static>(app_state: AppState<H>) {
     let settings = cosmic::app::Settings::default();
 
-    let flags = Flags { app_state };
-
-    if let Err(e) = cosmic::app::run::<Ui<H>>(settings, flags) {
+    let AppState {
+        dir_manager,
+        bridge,
+        app_graph,
+        dry_run,
+        ..
+    } = app_state;
+
+    let hardware = bridge.hardware().clone();
+    let can_rescan = bridge.can_rescan();
+    let update_delay = Duration::from_millis(dir_manager.settings().update_delay);
+    let update_loop = UpdateLoop::spawn(bridge, app_graph.clone(), update_delay);
+
+    let flags = Flags {
+        dir_manager,
+        app_graph,
+        hardware,
+        update_loop,
+        dry_run,
+        can_rescan,
+    };
+
+    if let Err(e) = cosmic::app::run::<Ui>(settings, flags) {
         error!("error while running ui: {}", e);
         panic!()
     }
 }
 
-struct Flags<H: HardwareBridge> {
-    app_state: AppState<H>,
+struct Flags {
+    dir_manager: DirManager,
+    app_graph: AppGraph,
+    hardware: Hardware,
+    update_loop: UpdateLoop,
+    dry_run: bool,
+    can_rescan: bool,
 }
 
-struct Ui<H: HardwareBridge> {
+struct Ui {
     core: Core,
-    app_state: AppState<H>,
+    dir_manager: DirManager,
+    app_graph: AppGraph,
+    /// Hardware of the bridge, which is owned by the update thread
+    hardware: Hardware,
+    update_loop: UpdateLoop,
+    /// The update thread doesn't write to the hardware
+    dry_run: bool,
+    /// The bridge of the update thread can find new hardware
+    can_rescan: bool,
     current_config_cached: String,
     create_button_expanded: bool,
     choose_config_expanded: bool,
     nodes_c: NodesC,
-    is_updating: bool,
     graph_window: Option<GraphWindow>,
     toasts: Toasts<AppMsg>,
 }
 
-impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
+impl cosmic::Application for Ui {
     type Executor = executor::Default;
     type Message = AppMsg;
-    type Flags = Flags<H>;
+    type Flags = Flags;
 
     const APP_ID: &'static str = utils::APP_ID;
 
@@ -101,30 +138,30 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
     }
 
     fn init(core: Core, flags: Self::Flags) -> (Self, Command<Self::Message>) {
-        let app_state = flags.app_state;
-
-        let current_config_cached = app_state
+        let current_config_cached = flags
             .dir_manager
             .settings()
             .current_config_text()
             .to_owned();
 
         let ui_state = Ui {
-            nodes_c: NodesC::new(app_state.app_graph.nodes.values()),
-            app_state,
+            nodes_c: NodesC::new(flags.app_graph.nodes.values()),
+            dir_manager: flags.dir_manager,
+            app_graph: flags.app_graph,
+            hardware: flags.hardware,
+            update_loop: flags.update_loop,
+            dry_run: flags.dry_run,
+            can_rescan: flags.can_rescan,
             core,
             create_button_expanded: false,
             choose_config_expanded: false,
             current_config_cached,
-            is_updating: false,
             graph_window: None,
             toasts: Toasts::default(),
         };
 
         let commands = Command::batch([
-            command::set_theme(to_cosmic_theme(
-                &ui_state.app_state.dir_manager.settings().theme,
-            )),
+            command::set_theme(to_cosmic_theme(&ui_state.dir_manager.settings().theme)),
             cosmic::app::command::message(cosmic::app::message::app(AppMsg::Tick)),
         ]);
 
@@ -132,31 +169,73 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
     }
 
     fn update(&mut self, message: Self::Message) -> Command<Self::Message> {
-        let dir_manager = &mut self.app_state.dir_manager;
+        let dir_manager = &mut self.dir_manager;
 
         match message {
             AppMsg::Tick => {
-                self.update_hardware();
+                let mut commands = Vec::new();
+
+                for event in self.update_loop.events() {
+                    match event {
+                        UpdateEvent::Values(values) => {
+                            for (id, value) in values {
+                                if let Some(node) = self.app_graph.nodes.get_mut(&id) {
+                                    node.value = value;
+                                }
+                            }
+                        }
+                        UpdateEvent::SensorStats(stats) => {
+                            for (id, stats) in stats {
+                                let node = self.app_graph.nodes.get_mut(&id);
+                                match node.map(|node| &mut node.node_type) {
+                                    Some(NodeType::Temp(temp)) => temp.health.stats = stats,
+                                    Some(NodeType::Fan(fan)) => fan.health.stats = stats,
+                                    Some(NodeType::CommandTemp(command_temp)) => {
+                                        command_temp.health.stats = stats
+                                    }
+                                    Some(NodeType::FileTemp(file_temp)) => {
+                                        file_temp.health.stats = stats
+                                    }
+                                    _ => {}
+                                }
+                            }
+                        }
+                        UpdateEvent::Takeovers(takeovers) => {
+                            for (id, takeovers) in takeovers {
+                                let node = self.app_graph.nodes.get_mut(&id);
+                                if let Some(NodeType::Control(control)) =
+                                    node.map(|node| &mut node.node_type)
+                                {
+                                    control.takeovers = takeovers;
+                                }
+                            }
+                        }
+                        UpdateEvent::Rescanned { hardware, changes } => {
+                            self.hardware = hardware;
+                            self.app_graph.rebind_hardware(&self.hardware);
+
+                            if !changes.is_empty() {
+                                commands
+                                    .push(self.toasts.push(Toast::new(fl!("hardware_changed"))));
+                            }
+                        }
+                    }
+                }
+
+                return Command::batch(commands);
             }
 
             AppMsg::ModifNode(id, modif_node_msg) => {
-                let node = self.app_state.app_graph.get_mut(&id);
+                let node = self.app_graph.get_mut(&id);
                 match modif_node_msg {
                     ModifNodeMsg::ChangeHardware(hardware_id) => {
-                        let bridge = &mut self.app_state.bridge;
-
+                        // the update thread sets the previous hardware to auto
                         match &mut node.node_type {
                             NodeType::Control(i) => {
-                                if i.is_valid() {
-                                    if let Err(e) = i.set_mode(Mode::Auto, bridge) {
-                                        error!("Can't set control to auto when removing his hardware ref: {e}.");
-                                    }
-                                }
-
                                 i.hardware_id = hardware_id;
                                 i.control_h = match &i.hardware_id {
-                                    Some(hardware_id) => bridge
-                                        .hardware()
+                                    Some(hardware_id) => self
+                                        .hardware
                                         .controls
                                         .iter()
                                         .find(|h| &h.hardware_id == hardware_id)
@@ -168,8 +247,8 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
                             NodeType::Fan(i) => {
                                 i.hardware_id = hardware_id;
                                 i.fan_h = match &i.hardware_id {
-                                    Some(hardware_id) => bridge
-                                        .hardware()
+                                    Some(hardware_id) => self
+                                        .hardware
                                         .fans
                                         .iter()
                                         .find(|h| &h.hardware_id == hardware_id)
@@ -181,8 +260,8 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
                             NodeType::Temp(i) => {
                                 i.hardware_id = hardware_id;
                                 i.temp_h = match &i.hardware_id {
-                                    Some(hardware_id) => bridge
-                                        .hardware()
+                                    Some(hardware_id) => self
+                                        .hardware
                                         .temps
                                         .iter()
                                         .find(|h| &h.hardware_id == hardware_id)
@@ -207,9 +286,11 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
                         };
                         match &mut node.node_type {
                             NodeType::Control(i) => i.input = optional_name,
+                            NodeType::Filter(i) => i.input = optional_name,
                             NodeType::Graph(i) => i.input = optional_name,
                             NodeType::Linear(i, ..) => i.input = optional_name,
                             NodeType::Target(i, ..) => i.input = optional_name,
+                            NodeType::Pid(i) => i.input = optional_name,
                             _ => panic!("node have not exactly one input"),
                         }
                     }
@@ -218,6 +299,7 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
 
                         match &mut node.node_type {
                             NodeType::CustomTemp(i) => i.inputs.push(input.name),
+                            NodeType::Mix(i) => i.add_input(input.name),
                             _ => panic!("node have not multiple inputs"),
                         }
                     }
@@ -228,6 +310,7 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
                             NodeType::CustomTemp(i) => {
                                 i.inputs.remove_elem(|n| n == &input.name);
                             }
+                            NodeType::Mix(i) => i.remove_input(&input.name),
                             _ => panic!("node have not multiple inputs"),
                         }
                     }
@@ -237,12 +320,72 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
                             control.active = is_active;
                         }
                     },
+                    ModifNodeMsg::CommandTemp(command_temp_msg) => {
+                        let command_temp = node.node_type.unwrap_command_temp_mut();
+                        let command_temp_c = self
+                            .nodes_c
+                            .get_mut(&id)
+                            .node_type_c
+                            .unwrap_command_temp_mut();
+
+                        match command_temp_msg {
+                            CommandTempMsg::Command(command) => command_temp_c.command = command,
+                            CommandTempMsg::Regex(regex) => command_temp_c.regex = regex,
+                            CommandTempMsg::JsonPointer(json_pointer) => {
+                                command_temp_c.json_pointer = json_pointer
+                            }
+                            CommandTempMsg::TimeoutMs(timeout_ms, cached_value) => {
+                                command_temp.timeout_ms = timeout_ms;
+                                command_temp_c.timeout_ms = cached_value;
+                            }
+                            CommandTempMsg::Submit => {
+                                let non_empty = |s: &String| (!s.is_empty()).then(|| s.clone());
+                                command_temp.command = command_temp_c.command.clone();
+                                command_temp.regex = non_empty(&command_temp_c.regex);
+                                command_temp.json_pointer = non_empty(&command_temp_c.json_pointer);
+                                command_temp.compile_parser();
+                            }
+                        }
+                    }
+                    ModifNodeMsg::FileTemp(file_temp_msg) => {
+                        let file_temp = node.node_type.unwrap_file_temp_mut();
+                        let file_temp_c =
+                            self.nodes_c.get_mut(&id).node_type_c.unwrap_file_temp_mut();
+
+                        match file_temp_msg {
+                            FileTempMsg::Path(path) => file_temp_c.path = path,
+                            FileTempMsg::Submit => file_temp.path = file_temp_c.path.clone(),
+                        }
+                    }
                     ModifNodeMsg::CustomTemp(custom_temp_msg) => match custom_temp_msg {
                         CustomTempMsg::Kind(kind) => {
                             let custom_temp = node.node_type.unwrap_custom_temp_mut();
                             custom_temp.kind = kind;
                         }
                     },
+                    ModifNodeMsg::Mix(mix_msg) => match mix_msg {
+                        MixMsg::Kind(kind) => {
+                            let mix = node.node_type.unwrap_mix_mut();
+                            mix.kind = kind;
+                        }
+                    },
+                    ModifNodeMsg::Filter(filter_msg) => {
+                        let filter = node.node_type.unwrap_filter_mut();
+
+                        match filter_msg {
+                            FilterMsg::Kind(kind) => {
+                                filter.kind = kind;
+                            }
+                            FilterMsg::Period(period, cached_value) => {
+                                filter.period = period;
+                                self.nodes_c
+                                    .get_mut(&id)
+                                    .node_type_c
+                                    .unwrap_filter_mut()
+                                    .period = cached_value;
+                            }
+                        }
+                    }
                     ModifNodeMsg::Flat(flat_msg) => match flat_msg {
                         FlatMsg::Value(value) => {
                             let flat = node.node_type.unwrap_flat_mut();
@@ -296,22 +439,45 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
                             }
                         }
                     }
-                    ModifNodeMsg::Delete => {
-                        match self.app_state.app_graph.remove_node(id) {
-                            Some(mut node) => {
-                                if let NodeType::Control(control) = &mut node.node_type {
-                                    if let Err(e) =
-                                        control.set_mode(Mode::Auto, &mut self.app_state.bridge)
-                                    {
-                                        error!("can't set unactive when removing a control: {}", e);
-                                    }
-                                }
+                    ModifNodeMsg::Pid(pid_msg) => {
+                        let pid = node.node_type.unwrap_pid_mut();
+                        let pid_c = self.nodes_c.get_mut(&id).node_type_c.unwrap_pid_mut();
+
+                        match pid_msg {
+                            PidMsg::Setpoint(setpoint, cached_value) => {
+                                pid.setpoint = setpoint;
+                                pid_c.setpoint = cached_value;
+                            }
+                            PidMsg::Kp(kp, cached_value) => {
+                                pid.kp = kp;
+                                pid_c.kp = cached_value;
+                            }
+                            PidMsg::Ki(ki, cached_value) => {
+                                pid.ki = ki;
+                                pid_c.ki = cached_value;
+                            }
+                            PidMsg::Kd(kd, cached_value) => {
+                                pid.kd = kd;
+                                pid_c.kd = cached_value;
                             }
-                            None => error!("Node was not found when trying to remove it"),
+                            PidMsg::MinSpeed(min_speed, cached_value) => {
+                                pid.min_speed = min_speed;
+                                pid_c.min_speed = cached_value;
+                            }
+                            PidMsg::MaxSpeed(max_speed, cached_value) => {
+                                pid.max_speed = max_speed;
+                                pid_c.max_speed = cached_value;
+                            }
+                        }
+                    }
+                    ModifNodeMsg::Delete => {
+                        // the update thread sets a removed control to auto
+                        if self.app_graph.remove_node(id).is_none() {
+                            error!("Node was not found when trying to remove it");
                         }
 
                         self.nodes_c.remove(&id);
-                        self.app_state.app_graph.sanitize_inputs(false)
+                        self.app_graph.sanitize_inputs(false)
                     }
                     ModifNodeMsg::Graph(graph_msg) => {
                         let graph = node.node_type.unwrap_graph_mut();
@@ -331,11 +497,7 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
                     }
                 }
 
-                self.app_state.update.set_invalid_root_nodes_to_auto(
-                    &mut self.app_state.app_graph.nodes,
-                    &self.app_state.app_graph.root_nodes,
-                    &mut self.app_state.bridge,
-                );
+                self.send_graph();
             }
 
             AppMsg::Settings(settings_msg) => match settings_msg {
@@ -345,15 +507,20 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
                     });
                     return cosmic::app::command::set_theme(to_cosmic_theme(&theme));
                 }
-                SettingsMsg::UpdateDelay(update_delay) => dir_manager.update_settings(|settings| {
-                    settings.update_delay = update_delay;
-                }),
+                SettingsMsg::UpdateDelay(update_delay) => {
+                    dir_manager.update_settings(|settings| {
+                        settings.update_delay = update_delay;
+                    });
+                    self.update_loop
+                        .send(UpdateCommand::Delay(Duration::from_millis(update_delay)));
+                }
             },
             AppMsg::NewNode(node_type_light) => {
-                let node = self.app_state.app_graph.create_new_node(node_type_light);
+                let node = self.app_graph.create_new_node(node_type_light);
                 let node_c = NodeC::new(&node);
                 self.nodes_c.insert(node.id, node_c);
-                self.app_state.app_graph.insert_node(node);
+                self.app_graph.insert_node(node);
+                self.send_graph();
             }
             AppMsg::Toggle(ui_msg) => match ui_msg {
                 ToogleMsg::CreateButton(expanded) => self.create_button_expanded = expanded,
@@ -371,7 +538,7 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
             },
             AppMsg::Config(config_msg) => match config_msg {
                 ConfigMsg::Save => {
-                    let config = Config::from_app_graph(&self.app_state.app_graph);
+                    let config = Config::from_app_graph(&self.app_graph);
 
                     if let Err(e) = dir_manager.save_config(&self.current_config_cached, &config) {
                         error!("can't save config: {}", e);
@@ -382,23 +549,15 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
                 ConfigMsg::Change(selected) => {
                     self.choose_config_expanded = false;
 
-                    if selected.is_some() {
-                        self.app_state.update.set_valid_root_nodes_to_auto(
-                            &mut self.app_state.app_graph.nodes,
-                            &self.app_state.app_graph.root_nodes,
-                            &mut self.app_state.bridge,
-                        );
-                    }
-
                     match dir_manager.change_config(selected) {
                         Ok(config) => match config {
                             Some((config_name, config)) => {
                                 self.current_config_cached = config_name;
-                                self.app_state.app_graph =
-                                    AppGraph::from_config(config, self.app_state.bridge.hardware());
-                                self.nodes_c = NodesC::new(self.app_state.app_graph.nodes.values());
+                                // the update thread sets the controls of the previous config to auto
+                                self.app_graph = AppGraph::from_config(config, &self.hardware);
+                                self.nodes_c = NodesC::new(self.app_graph.nodes.values());
 
-                                self.update_hardware();
+                                self.send_graph();
                             }
                             None => {
                                 self.current_config_cached.clear();
@@ -420,7 +579,7 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
                     }
                 },
                 ConfigMsg::Create(new_name) => {
-                    let config = Config::from_app_graph(&self.app_state.app_graph);
+                    let config = Config::from_app_graph(&self.app_graph);
 
                     match dir_manager.create_config(&new_name, &config) {
                         Ok(_) => {
@@ -436,9 +595,9 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
                 }
             },
             AppMsg::Rename(id, name) => {
-                let name_is_valid = validate_name(&self.app_state.app_graph.nodes, &id, &name);
+                let name_is_valid = validate_name(&self.app_graph.nodes, &id, &name);
 
-                let node = self.app_state.app_graph.get_mut(&id);
+                let node = self.app_graph.get_mut(&id);
                 let node_c = self.nodes_c.get_mut(&id);
 
                 node_c.name.clone_from(&name);
@@ -451,7 +610,7 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
 
                     // find nodes that depend on node.id
                     // change the name in input and item.input
-                    for n in self.app_state.app_graph.nodes.values_mut() {
+                    for n in self.app_graph.nodes.values_mut() {
                         if let Some(node_input) = n
                             .inputs
                             .iter_mut()
@@ -471,6 +630,8 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
                             }
                         }
                     }
+
+                    self.send_graph();
                 } else {
                     node_c.is_error_name = true;
                 }
@@ -523,6 +684,7 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
                     }
                 }
             },
+            AppMsg::RescanHardware => self.update_loop.send(UpdateCommand::Rescan),
             AppMsg::Toast(inner) => {
                 self.toasts.handle_message(&inner);
             }
@@ -532,10 +694,12 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
     }
 
     fn view(&self) -> Element<Self::Message> {
-        let app_state = &self.app_state;
-        let app_graph = &app_state.app_graph;
-
-        let content = items_view(&app_graph.nodes, &self.nodes_c, app_state.bridge.hardware());
+        let content = items_view(
+            &self.app_graph.nodes,
+            &self.nodes_c,
+            &self.hardware,
+            self.dry_run,
+        );
 
         let floating_button = Column::new()
             .push(Space::new(0.0, Length::Fill))
@@ -552,23 +716,23 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
 
     fn header_center(&self) -> Vec<Element<Self::Message>> {
         headers::header_center(
-            &self.app_state.dir_manager,
+            &self.dir_manager,
             &self.current_config_cached,
             self.choose_config_expanded,
         )
     }
 
     fn header_end(&self) -> Vec<Element<Self::Message>> {
-        headers::header_end()
+        headers::header_end(self.can_rescan)
     }
 
     fn context_drawer(&self) -> Option<Element<Self::Message>> {
-        settings_drawer(self.core.window.show_context, &self.app_state.dir_manager)
+        settings_drawer(self.core.window.show_context, &self.dir_manager)
     }
 
     fn subscription(&self) -> iced::Subscription<Self::Message> {
         time::every(Duration::from_millis(
-            self.app_state.dir_manager.settings().update_delay,
+            self.dir_manager.settings().update_delay,
         ))
         .map(|_| AppMsg::Tick)
 
@@ -576,9 +740,7 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
     }
 
     fn on_app_exit(&mut self) -> Option<Self::Message> {
-        if let Err(e) = self.app_state.bridge.shutdown() {
-            error!("shutdown hardware: {}", e);
-        }
+        self.update_loop.shutdown();
         None
     }
 
@@ -591,7 +753,6 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
         if let Some(graph_window) = &self.graph_window {
             if graph_window.window_id == id {
                 let graph = self
-                    .app_state
                     .app_graph
                     .get(&graph_window.node_id)
                     .node_type
@@ -613,37 +774,10 @@ fn to_cosmic_theme(theme: &AppTheme) -> theme::Theme {
     }
 }
 
-impl<H: HardwareBridge> Ui<H> {
-    fn update_hardware(&mut self) {
-        if self.is_updating {
-            warn!("An update is already processing: skipping that one.");
-            return;
-        }
-
-        self.is_updating = true;
-
-        if let Err(e) = self.app_state.bridge.update() {
-            error!("{}", e);
-            self.is_updating = false;
-            return;
-        }
-        if let Err(e) = self.app_state.update.all(
-            &mut self.app_state.app_graph.nodes,
-            &mut self.app_state.bridge,
-        ) {
-            error!("{}", e);
-            self.is_updating = false;
-            return;
-        }
-
-        if let Err(e) = self.app_state.update.nodes_which_update_can_change(
-            &mut self.app_state.app_graph.nodes,
-            &mut self.app_state.bridge,
-        ) {
-            error!("{}", e);
-            self.is_updating = false;
-            return;
-        }
-        self.is_updating = false;
+impl Ui {
+    /// The update thread works on its own copy of the graph.
+    fn send_graph(&self) {
+        self.update_loop
+            .send(UpdateCommand::Graph(self.app_graph.clone()));
     }
 }
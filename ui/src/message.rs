@@ -1,6 +1,6 @@
 use cosmic::widget::toaster::ToastMessage;
 use data::{
-    config::{custom_temp::CustomTempKind, graph::Coord},
+    config::{custom_temp::CustomTempKind, filter::FilterKind, graph::Coord, mix::MixKind},
     id::Id,
     node::{Input, NodeTypeLight},
     settings::AppTheme,
@@ -25,6 +25,8 @@ pub enum AppMsg {
 
     GraphWindow(GraphWindowMsg),
 
+    RescanHardware,
+
     Toast(ToastMessage),
 }
 
@@ -60,10 +62,15 @@ pub enum ModifNodeMsg {
     RemoveInput(Input),
 
     Control(ControlMsg),
+    CommandTemp(CommandTempMsg),
+    FileTemp(FileTempMsg),
     CustomTemp(CustomTempMsg),
+    Filter(FilterMsg),
     Flat(FlatMsg),
     Linear(LinearMsg),
     Target(TargetMsg),
+    Pid(PidMsg),
+    Mix(MixMsg),
     Graph(GraphMsg),
 }
 
@@ -72,11 +79,36 @@ pub enum ControlMsg {
     Active(bool),
 }
 
+#[derive(Debug, Clone)]
+pub enum CommandTempMsg {
+    /// Only modify the cache, the command is run when submitted
+    Command(String),
+    /// Only modify the cache, the regex is compiled when submitted
+    Regex(String),
+    /// Only modify the cache, like the regex
+    JsonPointer(String),
+    TimeoutMs(u64, String),
+    Submit,
+}
+
+#[derive(Debug, Clone)]
+pub enum FileTempMsg {
+    /// Only modify the cache, the file is read when submitted
+    Path(String),
+    Submit,
+}
+
 #[derive(Debug, Clone)]
 pub enum CustomTempMsg {
     Kind(CustomTempKind),
 }
 
+#[derive(Debug, Clone)]
+pub enum FilterMsg {
+    Kind(FilterKind),
+    Period(f64, String),
+}
+
 #[derive(Debug, Clone)]
 pub enum FlatMsg {
     Value(u16),
@@ -84,20 +116,35 @@ pub enum FlatMsg {
 
 #[derive(Debug, Clone)]
 pub enum LinearMsg {
-    MinTemp(u8, String),
+    MinTemp(f64, String),
     MinSpeed(u8, String),
-    MaxTemp(u8, String),
+    MaxTemp(f64, String),
     MaxSpeed(u8, String),
 }
 
 #[derive(Debug, Clone)]
 pub enum TargetMsg {
-    IdleTemp(u8, String),
+    IdleTemp(f64, String),
     IdleSpeed(u8, String),
-    LoadTemp(u8, String),
+    LoadTemp(f64, String),
     LoadSpeed(u8, String),
 }
 
+#[derive(Debug, Clone)]
+pub enum MixMsg {
+    Kind(MixKind),
+}
+
+#[derive(Debug, Clone)]
+pub enum PidMsg {
+    Setpoint(f64, String),
+    Kp(f64, String),
+    Ki(f64, String),
+    Kd(f64, String),
+    MinSpeed(u8, String),
+    MaxSpeed(u8, String),
+}
+
 #[allow(clippy::enum_variant_names)]
 #[derive(Debug, Clone)]
 pub enum GraphMsg {
@@ -24,11 +24,16 @@ pub enum NodeTypeC {
     Control(ControlC),
     Fan(FanC),
     Temp(TempC),
+    CommandTemp(CommandTempC),
+    FileTemp(FileTempC),
     CustomTemp(CustomTempC),
+    Filter(FilterC),
     Graph(GraphC),
     Flat(FlatC),
     Linear(LinearC),
     Target(TargetC),
+    Pid(PidC),
+    Mix(MixC),
 }
 
 #[derive(Debug, Clone)]
@@ -40,6 +45,19 @@ pub struct FanC {}
 #[derive(Debug, Clone)]
 pub struct TempC {}
 
+#[derive(Debug, Clone)]
+pub struct CommandTempC {
+    pub command: String,
+    pub regex: String,
+    pub json_pointer: String,
+    pub timeout_ms: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct FileTempC {
+    pub path: String,
+}
+
 #[derive(Debug, Clone)]
 pub struct CustomTempC {}
 
@@ -65,6 +83,24 @@ pub struct TargetC {
     pub load_speed: String,
 }
 
+#[derive(Debug, Clone)]
+pub struct MixC {}
+
+#[derive(Debug, Clone)]
+pub struct FilterC {
+    pub period: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct PidC {
+    pub setpoint: String,
+    pub kp: String,
+    pub ki: String,
+    pub kd: String,
+    pub min_speed: String,
+    pub max_speed: String,
+}
+
 impl NodesC {
     pub fn new<'a>(nodes: impl Iterator<Item = &'a Node>) -> Self {
         let mut data = HashMap::new();
@@ -114,6 +150,17 @@ impl NodeTypeC {
             data::node::NodeType::Control(_) => NodeTypeC::Control(ControlC {}),
             data::node::NodeType::Fan(_) => NodeTypeC::Fan(FanC {}),
             data::node::NodeType::Temp(_) => NodeTypeC::Temp(TempC {}),
+            data::node::NodeType::CommandTemp(command_temp) => {
+                NodeTypeC::CommandTemp(CommandTempC {
+                    command: command_temp.command.clone(),
+                    regex: command_temp.regex.clone().unwrap_or_default(),
+                    json_pointer: command_temp.json_pointer.clone().unwrap_or_default(),
+                    timeout_ms: command_temp.timeout_ms.to_string(),
+                })
+            }
+            data::node::NodeType::FileTemp(file_temp) => NodeTypeC::FileTemp(FileTempC {
+                path: file_temp.path.clone(),
+            }),
             data::node::NodeType::CustomTemp(_) => NodeTypeC::CustomTemp(CustomTempC {}),
             data::node::NodeType::Graph(_) => NodeTypeC::Graph(GraphC {}),
             data::node::NodeType::Flat(_) => NodeTypeC::Flat(FlatC {}),
@@ -129,6 +176,18 @@ impl NodeTypeC {
                 load_temp: target.load_temp.to_string(),
                 load_speed: target.load_speed.to_string(),
             }),
+            data::node::NodeType::Filter(filter) => NodeTypeC::Filter(FilterC {
+                period: filter.period.to_string(),
+            }),
+            data::node::NodeType::Mix(_) => NodeTypeC::Mix(MixC {}),
+            data::node::NodeType::Pid(pid) => NodeTypeC::Pid(PidC {
+                setpoint: pid.setpoint.to_string(),
+                kp: pid.kp.to_string(),
+                ki: pid.ki.to_string(),
+                kd: pid.kd.to_string(),
+                min_speed: pid.min_speed.to_string(),
+                max_speed: pid.max_speed.to_string(),
+            }),
         }
     }
 }
@@ -128,7 +128,7 @@ pub mod hardware {
 
     use hardware::HItem;
     use std::fmt::Display;
-    use std::rc::Rc;
+    use std::sync::Arc;
 
     use super::MyOption;
 
@@ -150,8 +150,8 @@ pub mod hardware {
         }
     }
 
-    impl<H: HItem> From<&Rc<H>> for HardwarePickListOption {
-        fn from(value: &Rc<H>) -> Self {
+    impl<H: HItem> From<&Arc<H>> for HardwarePickListOption {
+        fn from(value: &Arc<H>) -> Self {
             Self {
                 name: value.name().clone(),
                 id: value.id().clone(),
@@ -163,8 +163,8 @@ pub mod hardware {
     /// availlable hardware
     pub fn availlable_hardware<'a, H: HItem>(
         hardware_id: &'a Option<String>,
-        hardwares: &'a [Rc<H>],
-        one_ref: bool,
+        hardwares: &'a [Arc<H>],
+        is_used: impl Fn(&H) -> bool,
     ) -> (
         MyOption<HardwarePickListOption>,
         Vec<MyOption<HardwarePickListOption>>,
@@ -186,12 +186,8 @@ pub mod hardware {
                     None => false,
                 };
 
-                if one_ref {
-                    // we leverage rc to know if this specific hardware
-                    // is already in use by one node
-                    if Rc::strong_count(h) > 1 {
-                        return None;
-                    }
+                if is_used(h) {
+                    return None;
                 }
 
                 // we only add if hardware_id != h